@@ -0,0 +1,6 @@
+// `sqlx::migrate!` embeds the migrations at compile time but doesn't track the
+// directories, so a new script would not be picked up without this.
+fn main() {
+    println!("cargo:rerun-if-changed=migrations");
+    println!("cargo:rerun-if-changed=migrations/down");
+}
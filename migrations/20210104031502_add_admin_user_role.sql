@@ -0,0 +1,4 @@
+ALTER TABLE `admin_user`
+  ADD COLUMN `role` TINYINT NOT NULL DEFAULT 0 COMMENT '角色，0：只读，1：商品编辑，2：价格管理，3：超级管理员' AFTER `password`;
+
+UPDATE `admin_user` SET `role` = 3 WHERE `username` = 'admin';
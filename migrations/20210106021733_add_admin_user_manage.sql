@@ -0,0 +1,3 @@
+ALTER TABLE `admin_user`
+  MODIFY COLUMN `status` TINYINT NOT NULL DEFAULT 0 COMMENT '状态，0：正常，1：已删除，2：已禁用',
+  ADD COLUMN `must_change_password` TINYINT NOT NULL DEFAULT 0 COMMENT '下次登录需修改密码，0：否，1：是' AFTER `role`;
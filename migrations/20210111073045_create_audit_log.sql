@@ -0,0 +1,13 @@
+CREATE TABLE `audit_log` (
+  `id` BIGINT UNSIGNED PRIMARY KEY NOT NULL AUTO_INCREMENT,
+  `operator` VARCHAR(32) NOT NULL DEFAULT '' COMMENT '操作人',
+  `action` VARCHAR(32) NOT NULL DEFAULT '' COMMENT '操作，create/update/delete 等',
+  `entity_type` VARCHAR(32) NOT NULL DEFAULT '' COMMENT '实体类型，brand/product/hot_product',
+  `entity_id` BIGINT UNSIGNED NOT NULL DEFAULT 0 COMMENT '实体ID',
+  `before_data` JSON NULL COMMENT '修改前快照',
+  `after_data` JSON NULL COMMENT '修改后快照',
+  `diff` JSON NULL COMMENT '变更字段',
+  `created_at` TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  KEY `idx_entity` (`entity_type`, `entity_id`),
+  KEY `idx_operator` (`operator`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4 COLLATE=utf8mb4_unicode_ci COMMENT='操作审计表';
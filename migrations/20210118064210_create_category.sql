@@ -0,0 +1,24 @@
+CREATE TABLE `category` (
+  `id` BIGINT UNSIGNED PRIMARY KEY NOT NULL AUTO_INCREMENT,
+  `parent_id` BIGINT UNSIGNED NOT NULL DEFAULT 0 COMMENT '父分类 category.id，0：顶级分类',
+  `path` VARCHAR(255) NOT NULL DEFAULT '' COMMENT '分类路径，如 /1/5/',
+  `name` VARCHAR(64) NOT NULL DEFAULT '' COMMENT '分类名称',
+  `sequence` INT NOT NULL DEFAULT 0 COMMENT '排序序号',
+  `status` TINYINT NOT NULL DEFAULT 0 COMMENT '状态，0：默认，1：已删除',
+  `creator` VARCHAR(32) NOT NULL DEFAULT '',
+  `modifier` VARCHAR(32) NOT NULL DEFAULT '',
+  `created_at` TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  `updated_at` TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+  KEY `idx_parent` (`parent_id`),
+  KEY `idx_path` (`path`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4 COLLATE=utf8mb4_unicode_ci COMMENT='商品分类表';
+
+CREATE TABLE `product_category` (
+  `id` BIGINT UNSIGNED PRIMARY KEY NOT NULL AUTO_INCREMENT,
+  `product_id` BIGINT UNSIGNED NOT NULL DEFAULT 0 COMMENT '商品ID product.id',
+  `category_id` BIGINT UNSIGNED NOT NULL DEFAULT 0 COMMENT '分类ID category.id',
+  `creator` VARCHAR(32) NOT NULL DEFAULT '',
+  `created_at` TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  UNIQUE KEY `udx_pid_cid` (`product_id`, `category_id`),
+  KEY `idx_cid` (`category_id`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4 COLLATE=utf8mb4_unicode_ci COMMENT='商品分类关联表';
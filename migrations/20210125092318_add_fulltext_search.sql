@@ -0,0 +1,5 @@
+ALTER TABLE `product`
+  ADD FULLTEXT INDEX `ft_product` (`name`, `alias`, `title`, `subtitle`) WITH PARSER ngram;
+
+ALTER TABLE `brand`
+  ADD FULLTEXT INDEX `ft_brand` (`name`) WITH PARSER ngram;
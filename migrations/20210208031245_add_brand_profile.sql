@@ -0,0 +1,6 @@
+ALTER TABLE `brand`
+  ADD COLUMN `en_name` VARCHAR(128) NOT NULL DEFAULT '' COMMENT '英文名称' AFTER `name`,
+  ADD COLUMN `logo_url` VARCHAR(255) NOT NULL DEFAULT '' COMMENT '品牌标志地址' AFTER `en_name`,
+  ADD COLUMN `country` VARCHAR(64) NOT NULL DEFAULT '' COMMENT '品牌所属国家或地区' AFTER `logo_url`,
+  ADD COLUMN `description` VARCHAR(1024) NOT NULL DEFAULT '' COMMENT '品牌简介' AFTER `country`,
+  ADD COLUMN `website` VARCHAR(255) NOT NULL DEFAULT '' COMMENT '品牌官网' AFTER `description`;
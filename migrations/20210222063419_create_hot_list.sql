@@ -0,0 +1,21 @@
+CREATE TABLE `hot_list` (
+  `id` BIGINT UNSIGNED PRIMARY KEY NOT NULL AUTO_INCREMENT,
+  `slot` VARCHAR(32) NOT NULL DEFAULT '' COMMENT '展示位，如 home、new_arrivals',
+  `start_at` DATETIME NOT NULL COMMENT '生效时间',
+  `end_at` DATETIME NULL DEFAULT NULL COMMENT '失效时间，空：一直有效',
+  `status` TINYINT NOT NULL DEFAULT 0 COMMENT '状态，0：正常，1：已删除',
+  `creator` VARCHAR(32) NOT NULL DEFAULT '',
+  `modifier` VARCHAR(32) NOT NULL DEFAULT '',
+  `created_at` TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  `updated_at` TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+  KEY `idx_slot_start` (`slot`, `start_at`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4 COLLATE=utf8mb4_unicode_ci COMMENT='热门商品排期表';
+
+ALTER TABLE `hot_product`
+  ADD COLUMN `list_id` BIGINT UNSIGNED NOT NULL DEFAULT 0 COMMENT '排期ID hot_list.id' AFTER `id`,
+  ADD COLUMN `sequence` INT NOT NULL DEFAULT 0 COMMENT '排序序号' AFTER `product_id`,
+  ADD KEY `idx_lid` (`list_id`);
+
+-- the current global list becomes the home slot
+INSERT INTO `hot_list` (`slot`, `start_at`, `creator`) VALUES ('home', '2020-01-01 00:00:00', 'migration');
+UPDATE `hot_product` SET `list_id` = LAST_INSERT_ID(), `sequence` = `id` WHERE `status` = 0;
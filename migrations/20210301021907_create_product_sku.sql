@@ -0,0 +1,30 @@
+CREATE TABLE `product_sku` (
+  `id` BIGINT UNSIGNED PRIMARY KEY NOT NULL AUTO_INCREMENT,
+  `product_id` BIGINT UNSIGNED NOT NULL DEFAULT 0 COMMENT '商品ID product.id',
+  `spec` VARCHAR(128) NOT NULL DEFAULT '' COMMENT '规格，如 30ml',
+  `kind` TINYINT NOT NULL DEFAULT 0 COMMENT '类型，0：正装，1：小样',
+  `barcode` VARCHAR(32) NOT NULL DEFAULT '' COMMENT '商品条码',
+  `sell_price` DECIMAL(9,2) NOT NULL DEFAULT 0 COMMENT '售价',
+  `import_price` DECIMAL(9,2) NOT NULL DEFAULT 0 COMMENT '参考进货价',
+  `sequence` INT NOT NULL DEFAULT 0 COMMENT '排序序号，第一个为默认规格',
+  `status` TINYINT NOT NULL DEFAULT 0 COMMENT '状态，0：在售，1：已删除，2：停售',
+  `creator` VARCHAR(32) NOT NULL DEFAULT '',
+  `modifier` VARCHAR(32) NOT NULL DEFAULT '',
+  `created_at` TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  `updated_at` TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+  KEY `idx_pid` (`product_id`),
+  KEY `idx_barcode` (`barcode`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4 COLLATE=utf8mb4_unicode_ci COMMENT='商品规格表';
+
+-- every product starts with its own spec and prices as the default sku
+INSERT INTO `product_sku` (`product_id`, `spec`, `kind`, `sell_price`, `import_price`, `sequence`, `creator`)
+SELECT `id`, `spec`, `kind`, `sell_price`, `import_price`, 1, 'migration' FROM `product`;
+
+ALTER TABLE `price_history`
+  ADD COLUMN `sku_id` BIGINT UNSIGNED NOT NULL DEFAULT 0 COMMENT '规格ID product_sku.id' AFTER `product_id`,
+  ADD KEY `idx_sid` (`sku_id`);
+
+UPDATE `price_history` h
+JOIN `product_sku` s
+ON s.product_id = h.product_id
+SET h.sku_id = s.id;
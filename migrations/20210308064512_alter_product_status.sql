@@ -0,0 +1,3 @@
+-- 0 and 1 keep their meaning from CommonStatus, rows already stored stay the same
+ALTER TABLE `product`
+  MODIFY COLUMN `status` TINYINT NOT NULL DEFAULT 0 COMMENT '状态，0：已上架，1：已删除，2：已下架，3：草稿';
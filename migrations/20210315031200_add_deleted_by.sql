@@ -0,0 +1,14 @@
+-- who deleted a row and when, `modifier` and `updated_at` change with every later write
+ALTER TABLE `brand`
+  ADD COLUMN `deleted_by` VARCHAR(32) NOT NULL DEFAULT '' COMMENT '删除人' AFTER `modifier`,
+  ADD COLUMN `deleted_at` TIMESTAMP NULL DEFAULT NULL COMMENT '删除时间' AFTER `deleted_by`;
+
+ALTER TABLE `product`
+  ADD COLUMN `deleted_by` VARCHAR(32) NOT NULL DEFAULT '' COMMENT '删除人' AFTER `modifier`,
+  ADD COLUMN `deleted_at` TIMESTAMP NULL DEFAULT NULL COMMENT '删除时间' AFTER `deleted_by`;
+
+-- the best guess for rows deleted before, `updated_at` is kept as it is
+UPDATE `brand` SET `deleted_by` = `modifier`, `deleted_at` = `updated_at`, `updated_at` = `updated_at`
+WHERE `status` = 1;
+UPDATE `product` SET `deleted_by` = `modifier`, `deleted_at` = `updated_at`, `updated_at` = `updated_at`
+WHERE `status` = 1;
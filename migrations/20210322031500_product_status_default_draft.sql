@@ -0,0 +1,3 @@
+-- a product inserted without a status is a draft, as the api creates it
+ALTER TABLE `product`
+  MODIFY COLUMN `status` TINYINT NOT NULL DEFAULT 3 COMMENT '状态，0：已上架，1：已删除，2：已下架，3：草稿';
@@ -0,0 +1,6 @@
+-- disabled users have to stay out once the status is back to two values
+UPDATE `admin_user` SET `status` = 1 WHERE `status` = 2;
+
+ALTER TABLE `admin_user`
+  MODIFY COLUMN `status` TINYINT NOT NULL DEFAULT 0 COMMENT '状态，0：默认，1：已删除',
+  DROP COLUMN `must_change_password`;
@@ -0,0 +1,3 @@
+DROP TABLE `product_category`;
+
+DROP TABLE `category`;
@@ -0,0 +1,5 @@
+ALTER TABLE `brand`
+  DROP INDEX `ft_brand`;
+
+ALTER TABLE `product`
+  DROP INDEX `ft_product`;
@@ -0,0 +1,6 @@
+ALTER TABLE `brand`
+  DROP COLUMN `website`,
+  DROP COLUMN `description`,
+  DROP COLUMN `country`,
+  DROP COLUMN `logo_url`,
+  DROP COLUMN `en_name`;
@@ -0,0 +1 @@
+DROP TABLE `hot_brand`;
@@ -0,0 +1,16 @@
+-- only the products of the live home list stay in the global list
+UPDATE `hot_product` SET `status` = 1
+WHERE `list_id` <> COALESCE((
+  SELECT `id` FROM `hot_list`
+  WHERE `slot` = 'home' AND `status` = 0 AND `start_at` <= NOW()
+  AND (`end_at` IS NULL OR `end_at` > NOW())
+  ORDER BY `start_at` DESC, `id` DESC
+  LIMIT 1
+), 0);
+
+ALTER TABLE `hot_product`
+  DROP KEY `idx_lid`,
+  DROP COLUMN `sequence`,
+  DROP COLUMN `list_id`;
+
+DROP TABLE `hot_list`;
@@ -0,0 +1,5 @@
+ALTER TABLE `price_history`
+  DROP KEY `idx_sid`,
+  DROP COLUMN `sku_id`;
+
+DROP TABLE `product_sku`;
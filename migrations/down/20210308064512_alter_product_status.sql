@@ -0,0 +1,5 @@
+-- drafts were never public, they go back as off shelf
+UPDATE `product` SET `status` = 2 WHERE `status` = 3;
+
+ALTER TABLE `product`
+  MODIFY COLUMN `status` TINYINT NOT NULL DEFAULT 0 COMMENT '状态，0：默认，1：已上架，2：已下架';
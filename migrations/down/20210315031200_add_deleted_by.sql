@@ -0,0 +1,2 @@
+ALTER TABLE `brand` DROP COLUMN `deleted_by`, DROP COLUMN `deleted_at`;
+ALTER TABLE `product` DROP COLUMN `deleted_by`, DROP COLUMN `deleted_at`;
@@ -1,19 +1,28 @@
+use std::net::{IpAddr, SocketAddr};
+use warp::hyper::body::Bytes;
+use warp::multipart::FormData;
 use warp::Filter;
 
 use crate::environment::Environment;
 use crate::handlers;
-use crate::helpers::problem;
-use crate::models::admin::UpdatePassword;
-use crate::models::admin::{AdminLoginRequest, AdminUser};
+use crate::helpers::{problem, upload};
+use crate::models::admin::{AdminLoginRequest, AdminUser, NewAdminUser, Permission};
+use crate::models::admin::{RefreshTokenRequest, ResetPassword, UpdateAdminUser};
+use crate::models::admin::{UpdatePassword, UserStatus};
+use crate::models::audit::AuditQuery;
 use crate::models::cosmetics::*;
-use crate::models::Paging;
+use crate::models::{AuthError, Paging};
 
 pub fn admin_filters(
     env: Environment,
 ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
     admin_login(env.clone())
+        .or(admin_refresh_token(env.clone()))
+        .or(admin_logout(env.clone()))
         .or(admin_cosmetics(env.clone()))
         .or(admin_create_user(env.clone()))
+        .or(admin_users(env.clone()))
+        .or(admin_audit_logs(env.clone()))
         .or(admin_current_user(env.clone()))
         .or(admin_update_password(env.clone()))
 }
@@ -21,14 +30,84 @@ pub fn admin_filters(
 fn admin_login(
     env: Environment,
 ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    let addr = client_addr(env.clone());
     let env = warp::any().map(move || env.clone());
     warp::path!("admin" / "api" / "v1" / "login")
         .and(warp::post())
         .and(env.clone())
+        .and(addr)
         .and(warp::body::content_length_limit(1024))
         .and(warp::body::json())
-        .and_then(|env: Environment, req: AdminLoginRequest| async move {
-            handlers::admin::login_handler(env, req)
+        .and_then(
+            |env: Environment, addr: String, req: AdminLoginRequest| async move {
+                handlers::admin::login_handler(env, addr, req)
+                    .await
+                    .map_err(problem::build)
+            },
+        )
+}
+
+// the forwarding headers are only believed when the direct peer is one of the
+// configured proxies, anyone else could send them to dodge the login throttle
+fn client_addr(
+    env: Environment,
+) -> impl Filter<Extract = (String,), Error = std::convert::Infallible> + Clone {
+    warp::header::optional::<String>("x-real-ip")
+        .and(warp::header::optional::<String>("x-forwarded-for"))
+        .and(warp::addr::remote())
+        .map(
+            move |real_ip: Option<String>,
+                  forwarded: Option<String>,
+                  remote: Option<SocketAddr>| {
+                let peer = match remote {
+                    Some(addr) => addr.ip(),
+                    None => return "unknown".to_owned(),
+                };
+                let trusted = env.trusted_proxies();
+                if !trusted.contains(&peer) {
+                    return peer.to_string();
+                }
+                real_ip
+                    .and_then(|ip| ip.trim().parse::<IpAddr>().ok())
+                    .or_else(|| {
+                        // every proxy appends the address it got the request from,
+                        // the right-most one not added by our own proxies is the client
+                        forwarded.and_then(|f| {
+                            f.rsplit(',')
+                                .filter_map(|ip| ip.trim().parse::<IpAddr>().ok())
+                                .find(|ip| !trusted.contains(ip))
+                        })
+                    })
+                    .unwrap_or(peer)
+                    .to_string()
+            },
+        )
+}
+
+fn admin_refresh_token(
+    env: Environment,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    let env = warp::any().map(move || env.clone());
+    warp::path!("admin" / "api" / "v1" / "token" / "refresh")
+        .and(warp::post())
+        .and(env.clone())
+        .and(warp::body::content_length_limit(1024))
+        .and(warp::body::json())
+        .and_then(|env: Environment, req: RefreshTokenRequest| async move {
+            handlers::admin::refresh_token_handler(env, req)
+                .await
+                .map_err(problem::build)
+        })
+}
+
+fn admin_logout(
+    env: Environment,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("admin" / "api" / "v1" / "logout")
+        .and(warp::post())
+        .and(with_auth(env.clone()))
+        .and_then(|env: Environment, user: AdminUser| async move {
+            handlers::admin::logout_handler(env, user)
                 .await
                 .map_err(problem::build)
         })
@@ -43,22 +122,40 @@ fn with_auth(
         .and_then(|jwt_raw: Option<String>, env: Environment| async move {
             env.jwt()
                 .decode_to_admin_user(jwt_raw)
+                .await
                 .map_err(problem::build)
         });
 
     warp::any().and(env.clone()).and(auth)
 }
 
+fn with_permission(
+    env: Environment,
+    permission: Permission,
+) -> impl Filter<Extract = (Environment, AdminUser), Error = warp::Rejection> + Clone {
+    with_auth(env)
+        .and_then(move |env: Environment, user: AdminUser| async move {
+            if user.must_change_password {
+                return Err(problem::build(AuthError::PasswordChangeRequired));
+            }
+            user.role
+                .require(permission)
+                .map(|_| (env, user))
+                .map_err(problem::build)
+        })
+        .untuple_one()
+}
+
 fn admin_create_user(
     env: Environment,
 ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
     warp::path!("admin" / "gen")
         .and(warp::post())
-        .and(with_auth(env.clone()))
+        .and(with_permission(env.clone(), Permission::ManageUsers))
         .and(warp::body::content_length_limit(1024))
         .and(warp::body::json())
         .and_then(
-            |env: Environment, user: AdminUser, req: AdminLoginRequest| async move {
+            |env: Environment, user: AdminUser, req: NewAdminUser| async move {
                 handlers::admin::create_user_handler(env, user, req)
                     .await
                     .map_err(problem::build)
@@ -66,6 +163,135 @@ fn admin_create_user(
         )
 }
 
+fn admin_users(
+    env: Environment,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    // user management api
+    let prefix = warp::path!("admin" / "api" / "v1" / "users" / ..);
+
+    // GET /../users
+    let get_users = warp::path::end()
+        .and(warp::get())
+        .and(with_permission(env.clone(), Permission::ManageUsers))
+        .and(warp::query::<Paging>())
+        .and_then(
+            |env: Environment, _user: AdminUser, paging: Paging| async move {
+                handlers::admin::get_users_handler(env, paging)
+                    .await
+                    .map_err(problem::build)
+            },
+        );
+
+    // POST /../users
+    let create_user = warp::path::end()
+        .and(warp::post())
+        .and(with_permission(env.clone(), Permission::ManageUsers))
+        .and(warp::body::content_length_limit(1024))
+        .and(warp::body::json())
+        .and_then(
+            |env: Environment, user: AdminUser, req: NewAdminUser| async move {
+                handlers::admin::create_user_handler(env, user, req)
+                    .await
+                    .map_err(problem::build)
+            },
+        );
+
+    // GET /../users/{id}
+    let get_user = warp::path!(u64)
+        .and(warp::get())
+        .and(with_permission(env.clone(), Permission::ManageUsers))
+        .and_then(|id: u64, env: Environment, _user: AdminUser| async move {
+            handlers::admin::get_user_handler(env, id)
+                .await
+                .map_err(problem::build)
+        });
+
+    // PUT /../users/{id}
+    let update_user = warp::path!(u64)
+        .and(warp::put())
+        .and(with_permission(env.clone(), Permission::ManageUsers))
+        .and(warp::body::content_length_limit(1024))
+        .and(warp::body::json())
+        .and_then(
+            |id: u64, env: Environment, user: AdminUser, req: UpdateAdminUser| async move {
+                handlers::admin::update_user_handler(env, user, id, req)
+                    .await
+                    .map_err(problem::build)
+            },
+        );
+
+    // PUT /../users/{id}/disable
+    let disable_user = warp::path!(u64 / "disable")
+        .and(warp::put())
+        .and(with_permission(env.clone(), Permission::ManageUsers))
+        .and_then(|id: u64, env: Environment, user: AdminUser| async move {
+            handlers::admin::update_user_status_handler(env, user, id, UserStatus::Disabled)
+                .await
+                .map_err(problem::build)
+        });
+
+    // PUT /../users/{id}/enable
+    let enable_user = warp::path!(u64 / "enable")
+        .and(warp::put())
+        .and(with_permission(env.clone(), Permission::ManageUsers))
+        .and_then(|id: u64, env: Environment, user: AdminUser| async move {
+            handlers::admin::update_user_status_handler(env, user, id, UserStatus::Active)
+                .await
+                .map_err(problem::build)
+        });
+
+    // PUT /../users/{id}/password
+    let reset_password = warp::path!(u64 / "password")
+        .and(warp::put())
+        .and(with_permission(env.clone(), Permission::ManageUsers))
+        .and(warp::body::content_length_limit(1024))
+        .and(warp::body::json())
+        .and_then(
+            |id: u64, env: Environment, user: AdminUser, req: ResetPassword| async move {
+                handlers::admin::reset_password_handler(env, user, id, req)
+                    .await
+                    .map_err(problem::build)
+            },
+        );
+
+    // DELETE /../users/{id}
+    let delete_user = warp::path!(u64)
+        .and(warp::delete())
+        .and(with_permission(env.clone(), Permission::ManageUsers))
+        .and_then(|id: u64, env: Environment, user: AdminUser| async move {
+            handlers::admin::update_user_status_handler(env, user, id, UserStatus::Deleted)
+                .await
+                .map_err(problem::build)
+        });
+
+    prefix.and(
+        get_users
+            .or(create_user)
+            .or(get_user)
+            .or(update_user)
+            .or(disable_user)
+            .or(enable_user)
+            .or(reset_password)
+            .or(delete_user),
+    )
+}
+
+fn admin_audit_logs(
+    env: Environment,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("admin" / "api" / "v1" / "audit")
+        .and(warp::get())
+        .and(with_permission(env.clone(), Permission::ReadCatalog))
+        .and(warp::query::<AuditQuery>())
+        .and_then(
+            |env: Environment, _user: AdminUser, query: AuditQuery| async move {
+                handlers::audit::get_audit_logs(env, query)
+                    .await
+                    .map_err(problem::build)
+            },
+        )
+}
+
 fn admin_current_user(
     env: Environment,
 ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
@@ -87,12 +313,11 @@ fn admin_update_password(
     warp::path!("admin" / "api" / "v1" / "password")
         .and(warp::put())
         .and(with_auth(env.clone()))
-        .and(warp::header::<String>("authorization"))
         .and(warp::body::content_length_limit(1024))
         .and(warp::body::json())
         .and_then(
-            |env: Environment, user: AdminUser, jwt, req: UpdatePassword| async move {
-                handlers::admin::update_password_handler(env, user, jwt, req)
+            |env: Environment, user: AdminUser, req: UpdatePassword| async move {
+                handlers::admin::update_password_handler(env, user, req)
                     .await
                     .map_err(problem::build)
             },
@@ -111,7 +336,7 @@ fn admin_cosmetics(
     let create_brands = warp::path!("brands")
         .and(warp::path::end())
         .and(warp::post())
-        .and(with_auth(env.clone()))
+        .and(with_permission(env.clone(), Permission::WriteCatalog))
         .and(warp::body::content_length_limit(10240))
         .and(warp::body::json())
         .and_then(
@@ -126,7 +351,7 @@ fn admin_cosmetics(
     let get_brands = warp::path!("brands")
         .and(warp::path::end())
         .and(warp::get())
-        .and(with_auth(env.clone()))
+        .and(with_permission(env.clone(), Permission::ReadCatalog))
         .and_then(|env: Environment, _user: AdminUser| async move {
             handlers::cosmetics::get_all_brands(env)
                 .await
@@ -137,7 +362,7 @@ fn admin_cosmetics(
     let update_brands_sequence = warp::path!("brands" / "sequence")
         .and(warp::path::end())
         .and(warp::put())
-        .and(with_auth(env.clone()))
+        .and(with_permission(env.clone(), Permission::WriteCatalog))
         .and(warp::body::content_length_limit(10240))
         .and(warp::body::json())
         .and_then(
@@ -148,27 +373,104 @@ fn admin_cosmetics(
             },
         );
 
+    // POST /../brand/hot
+    let set_hot_brands = warp::path!("brand" / "hot")
+        .and(warp::post())
+        .and(with_permission(env.clone(), Permission::WriteCatalog))
+        .and(warp::body::content_length_limit(4096))
+        .and(warp::body::json())
+        .and_then(
+            |env: Environment, user: AdminUser, ids: Vec<u64>| async move {
+                handlers::cosmetics::set_hot_brands(env, ids, user.username.as_str())
+                    .await
+                    .map_err(problem::build)
+            },
+        );
+
+    // GET /../brand/hot
+    let get_hot_brands = warp::path!("brand" / "hot")
+        .and(warp::get())
+        .and(with_permission(env.clone(), Permission::ReadCatalog))
+        .and_then(|env: Environment, _user: AdminUser| async move {
+            handlers::cosmetics::get_hot_brands(env)
+                .await
+                .map_err(problem::build)
+        });
+
+    // PUT /../brand/{id}
+    let update_brand = warp::path!("brand" / u64)
+        .and(warp::put())
+        .and(with_permission(env.clone(), Permission::WriteCatalog))
+        .and(warp::body::content_length_limit(4096))
+        .and(warp::body::json())
+        .and_then(
+            |id: u64, env: Environment, user: AdminUser, brand: UpdateBrand| async move {
+                handlers::cosmetics::update_brand(env, id, brand, user.username.as_str())
+                    .await
+                    .map_err(problem::build)
+            },
+        );
+
     // DELETE /../brand/{id}
     let delete_brand = warp::path!("brand" / u32)
         .and(warp::delete())
-        .and(with_auth(env.clone()))
+        .and(with_permission(env.clone(), Permission::DeleteCatalog))
         .and_then(|id: u32, env: Environment, user: AdminUser| async move {
             handlers::cosmetics::delete_brand(env, id, user.username.as_str())
                 .await
                 .map_err(problem::build)
         });
 
+    // GET /../brands/export?format=csv
+    let export_brands = warp::path!("brands" / "export")
+        .and(warp::get())
+        .and(with_permission(env.clone(), Permission::ReadCatalog))
+        .and(warp::query::<ExportQuery>())
+        .and_then(
+            |env: Environment, _user: AdminUser, query: ExportQuery| async move {
+                handlers::cosmetics::export_brands(env, query)
+                    .await
+                    .map_err(problem::build)
+            },
+        );
+
+    // PUT /../brand/{id}/restore
+    let restore_brand = warp::path!("brand" / u64 / "restore")
+        .and(warp::put())
+        .and(with_permission(env.clone(), Permission::DeleteCatalog))
+        .and_then(|id: u64, env: Environment, user: AdminUser| async move {
+            handlers::cosmetics::restore_brand(env, id, user.username.as_str())
+                .await
+                .map_err(problem::build)
+        });
+
+    // DELETE /../brand/{id}/purge
+    let purge_brand = warp::path!("brand" / u64 / "purge")
+        .and(warp::delete())
+        .and(with_permission(env.clone(), Permission::PurgeCatalog))
+        .and_then(|id: u64, env: Environment, user: AdminUser| async move {
+            handlers::cosmetics::purge_brand(env, id, user.username.as_str())
+                .await
+                .map_err(problem::build)
+        });
+
     let api_brands = create_brands
         .or(get_brands)
         .or(update_brands_sequence)
-        .or(delete_brand);
+        .or(update_brand)
+        .or(set_hot_brands)
+        .or(get_hot_brands)
+        .or(export_brands)
+        .or(delete_brand)
+        .or(restore_brand)
+        .or(purge_brand);
 
     // product
 
     // POST /../product
     let create_product = warp::path!("product")
         .and(warp::path::end())
-        .and(with_auth(env.clone()))
+        .and(with_permission(env.clone(), Permission::WriteCatalog))
         .and(warp::body::content_length_limit(4096))
         .and(warp::body::json())
         .and_then(
@@ -183,11 +485,12 @@ fn admin_cosmetics(
     let get_product_list = warp::path!("products")
         .and(warp::path::end())
         .and(warp::get())
-        .and(with_auth(env.clone()))
+        .and(with_permission(env.clone(), Permission::ReadCatalog))
+        .and(warp::query::<ProductFilter>())
         .and(warp::query::<Paging>())
         .and_then(
-            |env: Environment, _user: AdminUser, paging: Paging| async move {
-                handlers::cosmetics::get_products(env, paging)
+            |env: Environment, _user: AdminUser, filter: ProductFilter, paging: Paging| async move {
+                handlers::cosmetics::get_products(env, filter, paging)
                     .await
                     .map_err(problem::build)
             },
@@ -196,7 +499,7 @@ fn admin_cosmetics(
     // GET /../product/{id}
     let get_product = warp::path!("product" / u64)
         .and(warp::get())
-        .and(with_auth(env.clone()))
+        .and(with_permission(env.clone(), Permission::ReadCatalog))
         .and_then(|id: u64, env: Environment, _user: AdminUser| async move {
             handlers::cosmetics::get_product(env, id, false)
                 .await
@@ -206,37 +509,275 @@ fn admin_cosmetics(
     // PUT /../product/{id}
     let update_product = warp::path!("product" / u64)
         .and(warp::put())
-        .and(with_auth(env.clone()))
+        .and(with_permission(env.clone(), Permission::WriteCatalog))
         .and(warp::body::content_length_limit(4096))
         .and(warp::body::json())
         .and_then(
             |id: u64, env: Environment, user: AdminUser, product: NewProduct| async move {
-                handlers::cosmetics::update_product_by_admin(
-                    env,
-                    id,
-                    product,
-                    user.username.as_str(),
-                )
-                .await
-                .map_err(problem::build)
+                handlers::cosmetics::update_product_by_admin(env, id, product, &user)
+                    .await
+                    .map_err(problem::build)
             },
         );
 
+    // PUT /../product/{id}/price
+    let update_product_price = warp::path!("product" / u64 / "price")
+        .and(warp::put())
+        .and(with_permission(env.clone(), Permission::UpdatePrice))
+        .and(warp::body::content_length_limit(1024))
+        .and(warp::body::json())
+        .and_then(
+            |id: u64, env: Environment, user: AdminUser, price: ProductPrice| async move {
+                handlers::cosmetics::update_product_price(env, id, price, user.username.as_str())
+                    .await
+                    .map_err(problem::build)
+            },
+        );
+
+    // PUT /../product/{id}/publish
+    let publish_product = warp::path!("product" / u64 / "publish")
+        .and(warp::put())
+        .and(with_permission(env.clone(), Permission::WriteCatalog))
+        .and_then(|id: u64, env: Environment, user: AdminUser| async move {
+            handlers::cosmetics::publish_product(env, id, user.username.as_str())
+                .await
+                .map_err(problem::build)
+        });
+
+    // PUT /../product/{id}/unpublish
+    let unpublish_product = warp::path!("product" / u64 / "unpublish")
+        .and(warp::put())
+        .and(with_permission(env.clone(), Permission::WriteCatalog))
+        .and_then(|id: u64, env: Environment, user: AdminUser| async move {
+            handlers::cosmetics::unpublish_product(env, id, user.username.as_str())
+                .await
+                .map_err(problem::build)
+        });
+
     // DELETE /../product/{id}
     let delete_product = warp::path!("product" / u64)
         .and(warp::delete())
-        .and(with_auth(env.clone()))
+        .and(with_permission(env.clone(), Permission::DeleteCatalog))
         .and_then(|id: u64, env: Environment, user: AdminUser| async move {
             handlers::cosmetics::delete_product(env, id, user.username.as_str())
                 .await
                 .map_err(problem::build)
         });
 
+    // PUT /../product/{id}/restore
+    let restore_product = warp::path!("product" / u64 / "restore")
+        .and(warp::put())
+        .and(with_permission(env.clone(), Permission::DeleteCatalog))
+        .and_then(|id: u64, env: Environment, user: AdminUser| async move {
+            handlers::cosmetics::restore_product(env, id, user.username.as_str())
+                .await
+                .map_err(problem::build)
+        });
+
+    // DELETE /../product/{id}/purge
+    let purge_product = warp::path!("product" / u64 / "purge")
+        .and(warp::delete())
+        .and(with_permission(env.clone(), Permission::PurgeCatalog))
+        .and_then(|id: u64, env: Environment, user: AdminUser| async move {
+            handlers::cosmetics::purge_product(env, id, user.username.as_str())
+                .await
+                .map_err(problem::build)
+        });
+
+    // GET /../product/{id}/prices
+    let get_product_prices = warp::path!("product" / u64 / "prices")
+        .and(warp::get())
+        .and(with_permission(env.clone(), Permission::ReadCatalog))
+        .and(warp::query::<PriceHistoryQuery>())
+        .and_then(
+            |id: u64, env: Environment, _user: AdminUser, query: PriceHistoryQuery| async move {
+                handlers::cosmetics::get_price_history(env, id, query, false)
+                    .await
+                    .map_err(problem::build)
+            },
+        );
+
+    // POST /../products/import?format=csv&commit=true
+    let import_products = warp::path!("products" / "import")
+        .and(warp::post())
+        .and(with_permission(env.clone(), Permission::WriteCatalog))
+        .and(warp::query::<ImportQuery>())
+        .and(warp::body::content_length_limit(10 * 1024 * 1024))
+        .and(warp::body::bytes())
+        .and_then(
+            |env: Environment, user: AdminUser, query: ImportQuery, data: Bytes| async move {
+                handlers::cosmetics::import_products(env, query, &data, &user)
+                    .await
+                    .map_err(problem::build)
+            },
+        );
+
+    // GET /../products/export?format=csv
+    let export_products = warp::path!("products" / "export")
+        .and(warp::get())
+        .and(with_permission(env.clone(), Permission::ReadCatalog))
+        .and(warp::query::<ProductFilter>())
+        .and(warp::query::<ExportQuery>())
+        .and_then(
+            |env: Environment, _user: AdminUser, filter: ProductFilter, query: ExportQuery| async move {
+                handlers::cosmetics::export_products(env, filter, query)
+                    .await
+                    .map_err(problem::build)
+            },
+        );
+
+    // POST /../product/{id}/images
+    let upload_product_images = warp::path!("product" / u64 / "images")
+        .and(warp::post())
+        .and(with_permission(env.clone(), Permission::WriteCatalog))
+        .and(warp::multipart::form().max_length(4 * upload::MAX_IMAGE_BYTES as u64))
+        .and_then(
+            |id: u64, env: Environment, user: AdminUser, form: FormData| async move {
+                handlers::cosmetics::upload_product_images(env, id, form, user.username.as_str())
+                    .await
+                    .map_err(problem::build)
+            },
+        );
+
+    // GET /../product/{id}/images
+    let get_product_images = warp::path!("product" / u64 / "images")
+        .and(warp::get())
+        .and(with_permission(env.clone(), Permission::ReadCatalog))
+        .and_then(|id: u64, env: Environment, _user: AdminUser| async move {
+            handlers::cosmetics::get_product_images(env, id, false)
+                .await
+                .map_err(problem::build)
+        });
+
+    // PUT /../product/{id}/images/sequence
+    let update_images_sequence = warp::path!("product" / u64 / "images" / "sequence")
+        .and(warp::put())
+        .and(with_permission(env.clone(), Permission::WriteCatalog))
+        .and(warp::body::content_length_limit(4096))
+        .and(warp::body::json())
+        .and_then(
+            |id: u64, env: Environment, user: AdminUser, iss: Vec<ImageSequence>| async move {
+                handlers::cosmetics::update_images_sequence(env, id, iss, user.username.as_str())
+                    .await
+                    .map_err(problem::build)
+            },
+        );
+
+    // DELETE /../product/{id}/image/{image_id}
+    let delete_product_image = warp::path!("product" / u64 / "image" / u64)
+        .and(warp::delete())
+        .and(with_permission(env.clone(), Permission::WriteCatalog))
+        .and_then(
+            |id: u64, image_id: u64, env: Environment, user: AdminUser| async move {
+                handlers::cosmetics::delete_product_image(env, id, image_id, user.username.as_str())
+                    .await
+                    .map_err(problem::build)
+            },
+        );
+
+    let api_product_images = upload_product_images
+        .or(get_product_images)
+        .or(update_images_sequence)
+        .or(delete_product_image);
+
+    // GET /../product/{id}/skus
+    let get_product_skus = warp::path!("product" / u64 / "skus")
+        .and(warp::get())
+        .and(with_permission(env.clone(), Permission::ReadCatalog))
+        .and_then(|id: u64, env: Environment, _user: AdminUser| async move {
+            handlers::cosmetics::get_skus(env, id)
+                .await
+                .map_err(problem::build)
+        });
+
+    // POST /../product/{id}/skus
+    let create_product_sku = warp::path!("product" / u64 / "skus")
+        .and(warp::post())
+        .and(with_permission(env.clone(), Permission::WriteCatalog))
+        .and(warp::body::content_length_limit(1024))
+        .and(warp::body::json())
+        .and_then(
+            |id: u64, env: Environment, user: AdminUser, sku: NewSku| async move {
+                handlers::cosmetics::create_sku(env, id, sku, &user)
+                    .await
+                    .map_err(problem::build)
+            },
+        );
+
+    // PUT /../product/{id}/sku/{sku_id}
+    let update_product_sku = warp::path!("product" / u64 / "sku" / u64)
+        .and(warp::put())
+        .and(with_permission(env.clone(), Permission::WriteCatalog))
+        .and(warp::body::content_length_limit(1024))
+        .and(warp::body::json())
+        .and_then(
+            |id: u64, sku_id: u64, env: Environment, user: AdminUser, sku: NewSku| async move {
+                handlers::cosmetics::update_sku(env, id, sku_id, sku, &user)
+                    .await
+                    .map_err(problem::build)
+            },
+        );
+
+    // DELETE /../product/{id}/sku/{sku_id}
+    let delete_product_sku = warp::path!("product" / u64 / "sku" / u64)
+        .and(warp::delete())
+        .and(with_permission(env.clone(), Permission::DeleteCatalog))
+        .and_then(
+            |id: u64, sku_id: u64, env: Environment, user: AdminUser| async move {
+                handlers::cosmetics::delete_sku(env, id, sku_id, &user)
+                    .await
+                    .map_err(problem::build)
+            },
+        );
+
+    let api_product_skus = get_product_skus
+        .or(create_product_sku)
+        .or(update_product_sku)
+        .or(delete_product_sku);
+
     let api_products = create_product
         .or(get_product_list)
         .or(get_product)
         .or(update_product)
-        .or(delete_product);
+        .or(update_product_price)
+        .or(publish_product)
+        .or(unpublish_product)
+        .or(get_product_prices)
+        .or(import_products)
+        .or(export_products)
+        .or(delete_product)
+        .or(restore_product)
+        .or(purge_product);
+
+    // trash
+
+    // GET /../trash/brands
+    let get_deleted_brands = warp::path!("trash" / "brands")
+        .and(warp::get())
+        .and(with_permission(env.clone(), Permission::DeleteCatalog))
+        .and(warp::query::<Paging>())
+        .and_then(
+            |env: Environment, _user: AdminUser, paging: Paging| async move {
+                handlers::cosmetics::get_deleted_brands(env, paging)
+                    .await
+                    .map_err(problem::build)
+            },
+        );
+
+    // GET /../trash/products
+    let get_deleted_products = warp::path!("trash" / "products")
+        .and(warp::get())
+        .and(with_permission(env.clone(), Permission::DeleteCatalog))
+        .and(warp::query::<Paging>())
+        .and_then(
+            |env: Environment, _user: AdminUser, paging: Paging| async move {
+                handlers::cosmetics::get_deleted_products(env, paging)
+                    .await
+                    .map_err(problem::build)
+            },
+        );
+
+    let api_trash = get_deleted_brands.or(get_deleted_products);
 
     // hot product
 
@@ -244,7 +785,7 @@ fn admin_cosmetics(
     let add_hot_product = warp::path!("product" / "hot")
         .and(warp::path::end())
         .and(warp::post())
-        .and(with_auth(env.clone()))
+        .and(with_permission(env.clone(), Permission::WriteCatalog))
         .and(warp::body::content_length_limit(4096))
         .and(warp::body::json())
         .and_then(
@@ -255,18 +796,181 @@ fn admin_cosmetics(
             },
         );
 
-    // GET /../product/hot
+    // GET /../product/hot?slot=home
     let get_hot_products = warp::path!("product" / "hot")
         .and(warp::path::end())
         .and(warp::get())
-        .and(with_auth(env.clone()))
+        .and(with_permission(env.clone(), Permission::ReadCatalog))
+        .and(warp::query::<HotSlotQuery>())
+        .and_then(
+            |env: Environment, _user: AdminUser, query: HotSlotQuery| async move {
+                handlers::cosmetics::get_hot_products(env, query)
+                    .await
+                    .map_err(problem::build)
+            },
+        );
+
+    // GET /../product/hot/export?slot=home&format=csv
+    let export_hot_products = warp::path!("product" / "hot" / "export")
+        .and(warp::get())
+        .and(with_permission(env.clone(), Permission::ReadCatalog))
+        .and(warp::query::<HotSlotQuery>())
+        .and(warp::query::<ExportQuery>())
+        .and_then(
+            |env: Environment, _user: AdminUser, slot: HotSlotQuery, query: ExportQuery| async move {
+                handlers::cosmetics::export_hot_products(env, slot, query)
+                    .await
+                    .map_err(problem::build)
+            },
+        );
+
+    // POST /../hot/lists
+    let create_hot_list = warp::path!("hot" / "lists")
+        .and(warp::post())
+        .and(with_permission(env.clone(), Permission::WriteCatalog))
+        .and(warp::body::content_length_limit(4096))
+        .and(warp::body::json())
+        .and_then(
+            |env: Environment, user: AdminUser, list: NewHotList| async move {
+                handlers::cosmetics::create_hot_list(env, list, user.username.as_str())
+                    .await
+                    .map_err(problem::build)
+            },
+        );
+
+    // GET /../hot/lists?slot=home
+    let get_hot_lists = warp::path!("hot" / "lists")
+        .and(warp::get())
+        .and(with_permission(env.clone(), Permission::ReadCatalog))
+        .and(warp::query::<HotSlotQuery>())
+        .and_then(
+            |env: Environment, _user: AdminUser, query: HotSlotQuery| async move {
+                handlers::cosmetics::get_hot_lists(env, query)
+                    .await
+                    .map_err(problem::build)
+            },
+        );
+
+    // DELETE /../hot/list/{id}
+    let delete_hot_list = warp::path!("hot" / "list" / u64)
+        .and(warp::delete())
+        .and(with_permission(env.clone(), Permission::WriteCatalog))
+        .and_then(|id: u64, env: Environment, user: AdminUser| async move {
+            handlers::cosmetics::delete_hot_list(env, id, user.username.as_str())
+                .await
+                .map_err(problem::build)
+        });
+
+    let api_hot_products = add_hot_product
+        .or(get_hot_products)
+        .or(export_hot_products)
+        .or(create_hot_list)
+        .or(get_hot_lists)
+        .or(delete_hot_list);
+
+    // category
+
+    // GET /../categories
+    let get_categories = warp::path!("categories")
+        .and(warp::get())
+        .and(with_permission(env.clone(), Permission::ReadCatalog))
         .and_then(|env: Environment, _user: AdminUser| async move {
-            handlers::cosmetics::get_hot_products(env)
+            handlers::cosmetics::get_category_tree(env)
                 .await
                 .map_err(problem::build)
         });
 
-    let api_hot_products = add_hot_product.or(get_hot_products);
+    // POST /../categories
+    let create_category = warp::path!("categories")
+        .and(warp::post())
+        .and(with_permission(env.clone(), Permission::WriteCatalog))
+        .and(warp::body::content_length_limit(1024))
+        .and(warp::body::json())
+        .and_then(
+            |env: Environment, user: AdminUser, category: NewCategory| async move {
+                handlers::cosmetics::create_category(env, category, user.username.as_str())
+                    .await
+                    .map_err(problem::build)
+            },
+        );
+
+    // PUT /../categories/sequence
+    let update_categories_sequence = warp::path!("categories" / "sequence")
+        .and(warp::put())
+        .and(with_permission(env.clone(), Permission::WriteCatalog))
+        .and(warp::body::content_length_limit(10240))
+        .and(warp::body::json())
+        .and_then(
+            |env: Environment, user: AdminUser, css: Vec<CategorySequence>| async move {
+                handlers::cosmetics::update_categories_sequence(env, css, user.username.as_str())
+                    .await
+                    .map_err(problem::build)
+            },
+        );
+
+    // PUT /../category/{id}
+    let update_category = warp::path!("category" / u64)
+        .and(warp::put())
+        .and(with_permission(env.clone(), Permission::WriteCatalog))
+        .and(warp::body::content_length_limit(1024))
+        .and(warp::body::json())
+        .and_then(
+            |id: u64, env: Environment, user: AdminUser, category: NewCategory| async move {
+                handlers::cosmetics::update_category(env, id, category, user.username.as_str())
+                    .await
+                    .map_err(problem::build)
+            },
+        );
+
+    // DELETE /../category/{id}
+    let delete_category = warp::path!("category" / u64)
+        .and(warp::delete())
+        .and(with_permission(env.clone(), Permission::DeleteCatalog))
+        .and_then(|id: u64, env: Environment, user: AdminUser| async move {
+            handlers::cosmetics::delete_category(env, id, user.username.as_str())
+                .await
+                .map_err(problem::build)
+        });
+
+    // GET /../product/{id}/categories
+    let get_product_categories = warp::path!("product" / u64 / "categories")
+        .and(warp::get())
+        .and(with_permission(env.clone(), Permission::ReadCatalog))
+        .and_then(|id: u64, env: Environment, _user: AdminUser| async move {
+            handlers::cosmetics::get_product_categories(env, id)
+                .await
+                .map_err(problem::build)
+        });
+
+    // PUT /../product/{id}/categories
+    let set_product_categories = warp::path!("product" / u64 / "categories")
+        .and(warp::put())
+        .and(with_permission(env.clone(), Permission::WriteCatalog))
+        .and(warp::body::content_length_limit(4096))
+        .and(warp::body::json())
+        .and_then(
+            |id: u64, env: Environment, user: AdminUser, ids: Vec<u64>| async move {
+                handlers::cosmetics::set_product_categories(env, id, ids, user.username.as_str())
+                    .await
+                    .map_err(problem::build)
+            },
+        );
+
+    let api_categories = get_categories
+        .or(create_category)
+        .or(update_categories_sequence)
+        .or(update_category)
+        .or(delete_category)
+        .or(get_product_categories)
+        .or(set_product_categories);
 
-    prefix.and(api_brands.or(api_products).or(api_hot_products))
+    prefix.and(
+        api_brands
+            .or(api_products)
+            .or(api_product_images)
+            .or(api_product_skus)
+            .or(api_trash)
+            .or(api_hot_products)
+            .or(api_categories),
+    )
 }
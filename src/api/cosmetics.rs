@@ -3,6 +3,7 @@ use warp::Filter;
 use crate::environment::Environment;
 use crate::handlers;
 use crate::helpers::problem;
+use crate::models::cosmetics::{HotSlotQuery, PriceHistoryQuery, SearchQuery};
 use crate::models::Paging;
 
 pub fn cosmetics(
@@ -25,6 +26,16 @@ pub fn cosmetics(
                 .map_err(problem::build)
         });
 
+    // GET /api/v1/cosmetics/brands/hot
+    let get_hot_brands = warp::path!("brands" / "hot")
+        .and(warp::get())
+        .and(env.clone())
+        .and_then(|env: Environment| async move {
+            handlers::cosmetics::get_hot_brands(env)
+                .await
+                .map_err(problem::build)
+        });
+
     // GET /api/v1/cosmetics/brand/{id}
     let get_brand_detail = warp::path!("brand" / u32)
         .and(warp::get())
@@ -46,5 +57,85 @@ pub fn cosmetics(
                 .map_err(problem::build)
         });
 
-    prefix.and(get_brands.or(get_brand_detail).or(get_product_detail))
+    // GET /api/v1/cosmetics/product/{id}/prices
+    let get_product_prices = warp::path!("product" / u64 / "prices")
+        .and(warp::get())
+        .and(env.clone())
+        .and(warp::query::<PriceHistoryQuery>())
+        .and_then(
+            |id: u64, env: Environment, query: PriceHistoryQuery| async move {
+                handlers::cosmetics::get_price_history(env, id, query, true)
+                    .await
+                    .map_err(problem::build)
+            },
+        );
+
+    // GET /api/v1/cosmetics/product/{id}/images
+    let get_product_images = warp::path!("product" / u64 / "images")
+        .and(warp::get())
+        .and(env.clone())
+        .and_then(|id: u64, env: Environment| async move {
+            handlers::cosmetics::get_product_images(env, id, true)
+                .await
+                .map_err(problem::build)
+        });
+
+    // GET /api/v1/cosmetics/hot?slot=home
+    let get_hot_products = warp::path!("hot")
+        .and(warp::get())
+        .and(env.clone())
+        .and(warp::query::<HotSlotQuery>())
+        .and_then(|env: Environment, query: HotSlotQuery| async move {
+            handlers::cosmetics::get_hot_product_cards(env, query)
+                .await
+                .map_err(problem::build)
+        });
+
+    // GET /api/v1/cosmetics/search?q=
+    let search_products = warp::path!("search")
+        .and(warp::get())
+        .and(env.clone())
+        .and(warp::query::<SearchQuery>())
+        .and(warp::query::<Paging>())
+        .and_then(
+            |env: Environment, query: SearchQuery, paging: Paging| async move {
+                handlers::cosmetics::search_products(env, query, paging)
+                    .await
+                    .map_err(problem::build)
+            },
+        );
+
+    // GET /api/v1/cosmetics/categories
+    let get_categories = warp::path!("categories")
+        .and(warp::get())
+        .and(env.clone())
+        .and_then(|env: Environment| async move {
+            handlers::cosmetics::get_category_tree(env)
+                .await
+                .map_err(problem::build)
+        });
+
+    // GET /api/v1/cosmetics/category/{id}/products
+    let get_category_products = warp::path!("category" / u64 / "products")
+        .and(warp::get())
+        .and(env.clone())
+        .and(warp::query::<Paging>())
+        .and_then(|id: u64, env: Environment, paging: Paging| async move {
+            handlers::cosmetics::get_category_products(env, id, paging)
+                .await
+                .map_err(problem::build)
+        });
+
+    prefix.and(
+        get_brands
+            .or(get_hot_brands)
+            .or(get_brand_detail)
+            .or(get_product_detail)
+            .or(get_product_prices)
+            .or(get_product_images)
+            .or(get_hot_products)
+            .or(search_products)
+            .or(get_categories)
+            .or(get_category_products),
+    )
 }
@@ -1,6 +1,7 @@
 use anyhow::Result;
 use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
 
+use super::session::SessionStore;
 use crate::models::admin::AdminUser;
 use crate::models::admin::Claims;
 use crate::models::AuthError;
@@ -10,12 +11,14 @@ const BEARER: &str = "Bearer ";
 #[derive(Clone, Debug)]
 pub struct Jwt {
     secret: String,
+    sessions: SessionStore,
 }
 
 impl Jwt {
-    pub fn new(secret: &str) -> Self {
+    pub fn new(secret: &str, sessions: SessionStore) -> Self {
         Self {
             secret: secret.to_owned(),
+            sessions,
         }
     }
 
@@ -29,7 +32,7 @@ impl Jwt {
         Ok(token)
     }
 
-    pub fn decode(&self, jwt_raw: Option<String>) -> Result<Claims> {
+    pub async fn decode(&self, jwt_raw: Option<String>) -> Result<Claims> {
         match jwt_raw {
             None => return Err(AuthError::NoAuthHeaderError.into()),
             Some(v) => {
@@ -44,13 +47,16 @@ impl Jwt {
                     &Validation::new(Algorithm::HS512),
                 )
                 .map_err(|_| AuthError::JWTTokenError)?;
+                if !self.sessions.is_active(&decoded.claims.sid).await? {
+                    return Err(AuthError::SessionRevoked.into());
+                }
                 Ok(decoded.claims)
             }
         }
     }
 
-    pub fn decode_to_admin_user(&self, jwt_raw: Option<String>) -> Result<AdminUser> {
-        let claims = self.decode(jwt_raw)?;
+    pub async fn decode_to_admin_user(&self, jwt_raw: Option<String>) -> Result<AdminUser> {
+        let claims = self.decode(jwt_raw).await?;
         let id = claims.sub.parse::<u64>();
         if id.is_err() {
             return Err(AuthError::InvalidAuthHeaderError.into());
@@ -58,6 +64,9 @@ impl Jwt {
         Ok(AdminUser {
             id: id.unwrap(),
             username: claims.name,
+            role: claims.role,
+            must_change_password: claims.must_change_password,
+            session_id: claims.sid,
         })
     }
 
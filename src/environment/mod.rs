@@ -1,9 +1,27 @@
 mod jwt;
+mod session;
+mod storage;
+mod throttle;
 
 use clap::Clap;
 use jwt::Jwt;
 use sqlx::mysql::MySqlPool;
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+use crate::models::admin::Role;
+#[cfg(feature = "test-util")]
+use crate::repo::MemoryRepo;
+use crate::repo::{
+    AdminUserRepo, AuditLogRepo, BrandRepo, CategoryRepo, HotProductRepo, MySqlRepo, ProductRepo,
+};
+use crate::sql::migrate;
+
+pub use session::{Session, SessionStore, ACCESS_TOKEN_MINUTES, REFRESH_TOKEN_DAYS};
+#[cfg(feature = "test-util")]
+pub use storage::MemoryStorage;
+pub use storage::{LocalStorage, Storage};
+pub use throttle::LoginThrottle;
 
 #[derive(Clap, Debug)]
 #[clap(
@@ -16,40 +34,235 @@ pub struct Args {
     debug: bool,
 
     #[clap(required = true, short = 'D', long, env)]
-    database_url: String,
+    pub database_url: String,
     #[clap(required = true, short = 'R', long, env)]
-    redis_url: String,
+    pub redis_url: String,
 
     #[clap(required = true, long, env)]
     jwt_secret: String,
 
     #[clap(default_value = "127.0.0.1:3000", env)]
     pub host: SocketAddr,
+
+    /// Directory of the uploaded files.
+    #[clap(long, default_value = "uploads", env)]
+    pub storage_dir: String,
+    /// Url prefix of the uploaded files.
+    #[clap(long, default_value = "/uploads", env)]
+    pub storage_url: String,
+
+    /// Comma separated addresses of the reverse proxies, only requests coming
+    /// from them may set the client address with X-Real-IP or X-Forwarded-For.
+    #[clap(long, env, use_delimiter = true)]
+    pub trusted_proxies: Vec<IpAddr>,
+
+    /// Apply the pending migrations before serving.
+    #[clap(long, env, parse(try_from_str), default_value = "false")]
+    pub auto_migrate: bool,
+
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+}
+
+// the api is served when no command is given
+#[derive(Clap, Debug)]
+pub enum Command {
+    /// Manage the database migrations built into the binary.
+    Migrate(Migrate),
+    /// Manage the admin users, e.g. create the first superuser.
+    User(User),
+}
+
+#[derive(Clap, Debug)]
+pub struct Migrate {
+    #[clap(subcommand)]
+    pub action: MigrateAction,
+}
+
+#[derive(Clap, Debug)]
+pub enum MigrateAction {
+    /// Apply all the pending migrations.
+    Up,
+    /// List the migrations and whether they are applied.
+    Status,
+    /// Revert the latest applied migration.
+    Down,
+}
+
+#[derive(Clap, Debug)]
+pub struct User {
+    #[clap(subcommand)]
+    pub action: UserAction,
+}
+
+#[derive(Clap, Debug)]
+#[clap(rename_all = "kebab-case")]
+pub enum UserAction {
+    /// Create an admin user.
+    Create {
+        username: String,
+        /// One of viewer, catalog_editor, pricing_manager and superuser.
+        #[clap(long, default_value = "superuser")]
+        role: Role,
+        /// Read the password from stdin instead of prompting for it.
+        #[clap(long)]
+        password_stdin: bool,
+    },
+    /// Set a new password, to be changed at the next sign in.
+    ResetPassword {
+        username: String,
+        /// Read the password from stdin instead of prompting for it.
+        #[clap(long)]
+        password_stdin: bool,
+    },
+    /// List the admin users.
+    List,
 }
 
 #[derive(Clone, Debug)]
 pub struct Environment {
-    db_pool: MySqlPool,
+    brands: Arc<dyn BrandRepo>,
+    products: Arc<dyn ProductRepo>,
+    hot_products: Arc<dyn HotProductRepo>,
+    categories: Arc<dyn CategoryRepo>,
+    admin_users: Arc<dyn AdminUserRepo>,
+    audit_logs: Arc<dyn AuditLogRepo>,
+    sessions: SessionStore,
+    login_throttle: LoginThrottle,
     jwt: Jwt,
+    storage: Arc<dyn Storage>,
+    trusted_proxies: Arc<Vec<IpAddr>>,
 }
 
 impl Environment {
     pub async fn new(args: &Args) -> anyhow::Result<Self> {
         let Args {
             database_url,
+            redis_url,
             jwt_secret,
+            storage_dir,
+            storage_url,
+            auto_migrate,
+            trusted_proxies,
             ..
         } = &args;
         let db_pool = MySqlPool::connect(database_url).await?;
-        let jwt = Jwt::new(&jwt_secret);
-        Ok(Self { db_pool, jwt })
+        if *auto_migrate {
+            for m in migrate::up(&db_pool).await? {
+                tracing::info!("applied migration {} {}", m.version, m.description);
+            }
+        }
+        migrate::check(&db_pool).await?;
+        let redis = redis::Client::open(redis_url.as_str())?
+            .get_multiplexed_tokio_connection()
+            .await?;
+        Ok(Self::with_repo(
+            Arc::new(MySqlRepo::new(db_pool)),
+            SessionStore::redis(redis.clone()),
+            LoginThrottle::redis(redis),
+            jwt_secret,
+            Arc::new(LocalStorage::new(storage_dir, storage_url)),
+        )
+        .with_trusted_proxies(trusted_proxies.clone()))
+    }
+
+    /// Everything kept in process memory, no database or redis needed.
+    #[cfg(feature = "test-util")]
+    pub fn memory(jwt_secret: &str) -> Self {
+        Self::with_repo(
+            Arc::new(MemoryRepo::new()),
+            SessionStore::memory(),
+            LoginThrottle::memory(),
+            jwt_secret,
+            Arc::new(MemoryStorage::new("/uploads")),
+        )
     }
 
-    pub fn db(&self) -> &MySqlPool {
-        &self.db_pool
+    fn with_repo<R>(
+        repo: Arc<R>,
+        sessions: SessionStore,
+        login_throttle: LoginThrottle,
+        jwt_secret: &str,
+        storage: Arc<dyn Storage>,
+    ) -> Self
+    where
+        R: BrandRepo
+            + ProductRepo
+            + HotProductRepo
+            + CategoryRepo
+            + AdminUserRepo
+            + AuditLogRepo
+            + 'static,
+    {
+        let jwt = Jwt::new(jwt_secret, sessions.clone());
+        Self {
+            brands: repo.clone(),
+            products: repo.clone(),
+            hot_products: repo.clone(),
+            categories: repo.clone(),
+            admin_users: repo.clone(),
+            audit_logs: repo,
+            sessions,
+            login_throttle,
+            jwt,
+            storage,
+            trusted_proxies: Arc::new(vec![]),
+        }
+    }
+
+    /// Replace the reverse proxies allowed to forward the client address.
+    pub fn with_trusted_proxies(mut self, proxies: Vec<IpAddr>) -> Self {
+        self.trusted_proxies = Arc::new(proxies);
+        self
+    }
+
+    /// Replace where uploaded files are kept.
+    pub fn with_storage(mut self, storage: Arc<dyn Storage>) -> Self {
+        self.storage = storage;
+        self
+    }
+
+    pub fn brands(&self) -> &dyn BrandRepo {
+        self.brands.as_ref()
+    }
+
+    pub fn products(&self) -> &dyn ProductRepo {
+        self.products.as_ref()
+    }
+
+    pub fn hot_products(&self) -> &dyn HotProductRepo {
+        self.hot_products.as_ref()
+    }
+
+    pub fn categories(&self) -> &dyn CategoryRepo {
+        self.categories.as_ref()
+    }
+
+    pub fn admin_users(&self) -> &dyn AdminUserRepo {
+        self.admin_users.as_ref()
+    }
+
+    pub fn audit_logs(&self) -> &dyn AuditLogRepo {
+        self.audit_logs.as_ref()
+    }
+
+    pub fn sessions(&self) -> &SessionStore {
+        &self.sessions
+    }
+
+    pub fn login_throttle(&self) -> &LoginThrottle {
+        &self.login_throttle
     }
 
     pub fn jwt(&self) -> &Jwt {
         &self.jwt
     }
+
+    pub fn storage(&self) -> &dyn Storage {
+        self.storage.as_ref()
+    }
+
+    pub fn trusted_proxies(&self) -> &[IpAddr] {
+        &self.trusted_proxies
+    }
 }
@@ -0,0 +1,300 @@
+use anyhow::Result;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use redis::aio::MultiplexedConnection;
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::models::AuthError;
+
+const SESSION_PREFIX: &str = "kerria:session:";
+const USER_SESSIONS_PREFIX: &str = "kerria:user_sessions:";
+
+// KEYS: session, user sessions; ARGV: old secret, new data, session id, ttl.
+// Returns 1 when rotated, 0 when the secret doesn't match or the session is gone.
+const ROTATE_SCRIPT: &str = r#"
+local raw = redis.call('GET', KEYS[1])
+if not raw or cjson.decode(raw).secret ~= ARGV[1] then
+    return 0
+end
+redis.call('SET', KEYS[1], ARGV[2], 'EX', ARGV[4])
+redis.call('SADD', KEYS[2], ARGV[3])
+redis.call('EXPIRE', KEYS[2], ARGV[4])
+return 1
+"#;
+
+pub const ACCESS_TOKEN_MINUTES: i64 = 15;
+pub const REFRESH_TOKEN_DAYS: i64 = 14;
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct SessionData {
+    user_id: u64,
+    username: String,
+    secret: String,
+}
+
+/// A login session which can be refreshed until it is revoked.
+#[derive(Debug)]
+pub struct Session {
+    pub id: String,
+    pub user_id: u64,
+    pub username: String,
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Default)]
+struct MemorySessions {
+    sessions: HashMap<String, (SessionData, Instant)>,
+    user_sessions: HashMap<u64, HashSet<String>>,
+}
+
+impl MemorySessions {
+    fn get(&self, session_id: &str) -> Option<&SessionData> {
+        match self.sessions.get(session_id) {
+            Some((data, until)) if *until > Instant::now() => Some(data),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone)]
+enum Backend {
+    Redis(MultiplexedConnection),
+    Memory(Arc<Mutex<MemorySessions>>),
+}
+
+/// Server side session storage, a refresh token is `{session_id}.{secret}` and
+/// the secret is rotated on each refresh.
+#[derive(Clone)]
+pub struct SessionStore {
+    backend: Backend,
+}
+
+impl std::fmt::Debug for SessionStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let backend = match self.backend {
+            Backend::Redis(_) => "redis",
+            Backend::Memory(_) => "memory",
+        };
+        f.debug_struct("SessionStore")
+            .field("backend", &backend)
+            .finish()
+    }
+}
+
+impl SessionStore {
+    pub fn redis(conn: MultiplexedConnection) -> Self {
+        Self {
+            backend: Backend::Redis(conn),
+        }
+    }
+
+    pub fn memory() -> Self {
+        Self {
+            backend: Backend::Memory(Arc::new(Mutex::new(MemorySessions::default()))),
+        }
+    }
+
+    pub async fn create(&self, user_id: u64, username: &str) -> Result<Session> {
+        let session_id = random_string(24);
+        let data = SessionData {
+            user_id,
+            username: username.to_owned(),
+            secret: random_string(32),
+        };
+        self.save(&session_id, &data).await?;
+
+        Ok(Session {
+            refresh_token: format!("{}.{}", session_id, data.secret),
+            id: session_id,
+            user_id,
+            username: data.username,
+        })
+    }
+
+    /// Consume a refresh token and hand out a new one for the same session.
+    /// Reusing an already rotated token revokes the whole session.
+    pub async fn rotate(&self, refresh_token: &str) -> Result<Session> {
+        let mut parts = refresh_token.splitn(2, '.');
+        let (session_id, secret) = match (parts.next(), parts.next()) {
+            (Some(id), Some(secret)) => (id, secret),
+            _ => return Err(AuthError::InvalidRefreshToken.into()),
+        };
+        let mut data = self
+            .load(session_id)
+            .await?
+            .ok_or(AuthError::InvalidRefreshToken)?;
+        data.secret = random_string(32);
+        // checking the old secret and storing the new one happen in one step,
+        // two refreshes racing with the same token can't both succeed
+        let rotated = match &self.backend {
+            Backend::Redis(conn) => {
+                let mut conn = conn.clone();
+                let res: i64 = redis::cmd("EVAL")
+                    .arg(ROTATE_SCRIPT)
+                    .arg(2)
+                    .arg(session_key(session_id))
+                    .arg(user_sessions_key(data.user_id))
+                    .arg(secret)
+                    .arg(serde_json::to_string(&data)?)
+                    .arg(session_id)
+                    .arg(refresh_ttl())
+                    .query_async(&mut conn)
+                    .await?;
+                res == 1
+            }
+            Backend::Memory(store) => {
+                let mut store = store.lock().unwrap();
+                let until = Instant::now() + Duration::from_secs(refresh_ttl() as u64);
+                match store.sessions.get_mut(session_id) {
+                    Some((current, expiry))
+                        if *expiry > Instant::now() && current.secret == secret =>
+                    {
+                        *current = data.clone();
+                        *expiry = until;
+                        true
+                    }
+                    _ => false,
+                }
+            }
+        };
+        if !rotated {
+            self.revoke(session_id).await?;
+            return Err(AuthError::InvalidRefreshToken.into());
+        }
+
+        Ok(Session {
+            id: session_id.to_owned(),
+            refresh_token: format!("{}.{}", session_id, data.secret),
+            user_id: data.user_id,
+            username: data.username,
+        })
+    }
+
+    pub async fn is_active(&self, session_id: &str) -> Result<bool> {
+        match &self.backend {
+            Backend::Redis(conn) => {
+                let mut conn = conn.clone();
+                let exists: bool = conn.exists(session_key(session_id)).await?;
+                Ok(exists)
+            }
+            Backend::Memory(store) => Ok(store.lock().unwrap().get(session_id).is_some()),
+        }
+    }
+
+    pub async fn revoke(&self, session_id: &str) -> Result<()> {
+        match &self.backend {
+            Backend::Redis(conn) => {
+                let mut conn = conn.clone();
+                if let Some(data) = self.load(session_id).await? {
+                    let _: () = conn
+                        .srem(user_sessions_key(data.user_id), session_id)
+                        .await?;
+                }
+                let _: () = conn.del(session_key(session_id)).await?;
+            }
+            Backend::Memory(store) => {
+                let mut store = store.lock().unwrap();
+                if let Some((data, _)) = store.sessions.remove(session_id) {
+                    if let Some(ids) = store.user_sessions.get_mut(&data.user_id) {
+                        ids.remove(session_id);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn revoke_user(&self, user_id: u64) -> Result<()> {
+        match &self.backend {
+            Backend::Redis(conn) => {
+                let mut conn = conn.clone();
+                let user_key = user_sessions_key(user_id);
+                let session_ids: Vec<String> = conn.smembers(&user_key).await?;
+                for session_id in session_ids.iter() {
+                    let _: () = conn.del(session_key(session_id)).await?;
+                }
+                let _: () = conn.del(&user_key).await?;
+            }
+            Backend::Memory(store) => {
+                let mut store = store.lock().unwrap();
+                let session_ids = store.user_sessions.remove(&user_id).unwrap_or_default();
+                for session_id in session_ids.iter() {
+                    store.sessions.remove(session_id);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn load(&self, session_id: &str) -> Result<Option<SessionData>> {
+        match &self.backend {
+            Backend::Redis(conn) => {
+                let mut conn = conn.clone();
+                let raw: Option<String> = conn.get(session_key(session_id)).await?;
+                match raw {
+                    Some(v) => Ok(Some(serde_json::from_str(&v)?)),
+                    None => Ok(None),
+                }
+            }
+            Backend::Memory(store) => Ok(store.lock().unwrap().get(session_id).cloned()),
+        }
+    }
+
+    /// Store the session and keep the list of the user's sessions around for
+    /// as long as its newest session.
+    async fn save(&self, session_id: &str, data: &SessionData) -> Result<()> {
+        match &self.backend {
+            Backend::Redis(conn) => {
+                let mut conn = conn.clone();
+                let value = serde_json::to_string(data)?;
+                let user_key = user_sessions_key(data.user_id);
+                let _: () = redis::pipe()
+                    .atomic()
+                    .set_ex(session_key(session_id), value, refresh_ttl())
+                    .ignore()
+                    .sadd(&user_key, session_id)
+                    .ignore()
+                    .expire(&user_key, refresh_ttl())
+                    .ignore()
+                    .query_async(&mut conn)
+                    .await?;
+            }
+            Backend::Memory(store) => {
+                let until = Instant::now() + Duration::from_secs(refresh_ttl() as u64);
+                let mut store = store.lock().unwrap();
+                store
+                    .sessions
+                    .insert(session_id.to_owned(), (data.clone(), until));
+                store
+                    .user_sessions
+                    .entry(data.user_id)
+                    .or_default()
+                    .insert(session_id.to_owned());
+            }
+        }
+        Ok(())
+    }
+}
+
+fn session_key(session_id: &str) -> String {
+    format!("{}{}", SESSION_PREFIX, session_id)
+}
+
+fn user_sessions_key(user_id: u64) -> String {
+    format!("{}{}", USER_SESSIONS_PREFIX, user_id)
+}
+
+fn refresh_ttl() -> usize {
+    chrono::Duration::days(REFRESH_TOKEN_DAYS).num_seconds() as usize
+}
+
+fn random_string(len: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(len)
+        .collect()
+}
@@ -0,0 +1,115 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+#[cfg(feature = "test-util")]
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+#[cfg(feature = "test-util")]
+use std::sync::Mutex;
+
+/// Where uploaded files are kept, a key is a relative path like
+/// `products/1/abc.jpg` and the returned url is what clients fetch.
+#[async_trait]
+pub trait Storage: std::fmt::Debug + Send + Sync {
+    async fn put(&self, key: &str, data: Vec<u8>) -> Result<String>;
+    async fn delete(&self, key: &str) -> Result<()>;
+    /// The key of a url returned by `put`, `None` for urls stored elsewhere.
+    fn key_of(&self, url: &str) -> Option<String>;
+}
+
+fn key_of(base_url: &str, url: &str) -> Option<String> {
+    url.strip_prefix(base_url)?
+        .strip_prefix('/')
+        .filter(|key| !key.is_empty())
+        .map(str::to_owned)
+}
+
+/// Files on the local disk, served by the api under `base_url`.
+#[derive(Debug)]
+pub struct LocalStorage {
+    root: PathBuf,
+    base_url: String,
+}
+
+impl LocalStorage {
+    pub fn new(root: impl Into<PathBuf>, base_url: &str) -> Self {
+        Self {
+            root: root.into(),
+            base_url: base_url.trim_end_matches('/').to_owned(),
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn path(&self, key: &str) -> Result<PathBuf> {
+        let key = Path::new(key);
+        if !key.components().all(|c| matches!(c, Component::Normal(_))) {
+            return Err(anyhow!("Invalid storage key: {}", key.display()));
+        }
+        Ok(self.root.join(key))
+    }
+}
+
+#[async_trait]
+impl Storage for LocalStorage {
+    async fn put(&self, key: &str, data: Vec<u8>) -> Result<String> {
+        let path = self.path(key)?;
+        if let Some(dir) = path.parent() {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+        tokio::fs::write(&path, data).await?;
+        Ok(format!("{}/{}", self.base_url, key))
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        match tokio::fs::remove_file(self.path(key)?).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn key_of(&self, url: &str) -> Option<String> {
+        key_of(&self.base_url, url)
+    }
+}
+
+/// Files kept in process memory, for tests.
+#[cfg(feature = "test-util")]
+#[derive(Debug)]
+pub struct MemoryStorage {
+    base_url: String,
+    files: Mutex<HashMap<String, Vec<u8>>>,
+}
+
+#[cfg(feature = "test-util")]
+impl MemoryStorage {
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_owned(),
+            files: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<Vec<u8>> {
+        self.files.lock().unwrap().get(key).cloned()
+    }
+}
+
+#[cfg(feature = "test-util")]
+#[async_trait]
+impl Storage for MemoryStorage {
+    async fn put(&self, key: &str, data: Vec<u8>) -> Result<String> {
+        self.files.lock().unwrap().insert(key.to_owned(), data);
+        Ok(format!("{}/{}", self.base_url, key))
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        self.files.lock().unwrap().remove(key);
+        Ok(())
+    }
+
+    fn key_of(&self, url: &str) -> Option<String> {
+        key_of(&self.base_url, url)
+    }
+}
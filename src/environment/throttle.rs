@@ -0,0 +1,179 @@
+use anyhow::Result;
+use redis::aio::MultiplexedConnection;
+use redis::AsyncCommands;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::models::AuthError;
+
+const FAIL_PREFIX: &str = "kerria:login_fail:";
+const LOCK_PREFIX: &str = "kerria:login_lock:";
+
+/// Failed attempts are counted within this window.
+const FAIL_WINDOW_SECS: u64 = 15 * 60;
+/// Failures allowed per username before the account gets locked.
+const MAX_USER_FAILURES: u64 = 5;
+/// Failures allowed per client address before the address gets locked.
+const MAX_ADDR_FAILURES: u64 = 20;
+const BASE_LOCK_SECS: u64 = 30;
+const MAX_LOCK_SECS: u64 = 60 * 60;
+
+#[derive(Clone)]
+enum CounterStore {
+    Redis(MultiplexedConnection),
+    Memory(Arc<Mutex<HashMap<String, (u64, Instant)>>>),
+}
+
+/// Failed login counters per username and per client address, every failure
+/// past the limit doubles the lockout time.
+#[derive(Clone)]
+pub struct LoginThrottle {
+    store: CounterStore,
+}
+
+impl std::fmt::Debug for LoginThrottle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let backend = match self.store {
+            CounterStore::Redis(_) => "redis",
+            CounterStore::Memory(_) => "memory",
+        };
+        f.debug_struct("LoginThrottle")
+            .field("backend", &backend)
+            .finish()
+    }
+}
+
+impl LoginThrottle {
+    pub fn redis(conn: MultiplexedConnection) -> Self {
+        Self {
+            store: CounterStore::Redis(conn),
+        }
+    }
+
+    pub fn memory() -> Self {
+        Self {
+            store: CounterStore::Memory(Arc::new(Mutex::new(HashMap::new()))),
+        }
+    }
+
+    /// Reject the attempt while either the username or the address is locked.
+    pub async fn check(&self, username: &str, addr: &str) -> Result<()> {
+        let user_lock = self.locked_for(&user_key(username)).await?;
+        let addr_lock = self.locked_for(&addr_key(addr)).await?;
+        match user_lock.max(addr_lock) {
+            Some(secs) => Err(AuthError::TooManyAttempts(secs).into()),
+            None => Ok(()),
+        }
+    }
+
+    pub async fn record_failure(&self, username: &str, addr: &str) -> Result<()> {
+        let user_key = user_key(username);
+        let failures = self.incr(&user_key).await?;
+        if failures >= MAX_USER_FAILURES {
+            self.lock(&user_key, lock_secs(failures - MAX_USER_FAILURES))
+                .await?;
+        }
+        let addr_key = addr_key(addr);
+        let failures = self.incr(&addr_key).await?;
+        if failures >= MAX_ADDR_FAILURES {
+            self.lock(&addr_key, lock_secs(failures - MAX_ADDR_FAILURES))
+                .await?;
+        }
+        Ok(())
+    }
+
+    pub async fn record_success(&self, username: &str) -> Result<()> {
+        let key = user_key(username);
+        match &self.store {
+            CounterStore::Redis(conn) => {
+                let mut conn = conn.clone();
+                let _: () = conn
+                    .del(vec![format!("{}{}", FAIL_PREFIX, key), lock_key(&key)])
+                    .await?;
+            }
+            CounterStore::Memory(map) => {
+                let mut map = map.lock().unwrap();
+                map.remove(&format!("{}{}", FAIL_PREFIX, key));
+                map.remove(&lock_key(&key));
+            }
+        }
+        Ok(())
+    }
+
+    async fn incr(&self, key: &str) -> Result<u64> {
+        let key = format!("{}{}", FAIL_PREFIX, key);
+        match &self.store {
+            CounterStore::Redis(conn) => {
+                let mut conn = conn.clone();
+                let count: u64 = conn.incr(&key, 1).await?;
+                if count == 1 {
+                    let _: () = conn.expire(&key, FAIL_WINDOW_SECS as usize).await?;
+                }
+                Ok(count)
+            }
+            CounterStore::Memory(map) => {
+                let mut map = map.lock().unwrap();
+                let now = Instant::now();
+                let entry = map.entry(key).or_insert((0, now));
+                if entry.1 <= now {
+                    *entry = (0, now + Duration::from_secs(FAIL_WINDOW_SECS));
+                }
+                entry.0 += 1;
+                Ok(entry.0)
+            }
+        }
+    }
+
+    async fn lock(&self, key: &str, secs: u64) -> Result<()> {
+        let key = lock_key(key);
+        match &self.store {
+            CounterStore::Redis(conn) => {
+                let mut conn = conn.clone();
+                let _: () = conn.set_ex(&key, 1, secs as usize).await?;
+            }
+            CounterStore::Memory(map) => {
+                let until = Instant::now() + Duration::from_secs(secs);
+                map.lock().unwrap().insert(key, (1, until));
+            }
+        }
+        Ok(())
+    }
+
+    async fn locked_for(&self, key: &str) -> Result<Option<u64>> {
+        let key = lock_key(key);
+        match &self.store {
+            CounterStore::Redis(conn) => {
+                let mut conn = conn.clone();
+                let ttl: i64 = conn.ttl(&key).await?;
+                Ok(if ttl > 0 { Some(ttl as u64) } else { None })
+            }
+            CounterStore::Memory(map) => {
+                let now = Instant::now();
+                let locked = match map.lock().unwrap().get(&key) {
+                    Some((_, until)) if *until > now => Some((*until - now).as_secs().max(1)),
+                    _ => None,
+                };
+                Ok(locked)
+            }
+        }
+    }
+}
+
+fn user_key(username: &str) -> String {
+    format!("user:{}", username.to_lowercase())
+}
+
+fn addr_key(addr: &str) -> String {
+    format!("addr:{}", addr)
+}
+
+fn lock_key(key: &str) -> String {
+    format!("{}{}", LOCK_PREFIX, key)
+}
+
+fn lock_secs(extra_failures: u64) -> u64 {
+    BASE_LOCK_SECS
+        .saturating_mul(1u64 << extra_failures.min(16))
+        .min(MAX_LOCK_SECS)
+}
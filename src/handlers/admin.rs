@@ -1,57 +1,84 @@
 use anyhow::{anyhow, Result};
 use argon2::Config;
+use rand::distributions::Alphanumeric;
 use rand::Rng;
 use serde_json::json;
 use warp::http::StatusCode;
 
-use crate::environment::Environment;
+use crate::environment::{Environment, Session, ACCESS_TOKEN_MINUTES};
 use crate::models::admin::{
-    AdminLoginRequest, AdminLoginResponse, AdminUser, Claims, UpdatePassword,
+    AdminLoginRequest, AdminLoginResponse, AdminLoginUser, AdminUser, Claims, NewAdminUser,
+    RefreshTokenRequest, ResetPassword, UpdateAdminUser, UpdatePassword, UserStatus,
+    MIN_PASSWORD_LEN,
 };
-use crate::models::AuthError;
-use crate::sql;
+use crate::models::{AuthError, Paging, RespData, Validate};
 
-pub async fn login_handler(env: Environment, req: AdminLoginRequest) -> Result<impl warp::Reply> {
-    let res = sql::admin::get_user(env.db(), &req.username).await?;
+pub async fn login_handler(
+    env: Environment,
+    addr: String,
+    req: AdminLoginRequest,
+) -> Result<impl warp::Reply> {
+    let throttle = env.login_throttle();
+    throttle.check(&req.username, &addr).await?;
+    let res = env.admin_users().get_user(&req.username).await?;
     match res {
         Some(user) => {
             let is_valid = verify_password(&user.password, req.password.as_bytes())?;
             if is_valid {
-                let expiry = chrono::Utc::now()
-                    .checked_add_signed(chrono::Duration::days(1))
-                    .expect("valid timestamp")
-                    .timestamp();
-                let claims = Claims {
-                    sub: user.id.to_string(),
-                    name: user.username,
-                    exp: expiry as usize,
-                };
-                let token = env.jwt().encode(claims)?;
-                let reply = warp::reply::json(&AdminLoginResponse {
-                    username: req.username,
-                    token,
-                    avatar: None,
-                });
-                return Ok(reply);
+                throttle.record_success(&req.username).await?;
+                let session = env.sessions().create(user.id, &user.username).await?;
+                let resp = issue_token(&env, session, &user)?;
+                return Ok(warp::reply::json(&resp));
             } else {
+                throttle.record_failure(&req.username, &addr).await?;
                 return Err(AuthError::InvalidCredentials.into());
             }
         }
-        None => return Err(AuthError::InvalidUserName.into()),
+        None => {
+            throttle.record_failure(&req.username, &addr).await?;
+            return Err(AuthError::InvalidUserName.into());
+        }
     }
 }
 
+pub async fn refresh_token_handler(
+    env: Environment,
+    req: RefreshTokenRequest,
+) -> Result<impl warp::Reply> {
+    let session = env.sessions().rotate(&req.refresh_token).await?;
+    let user = match env.admin_users().get_user(&session.username).await? {
+        Some(u) => u,
+        None => {
+            env.sessions().revoke(&session.id).await?;
+            return Err(AuthError::InvalidUserName.into());
+        }
+    };
+    let resp = issue_token(&env, session, &user)?;
+    Ok(warp::reply::json(&resp))
+}
+
+pub async fn logout_handler(env: Environment, user: AdminUser) -> Result<impl warp::Reply> {
+    env.sessions().revoke(&user.session_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
 pub async fn create_user_handler(
     env: Environment,
     user: AdminUser,
-    req: AdminLoginRequest,
+    req: NewAdminUser,
 ) -> Result<impl warp::Reply> {
-    if user.username != "admin".to_string() {
-        return Err(AuthError::NoPermissionError.into());
-    }
+    req.validate()?;
     let pw = hash_password(req.password.as_bytes())?;
-    sql::admin::create_user(env.db(), &req.username, &pw, user.username.as_str()).await?;
-    let reply = warp::reply::json(&json!({"username": req.username, "password": req.password}));
+    let id = env
+        .admin_users()
+        .create_user(&req.username, &pw, req.role, user.username.as_str())
+        .await?;
+    let reply = warp::reply::json(&json!({
+        "id": id,
+        "username": req.username,
+        "password": req.password,
+        "role": req.role,
+    }));
     let reply = warp::reply::with_status(reply, StatusCode::CREATED);
     Ok(reply)
 }
@@ -64,7 +91,11 @@ pub async fn get_current_user_handler(
     let token = env.jwt().trim_token(jwt)?;
     let reply = warp::reply::json(&AdminLoginResponse {
         username: user.username,
+        role: user.role,
         token,
+        refresh_token: None,
+        expires_in: None,
+        must_change_password: user.must_change_password,
         avatar: None,
     });
     return Ok(reply);
@@ -73,13 +104,15 @@ pub async fn get_current_user_handler(
 pub async fn update_password_handler(
     env: Environment,
     user: AdminUser,
-    jwt: String,
     req: UpdatePassword,
 ) -> Result<impl warp::Reply> {
-    if req.new_password.len() < 12 {
-        return Err(anyhow!("password length must not be shorter than 12."));
+    if req.new_password.len() < MIN_PASSWORD_LEN {
+        return Err(anyhow!(
+            "password length must not be shorter than {}.",
+            MIN_PASSWORD_LEN
+        ));
     }
-    let res = sql::admin::get_user(env.db(), &user.username).await?;
+    let res = env.admin_users().get_user(&user.username).await?;
     match res {
         None => return Err(AuthError::InvalidUserName.into()),
         Some(u) => {
@@ -88,22 +121,150 @@ pub async fn update_password_handler(
                 return Err(AuthError::InvalidCredentials.into());
             }
             let new_pw = hash_password(req.new_password.as_bytes())?;
-            sql::admin::update_password(env.db(), &user.username, &new_pw).await?;
-
-            let token = env.jwt().trim_token(jwt)?;
-            let reply = warp::reply::json(&AdminLoginResponse {
-                username: user.username,
-                token,
-                avatar: None,
-            });
-            Ok(reply)
+            env.admin_users()
+                .update_password(&user.username, &new_pw)
+                .await?;
+
+            // sign out everywhere, then start a fresh session for this client
+            env.sessions().revoke_user(u.id).await?;
+            let session = env.sessions().create(u.id, &u.username).await?;
+            let u = AdminLoginUser {
+                must_change_password: false,
+                ..u
+            };
+            let resp = issue_token(&env, session, &u)?;
+            Ok(warp::reply::json(&resp))
+        }
+    }
+}
+
+// user management
+
+pub async fn get_users_handler(env: Environment, paging: Paging) -> Result<impl warp::Reply> {
+    let total = env.admin_users().count_users().await?;
+    let res = env.admin_users().get_users(&paging).await?;
+    let reply = warp::reply::json(&RespData {
+        total: total as usize,
+        data: res,
+        next_cursor: None,
+    });
+    Ok(reply)
+}
+
+pub async fn get_user_handler(env: Environment, id: u64) -> Result<Box<dyn warp::Reply>> {
+    match env.admin_users().get_user_by_id(id).await? {
+        Some(user) => Ok(Box::new(warp::reply::json(&user))),
+        None => Ok(Box::new(StatusCode::NOT_FOUND)),
+    }
+}
+
+pub async fn update_user_handler(
+    env: Environment,
+    user: AdminUser,
+    id: u64,
+    req: UpdateAdminUser,
+) -> Result<impl warp::Reply> {
+    if id == user.id {
+        return Err(anyhow!("Can't change the role of yourself."));
+    }
+    let ok = env
+        .admin_users()
+        .update_user_role(id, req.role, &user.username)
+        .await?;
+    if ok {
+        return Ok(StatusCode::OK);
+    }
+    Err(anyhow!("Update user failed, id: {}.", id))
+}
+
+pub async fn update_user_status_handler(
+    env: Environment,
+    user: AdminUser,
+    id: u64,
+    status: UserStatus,
+) -> Result<impl warp::Reply> {
+    if id == user.id {
+        return Err(anyhow!("Can't disable or delete yourself."));
+    }
+    let ok = env
+        .admin_users()
+        .update_user_status(id, status, &user.username)
+        .await?;
+    if !ok {
+        return Err(anyhow!("Update user status failed, id: {}.", id));
+    }
+    if status != UserStatus::Active {
+        env.sessions().revoke_user(id).await?;
+    }
+    if status == UserStatus::Deleted {
+        return Ok(StatusCode::NO_CONTENT);
+    }
+    Ok(StatusCode::OK)
+}
+
+pub async fn reset_password_handler(
+    env: Environment,
+    user: AdminUser,
+    id: u64,
+    req: ResetPassword,
+) -> Result<impl warp::Reply> {
+    let password = match req.password {
+        Some(pw) if pw.len() < MIN_PASSWORD_LEN => {
+            return Err(anyhow!(
+                "password length must not be shorter than {}.",
+                MIN_PASSWORD_LEN
+            ));
         }
+        Some(pw) => pw,
+        None => generate_password(),
+    };
+    let pw = hash_password(password.as_bytes())?;
+    let ok = env
+        .admin_users()
+        .reset_password(id, &pw, &user.username)
+        .await?;
+    if !ok {
+        return Err(anyhow!("Reset password failed, id: {}.", id));
     }
+    env.sessions().revoke_user(id).await?;
+    let reply = warp::reply::json(&json!({ "id": id, "password": password }));
+    Ok(reply)
+}
+
+fn issue_token(
+    env: &Environment,
+    session: Session,
+    user: &AdminLoginUser,
+) -> Result<AdminLoginResponse> {
+    let expires_in = chrono::Duration::minutes(ACCESS_TOKEN_MINUTES);
+    let expiry = chrono::Utc::now()
+        .checked_add_signed(expires_in)
+        .expect("valid timestamp")
+        .timestamp();
+    let claims = Claims {
+        sub: session.user_id.to_string(),
+        name: session.username.clone(),
+        role: user.role,
+        sid: session.id,
+        must_change_password: user.must_change_password,
+        exp: expiry as usize,
+    };
+    let token = env.jwt().encode(claims)?;
+    Ok(AdminLoginResponse {
+        username: session.username,
+        role: user.role,
+        token,
+        refresh_token: Some(session.refresh_token),
+        expires_in: Some(expires_in.num_seconds()),
+        must_change_password: user.must_change_password,
+        avatar: None,
+    })
 }
 
 // encrypt
 
-fn hash_password(password: &[u8]) -> Result<String> {
+/// Argon2 encoded hash with a random salt, what `admin_user.password` keeps.
+pub fn hash_password(password: &[u8]) -> Result<String> {
     let salt: [u8; 32] = rand::thread_rng().gen();
     let config = Config::default();
     let encode =
@@ -111,6 +272,13 @@ fn hash_password(password: &[u8]) -> Result<String> {
     Ok(encode)
 }
 
+fn generate_password() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(16)
+        .collect()
+}
+
 fn verify_password(password1: &str, password2: &[u8]) -> Result<bool> {
     argon2::verify_encoded(password1, password2).map_err(|_| AuthError::InvalidCredentials.into())
 }
@@ -0,0 +1,16 @@
+use anyhow::Result;
+
+use crate::environment::Environment;
+use crate::models::audit::AuditQuery;
+use crate::models::RespData;
+
+pub async fn get_audit_logs(env: Environment, query: AuditQuery) -> Result<impl warp::Reply> {
+    let total = env.audit_logs().count_audit_logs(&query).await?;
+    let res = env.audit_logs().get_audit_logs(&query).await?;
+    let reply = warp::reply::json(&RespData {
+        total: total as usize,
+        data: res,
+        next_cursor: None,
+    });
+    Ok(reply)
+}
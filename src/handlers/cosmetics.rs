@@ -1,10 +1,25 @@
 use crate::environment::Environment;
-use crate::models::cosmetics::{Brand, BrandSequence, NewBrand, NewProduct};
-use crate::models::{Paging, RespData, Validate};
-use crate::sql;
+use crate::helpers::spreadsheet::{self, Tabular};
+use crate::helpers::upload;
+use crate::models::admin::{AdminUser, Permission};
+use crate::models::audit::AuditAction;
+use crate::models::cosmetics::{
+    build_category_tree, Brand, BrandSequence, Category, CategorySequence, ExportQuery, FileFormat,
+    HotSlotQuery, ImageSequence, ImportError, ImportQuery, ImportReport, NewBrand, NewCategory,
+    NewHotList, NewProduct, NewSku, PriceHistoryQuery, ProductDetail, ProductFilter, ProductImage,
+    ProductItem, ProductPrice, ProductStatus, SearchQuery, Sku, SkuItem, SkuStatus, UpdateBrand,
+    DEFAULT_HOT_SLOT,
+};
+use crate::models::{Cursor, Paging, RespData, Validate, MAX_ROWS, MIN_ROWS};
 use anyhow::{anyhow, Result};
+use rand::distributions::Alphanumeric;
+use rand::Rng;
 use serde_json::json;
-use warp::http::StatusCode;
+use std::collections::{HashMap, HashSet};
+use warp::http::{header, Response, StatusCode};
+use warp::hyper::body::Sender;
+use warp::hyper::Body;
+use warp::multipart::FormData;
 
 // brand
 
@@ -13,7 +28,7 @@ pub async fn create_brand(
     brand: Brand,
     operator: &str,
 ) -> Result<impl warp::Reply> {
-    let res = sql::cosmetics::create_brand(env.db(), brand, operator).await?;
+    let res = env.brands().create_brand(brand, operator).await?;
     Ok(warp::reply::json(&json!({ "id": res })))
 }
 
@@ -28,41 +43,34 @@ pub async fn create_brands(
     for b in brands.iter() {
         b.validate()?;
     }
-    sql::cosmetics::is_brand_names_valid(env.db(), &brands).await?;
-    let mut max_sequence = sql::cosmetics::get_max_brand_sequence(env.db()).await?;
-    let new_brands: Vec<Brand> = brands
-        .iter()
-        .map(|b| {
-            max_sequence += 1;
-            Brand {
-                id: 0,
-                name: b.name.clone(),
-                sequence: max_sequence,
-                is_hot: false,
-            }
-        })
-        .collect();
-    let ok = sql::cosmetics::create_brands(env.db(), new_brands, operator).await?;
-    if ok {
-        return Ok(StatusCode::CREATED);
-    }
-    Err(anyhow!("Create brands failed.").into())
+    env.brands().create_brands(&brands, operator).await?;
+    Ok(StatusCode::CREATED)
 }
 
 pub async fn get_brands(env: Environment, paging: Paging) -> Result<impl warp::Reply> {
-    let res = sql::cosmetics::get_brands(env.db(), paging).await?;
+    let cursor = paging.cursor()?;
+    let total = env.brands().count_brands().await?;
+    let res = env.brands().get_brands(&paging, cursor).await?;
+    let next_cursor = match res.last() {
+        Some(b) if res.len() as u32 == paging.limit_or(MAX_ROWS) => {
+            Some(Cursor::new(b.sequence as i64, b.id).encode())
+        }
+        _ => None,
+    };
     let reply = warp::reply::json(&RespData {
-        total: res.len(),
+        total: total as usize,
         data: res,
+        next_cursor,
     });
     Ok(reply)
 }
 
 pub async fn get_all_brands(env: Environment) -> Result<impl warp::Reply> {
-    let res = sql::cosmetics::get_all_brands(env.db()).await?;
+    let res = env.brands().get_all_brands().await?;
     let reply = warp::reply::json(&RespData {
         total: res.len(),
         data: res,
+        next_cursor: None,
     });
     Ok(reply)
 }
@@ -72,14 +80,55 @@ pub async fn get_brand_detail(
     id: u32,
     paging: Paging,
 ) -> Result<impl warp::Reply> {
-    let res = sql::cosmetics::get_brand_detail(env.db(), id, paging).await?;
+    let cursor = paging.cursor()?;
+    let total = env.products().count_brand_products(id).await?;
+    let res = env
+        .products()
+        .get_brand_products(id, &paging, cursor)
+        .await?;
+    let next_cursor = match res.last() {
+        Some(p) if res.len() as u32 == paging.limit_or(MIN_ROWS) => {
+            Some(Cursor::new(0, p.id).encode())
+        }
+        _ => None,
+    };
     let reply = warp::reply::json(&RespData {
-        total: res.len(),
+        total: total as usize,
         data: res,
+        next_cursor,
     });
     Ok(reply)
 }
 
+pub async fn update_brand(
+    env: Environment,
+    id: u64,
+    brand: UpdateBrand,
+    operator: &str,
+) -> Result<impl warp::Reply> {
+    brand.validate()?;
+    let brand = UpdateBrand {
+        name: brand.name.trim().to_owned(),
+        ..brand
+    };
+    let before = env
+        .brands()
+        .get_brand(id)
+        .await?
+        .ok_or_else(|| anyhow!("Brand not exist, id: {}.", id))?;
+    if brand.name != before.name {
+        let names = vec![NewBrand {
+            name: brand.name.clone(),
+        }];
+        env.brands().is_brand_names_valid(&names).await?;
+    }
+    let ok = env.brands().update_brand(id, &brand, operator).await?;
+    if !ok {
+        return Err(anyhow!("Update brand failed, id: {}.", id));
+    }
+    Ok(StatusCode::OK)
+}
+
 pub async fn update_brands_sequence(
     env: Environment,
     bss: Vec<BrandSequence>,
@@ -88,21 +137,41 @@ pub async fn update_brands_sequence(
     if bss.len() == 0 {
         return Err(anyhow!("品牌顺序修改数据不能为空").into());
     }
-    let ids: Vec<String> = bss.iter().map(|bs| bs.id.to_string()).collect();
-    sql::cosmetics::is_brand_ids_valid(env.db(), ids).await?;
-    for bs in bss.iter() {
-        sql::cosmetics::update_brand_sequence(env.db(), bs, &operator).await?;
-    }
+    env.brands().update_brands_sequence(&bss, operator).await?;
+    Ok(warp::reply())
+}
 
+/// Replace the hot brands, they are ranked in the given order.
+pub async fn set_hot_brands(
+    env: Environment,
+    brand_ids: Vec<u64>,
+    operator: &str,
+) -> Result<impl warp::Reply> {
+    let mut seen = HashSet::new();
+    let brand_ids: Vec<u64> = brand_ids
+        .into_iter()
+        .filter(|id| seen.insert(*id))
+        .collect();
+    env.brands().set_hot_brands(&brand_ids, operator).await?;
     Ok(warp::reply())
 }
 
+pub async fn get_hot_brands(env: Environment) -> Result<impl warp::Reply> {
+    let res = env.brands().get_hot_brands().await?;
+    let reply = warp::reply::json(&RespData {
+        total: res.len(),
+        data: res,
+        next_cursor: None,
+    });
+    Ok(reply)
+}
+
 pub async fn delete_brand(env: Environment, id: u32, operator: &str) -> Result<impl warp::Reply> {
-    let ok = sql::cosmetics::delete_brand(env.db(), id, operator).await?;
-    if ok {
-        return Ok(StatusCode::NO_CONTENT);
+    let ok = env.brands().delete_brand(id, operator).await?;
+    if !ok {
+        return Err(anyhow!("Delete brand failed, id: {}", id).into());
     }
-    Err(anyhow!("Delete brand failed, id: {}", id).into())
+    Ok(StatusCode::NO_CONTENT)
 }
 
 // product
@@ -113,8 +182,12 @@ pub async fn create_product(
     operator: &str,
 ) -> Result<impl warp::Reply> {
     product.validate()?;
-    let brand_id = sql::cosmetics::get_brand_id(env.db(), &product.brand_name).await?;
-    let id = sql::cosmetics::create_product(env.db(), product, brand_id, operator).await?;
+    let brand_id = env.brands().get_brand_id(&product.brand_name).await?;
+    let id = env
+        .products()
+        .create_product(product, brand_id, operator)
+        .await?
+        .id;
     let reply = warp::reply::json(&json!({ "id": id }));
     let reply = warp::reply::with_status(reply, StatusCode::CREATED);
     Ok(reply)
@@ -126,21 +199,70 @@ pub async fn get_product(
     only_valid: bool,
 ) -> Result<Box<dyn warp::Reply>> {
     let res = if only_valid {
-        sql::cosmetics::get_valid_product(env.db(), id).await?
+        env.products().get_valid_product(id).await?
     } else {
-        sql::cosmetics::get_product(env.db(), id).await?
+        env.products().get_product(id).await?
     };
-    match res {
-        Some(product) => return Ok(Box::new(warp::reply::json(&product))),
+    let product = match res {
+        Some(product) => product,
         None => return Ok(Box::new(StatusCode::OK)),
+    };
+    if only_valid {
+        let skus = env.products().get_valid_skus(id).await?;
+        let skus = skus.into_iter().map(SkuItem::from).collect();
+        Ok(Box::new(warp::reply::json(&ProductDetail::<SkuItem> {
+            product,
+            skus,
+        })))
+    } else {
+        let skus = env.products().get_product_skus(id).await?;
+        Ok(Box::new(warp::reply::json(&ProductDetail {
+            product,
+            skus,
+        })))
     }
 }
 
-pub async fn get_products(env: Environment, paging: Paging) -> Result<impl warp::Reply> {
-    let res = sql::cosmetics::get_all_products(env.db(), paging).await?;
+pub async fn get_products(
+    env: Environment,
+    filter: ProductFilter,
+    paging: Paging,
+) -> Result<impl warp::Reply> {
+    if let (Some(min), Some(max)) = (filter.min_price, filter.max_price) {
+        if min > max {
+            return Err(anyhow!("最低价格不能高于最高价格"));
+        }
+    }
+    let total = env.products().count_products(&filter).await?;
+    let res = env
+        .products()
+        .get_products(
+            &filter,
+            paging.offset.unwrap_or(0),
+            paging.limit_or(MAX_ROWS),
+        )
+        .await?;
     let reply = warp::reply::json(&RespData {
-        total: res.len(),
+        total: total as usize,
+        data: res,
+        next_cursor: None,
+    });
+    Ok(reply)
+}
+
+pub async fn search_products(
+    env: Environment,
+    query: SearchQuery,
+    paging: Paging,
+) -> Result<impl warp::Reply> {
+    query.validate()?;
+    let q = query.q.trim();
+    let total = env.products().count_search_products(q).await?;
+    let res = env.products().search_products(q, &paging).await?;
+    let reply = warp::reply::json(&RespData {
+        total: total as usize,
         data: res,
+        next_cursor: None,
     });
     Ok(reply)
 }
@@ -148,58 +270,1059 @@ pub async fn get_products(env: Environment, paging: Paging) -> Result<impl warp:
 pub async fn update_product_by_admin(
     env: Environment,
     id: u64,
-    product: NewProduct,
-    operator: &str,
+    mut product: NewProduct,
+    user: &AdminUser,
 ) -> Result<impl warp::Reply> {
-    let ok = sql::cosmetics::update_product(env.db(), id, product, operator).await?;
-    if ok {
-        return Ok(StatusCode::OK);
+    let current = env
+        .products()
+        .get_product(id)
+        .await?
+        .ok_or_else(|| anyhow!("Product not exist, id: {}.", id))?;
+    if current.status == ProductStatus::Deleted {
+        return Err(anyhow!("回收站中的商品不能修改, id: {}", id));
     }
-    Err(anyhow!("Update product failed, id: {}.", id).into())
+    // the status is left to publish and unpublish
+    product.status = current.status;
+    if current.sell_price != product.sell_price || current.import_price != product.import_price {
+        user.role.require(Permission::UpdatePrice)?;
+    }
+    product.brand_id = env.brands().get_brand_id(&product.brand_name).await?;
+    env.products()
+        .update_product(id, product, user.username.as_str())
+        .await?
+        .ok_or_else(|| anyhow!("Update product failed, id: {}.", id))?;
+    Ok(StatusCode::OK)
 }
 
 pub async fn update_product(
     env: Environment,
     id: u64,
-    product: NewProduct,
+    mut product: NewProduct,
     operator: &str,
 ) -> Result<impl warp::Reply> {
-    let is_exist = sql::cosmetics::is_product_valid(env.db(), id).await?;
+    let is_exist = env.products().is_product_exist(id).await?;
     if !is_exist {
         return Err(anyhow!("Delete Failed, not exist, id: {}", id));
     }
-    let ok = sql::cosmetics::update_product(env.db(), id, product, operator).await?;
-    if ok {
-        return Ok(StatusCode::OK);
+    let before = env.products().get_product(id).await?;
+    if let Some(b) = &before {
+        product.status = b.status;
+    }
+    product.brand_id = env.brands().get_brand_id(&product.brand_name).await?;
+    env.products()
+        .update_product(id, product, operator)
+        .await?
+        .ok_or_else(|| anyhow!("Update product failed, id: {}.", id))?;
+    Ok(StatusCode::OK)
+}
+
+pub async fn update_product_price(
+    env: Environment,
+    id: u64,
+    price: ProductPrice,
+    operator: &str,
+) -> Result<impl warp::Reply> {
+    price.validate()?;
+    let before = env.products().get_product(id).await?;
+    if before.as_ref().map(|p| p.status) == Some(ProductStatus::Deleted) {
+        return Err(anyhow!("回收站中的商品不能修改, id: {}", id));
+    }
+    env.products()
+        .update_product_price(id, &price, operator)
+        .await?
+        .ok_or_else(|| anyhow!("Update product price failed, id: {}.", id))?;
+    Ok(StatusCode::OK)
+}
+
+// import
+
+const MAX_IMPORT_ROWS: usize = 5000;
+
+/// Validate every row of an uploaded spreadsheet, the rows are written in a
+/// single transaction only when committing and none of them has errors.
+pub async fn import_products(
+    env: Environment,
+    query: ImportQuery,
+    data: &[u8],
+    user: &AdminUser,
+) -> Result<impl warp::Reply> {
+    let rows = spreadsheet::read_rows(query.format, data)?;
+    if rows.len() > MAX_IMPORT_ROWS {
+        return Err(anyhow!("一次最多导入{}个商品", MAX_IMPORT_ROWS));
+    }
+    let mut report = ImportReport {
+        total: rows.len(),
+        ..Default::default()
+    };
+    let mut brands = HashMap::new();
+    let mut ids = HashSet::new();
+    let mut products = vec![];
+    for (row, record) in rows.iter() {
+        match check_import_row(&env, record, user, &mut brands, &mut ids).await {
+            Ok(p) => products.push(p),
+            Err(e) => report.errors.push(ImportError {
+                row: *row,
+                message: format!("{:#}", e),
+            }),
+        }
+    }
+    report.updated = products
+        .iter()
+        .filter(|(_, before)| before.is_some())
+        .count();
+    report.created = products.len() - report.updated;
+    if !report.errors.is_empty() {
+        let status = if query.commit {
+            StatusCode::UNPROCESSABLE_ENTITY
+        } else {
+            StatusCode::OK
+        };
+        return Ok(warp::reply::with_status(warp::reply::json(&report), status));
+    }
+    if !query.commit {
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&report),
+            StatusCode::OK,
+        ));
+    }
+
+    let products = products.into_iter().map(|(p, _)| p).collect();
+    env.products()
+        .import_products(products, user.username.as_str())
+        .await?;
+    report.committed = true;
+    Ok(warp::reply::with_status(
+        warp::reply::json(&report),
+        StatusCode::OK,
+    ))
+}
+
+// parse and check one import row, returns the product along with its current
+// state when the row updates an existing product
+async fn check_import_row(
+    env: &Environment,
+    record: &HashMap<String, String>,
+    user: &AdminUser,
+    brands: &mut HashMap<String, u64>,
+    ids: &mut HashSet<u64>,
+) -> Result<(NewProduct, Option<ProductItem>)> {
+    let mut product = NewProduct::from_row(record)?;
+    product.validate()?;
+    product.brand_id = match brands.get(&product.brand_name) {
+        Some(id) => *id,
+        None => {
+            let id = env.brands().get_brand_id(&product.brand_name).await?;
+            brands.insert(product.brand_name.clone(), id);
+            id
+        }
+    };
+    let id = match product.id {
+        Some(id) => id,
+        None => return Ok((product, None)),
+    };
+    if !ids.insert(id) {
+        return Err(anyhow!("商品ID重复: {}", id));
+    }
+    let current = env
+        .products()
+        .get_product(id)
+        .await?
+        .ok_or_else(|| anyhow!("Product not exist, id: {}.", id))?;
+    if current.status == ProductStatus::Deleted {
+        return Err(anyhow!("回收站中的商品不能修改, id: {}", id));
+    }
+    // rows without a status keep the current one
+    if record.get("status").map_or(true, |v| v.trim().is_empty()) {
+        product.status = current.status;
+    }
+    current.status.transition_to(product.status)?;
+    if current.sell_price != product.sell_price || current.import_price != product.import_price {
+        user.role.require(Permission::UpdatePrice)?;
+    }
+    Ok((product, Some(current)))
+}
+
+// export
+
+const EXPORT_BATCH_ROWS: u32 = 500;
+/// A workbook is built in memory, larger exports have to be csv or json lines.
+const MAX_XLSX_EXPORT_ROWS: u32 = 10000;
+
+/// Export the valid brands, csv and json lines are streamed page by page.
+pub async fn export_brands(env: Environment, query: ExportQuery) -> Result<impl warp::Reply> {
+    if query.format == FileFormat::Xlsx {
+        let res = env.brands().get_all_brands().await?;
+        let data = spreadsheet::write_all(query.format, "brands", &res)?;
+        return attachment(query.format, "brands", Body::from(data));
+    }
+
+    let (mut sender, body) = Body::channel();
+    tokio::spawn(async move {
+        if let Err(e) = stream_brands(&env, query.format, &mut sender).await {
+            tracing::error!("export brands failed: {:#}", e);
+            sender.abort();
+        }
+    });
+    attachment(query.format, "brands", body)
+}
+
+async fn stream_brands(env: &Environment, format: FileFormat, sender: &mut Sender) -> Result<()> {
+    let header = spreadsheet::write_header::<Brand>(format)?;
+    sender.send_data(header.into()).await?;
+    let paging = Paging {
+        offset: None,
+        limit: Some(MAX_ROWS),
+        cursor: None,
+    };
+    let mut cursor = None;
+    loop {
+        let res = env.brands().get_brands(&paging, cursor).await?;
+        if let Some(last) = res.last() {
+            cursor = Some(Cursor::new(last.sequence as i64, last.id));
+            let chunk = spreadsheet::write_rows(format, &res)?;
+            sender.send_data(chunk.into()).await?;
+        }
+        if (res.len() as u32) < MAX_ROWS {
+            return Ok(());
+        }
+    }
+}
+
+/// Export the products of the live list of a slot, csv and json lines are
+/// streamed batch by batch.
+pub async fn export_hot_products(
+    env: Environment,
+    slot: HotSlotQuery,
+    query: ExportQuery,
+) -> Result<impl warp::Reply> {
+    let res = env.hot_products().get_hot_product_items(&slot.slot).await?;
+    if query.format == FileFormat::Xlsx {
+        let data = spreadsheet::write_all(query.format, "hot_products", &res)?;
+        return attachment(query.format, "hot_products", Body::from(data));
+    }
+
+    let (mut sender, body) = Body::channel();
+    tokio::spawn(async move {
+        if let Err(e) = stream_rows(&res, query.format, &mut sender).await {
+            tracing::error!("export hot products failed: {:#}", e);
+            sender.abort();
+        }
+    });
+    attachment(query.format, "hot_products", body)
+}
+
+async fn stream_rows<T: Tabular>(
+    rows: &[T],
+    format: FileFormat,
+    sender: &mut Sender,
+) -> Result<()> {
+    let header = spreadsheet::write_header::<T>(format)?;
+    sender.send_data(header.into()).await?;
+    for batch in rows.chunks(EXPORT_BATCH_ROWS as usize) {
+        let chunk = spreadsheet::write_rows(format, batch)?;
+        sender.send_data(chunk.into()).await?;
+    }
+    Ok(())
+}
+
+/// Export the products matching the filter, csv and json lines are streamed
+/// batch by batch while a workbook has to be built in memory, up to
+/// `MAX_XLSX_EXPORT_ROWS` products.
+pub async fn export_products(
+    env: Environment,
+    filter: ProductFilter,
+    query: ExportQuery,
+) -> Result<impl warp::Reply> {
+    if let (Some(min), Some(max)) = (filter.min_price, filter.max_price) {
+        if min > max {
+            return Err(anyhow!("最低价格不能高于最高价格"));
+        }
+    }
+    if query.format == FileFormat::Xlsx {
+        let total = env.products().count_products(&filter).await?;
+        if total > MAX_XLSX_EXPORT_ROWS as i64 {
+            return Err(anyhow!(
+                "xlsx一次最多导出{}个商品, 请缩小筛选范围或导出csv",
+                MAX_XLSX_EXPORT_ROWS
+            ));
+        }
+        let res = env
+            .products()
+            .get_products(&filter, 0, MAX_XLSX_EXPORT_ROWS)
+            .await?;
+        let data = spreadsheet::write_all(query.format, "products", &res)?;
+        return attachment(query.format, "products", Body::from(data));
+    }
+
+    let (mut sender, body) = Body::channel();
+    tokio::spawn(async move {
+        if let Err(e) = stream_products(&env, &filter, query.format, &mut sender).await {
+            tracing::error!("export products failed: {:#}", e);
+            sender.abort();
+        }
+    });
+    attachment(query.format, "products", body)
+}
+
+async fn stream_products(
+    env: &Environment,
+    filter: &ProductFilter,
+    format: FileFormat,
+    sender: &mut Sender,
+) -> Result<()> {
+    let header = spreadsheet::write_header::<ProductItem>(format)?;
+    sender.send_data(header.into()).await?;
+    let mut offset = 0;
+    loop {
+        let res = env
+            .products()
+            .get_products(filter, offset, EXPORT_BATCH_ROWS)
+            .await?;
+        if !res.is_empty() {
+            let chunk = spreadsheet::write_rows(format, &res)?;
+            sender.send_data(chunk.into()).await?;
+        }
+        if (res.len() as u32) < EXPORT_BATCH_ROWS {
+            return Ok(());
+        }
+        offset += EXPORT_BATCH_ROWS;
+    }
+}
+
+fn attachment(format: FileFormat, name: &str, body: Body) -> Result<Response<Body>> {
+    let filename = format!(
+        "{}-{}.{}",
+        name,
+        chrono::Local::now().format("%Y%m%d%H%M%S"),
+        spreadsheet::extension(format)
+    );
+    let resp = Response::builder()
+        .header(header::CONTENT_TYPE, spreadsheet::content_type(format))
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}\"", filename),
+        )
+        .body(body)?;
+    Ok(resp)
+}
+
+// product sku
+
+pub async fn get_skus(env: Environment, id: u64) -> Result<impl warp::Reply> {
+    let res = env.products().get_product_skus(id).await?;
+    let reply = warp::reply::json(&RespData {
+        total: res.len(),
+        data: res,
+        next_cursor: None,
+    });
+    Ok(reply)
+}
+
+/// The sku whose spec and prices the product shows, the first one on sale or
+/// else the first live one.
+fn default_sku_id(skus: impl IntoIterator<Item = (u64, i32, SkuStatus)>) -> Option<u64> {
+    skus.into_iter()
+        .filter(|(_, _, status)| *status != SkuStatus::Deleted)
+        .min_by_key(|(id, sequence, status)| (*status != SkuStatus::OnSale, *sequence, *id))
+        .map(|(id, _, _)| id)
+}
+
+fn sku_order(skus: &[Sku]) -> Vec<(u64, i32, SkuStatus)> {
+    skus.iter().map(|s| (s.id, s.sequence, s.status)).collect()
+}
+
+pub async fn create_sku(
+    env: Environment,
+    id: u64,
+    sku: NewSku,
+    user: &AdminUser,
+) -> Result<impl warp::Reply> {
+    sku.validate()?;
+    if !env.products().is_product_exist(id).await? {
+        return Err(anyhow!("商品不存在, id: {}", id));
+    }
+    // a sku placed first becomes the default one and sets the product prices,
+    // without a sequence it goes last
+    let skus = env.products().get_product_skus(id).await?;
+    let mut order = sku_order(&skus);
+    if sku.sequence != 0 {
+        order.push((u64::MAX, sku.sequence, sku.status));
+    }
+    if default_sku_id(order) == Some(u64::MAX) {
+        user.role.require(Permission::UpdatePrice)?;
+    }
+    if !sku.barcode.is_empty() && env.products().is_barcode_taken(&sku.barcode, 0).await? {
+        return Err(anyhow!("商品条码已存在: {}", sku.barcode));
+    }
+    let operator = user.username.as_str();
+    let after = env.products().create_sku(id, sku, operator).await?;
+    let reply = warp::reply::json(&json!({ "id": after.id }));
+    Ok(warp::reply::with_status(reply, StatusCode::CREATED))
+}
+
+pub async fn update_sku(
+    env: Environment,
+    id: u64,
+    sku_id: u64,
+    sku: NewSku,
+    user: &AdminUser,
+) -> Result<impl warp::Reply> {
+    sku.validate()?;
+    let current = env
+        .products()
+        .get_sku(id, sku_id)
+        .await?
+        .ok_or_else(|| anyhow!("规格不存在, id: {}", sku_id))?;
+    let price_changed =
+        current.sell_price != sku.sell_price || current.import_price != sku.import_price;
+    // moving or disabling a sku may hand the default to another one
+    let skus = env.products().get_product_skus(id).await?;
+    let before = default_sku_id(sku_order(&skus));
+    let after = default_sku_id(sku_order(&skus).into_iter().map(|s| match s {
+        (i, _, _) if i == sku_id => (i, sku.sequence, sku.status),
+        s => s,
+    }));
+    if price_changed || before != after {
+        user.role.require(Permission::UpdatePrice)?;
+    }
+    if !sku.barcode.is_empty()
+        && env
+            .products()
+            .is_barcode_taken(&sku.barcode, sku_id)
+            .await?
+    {
+        return Err(anyhow!("商品条码已存在: {}", sku.barcode));
+    }
+    env.products()
+        .update_sku(id, sku_id, &sku, user.username.as_str())
+        .await?
+        .ok_or_else(|| anyhow!("规格修改失败, id: {}", sku_id))?;
+    Ok(StatusCode::OK)
+}
+
+pub async fn delete_sku(
+    env: Environment,
+    id: u64,
+    sku_id: u64,
+    user: &AdminUser,
+) -> Result<impl warp::Reply> {
+    // the next sku takes over the product prices when the default one goes
+    let skus = env.products().get_product_skus(id).await?;
+    if default_sku_id(sku_order(&skus)) == Some(sku_id) {
+        user.role.require(Permission::UpdatePrice)?;
     }
-    Err(anyhow!("Update product failed, id: {}.", id).into())
+    env.products()
+        .delete_sku(id, sku_id, user.username.as_str())
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn get_price_history(
+    env: Environment,
+    id: u64,
+    query: PriceHistoryQuery,
+    only_valid: bool,
+) -> Result<impl warp::Reply> {
+    let res = if only_valid && !env.products().is_product_valid(id).await? {
+        vec![]
+    } else {
+        let records = env.products().get_price_history(id, &query).await?;
+        query.granularity.aggregate(&records, !only_valid)
+    };
+    let reply = warp::reply::json(&RespData {
+        total: res.len(),
+        data: res,
+        next_cursor: None,
+    });
+    Ok(reply)
 }
 
 pub async fn delete_product(env: Environment, id: u64, operator: &str) -> Result<impl warp::Reply> {
-    let ok = sql::cosmetics::delete_product(env.db(), id, operator).await?;
-    if ok {
-        return Ok(StatusCode::NO_CONTENT);
+    change_product_status(
+        &env,
+        id,
+        ProductStatus::Deleted,
+        AuditAction::Delete,
+        operator,
+    )
+    .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Put a draft or an off shelf product on shelf.
+pub async fn publish_product(
+    env: Environment,
+    id: u64,
+    operator: &str,
+) -> Result<impl warp::Reply> {
+    change_product_status(
+        &env,
+        id,
+        ProductStatus::OnShelf,
+        AuditAction::Publish,
+        operator,
+    )
+    .await?;
+    Ok(StatusCode::OK)
+}
+
+/// Take a product off shelf, it stays editable but the public api hides it.
+pub async fn unpublish_product(
+    env: Environment,
+    id: u64,
+    operator: &str,
+) -> Result<impl warp::Reply> {
+    change_product_status(
+        &env,
+        id,
+        ProductStatus::OffShelf,
+        AuditAction::Unpublish,
+        operator,
+    )
+    .await?;
+    Ok(StatusCode::OK)
+}
+
+async fn change_product_status(
+    env: &Environment,
+    id: u64,
+    to: ProductStatus,
+    action: AuditAction,
+    operator: &str,
+) -> Result<()> {
+    let before = env
+        .products()
+        .get_product(id)
+        .await?
+        .ok_or_else(|| anyhow!("Product not exist, id: {}.", id))?;
+    if !before.status.can_transition_to(to) {
+        return Err(anyhow!(
+            "商品状态不能从{}变为{}",
+            before.status.as_str(),
+            to.as_str()
+        ));
     }
-    Err(anyhow!("Delete product failed, id: {}", id).into())
+    move_product_status(env, before, to, action, operator).await
 }
 
+// the status move itself, checked by the caller
+async fn move_product_status(
+    env: &Environment,
+    before: ProductItem,
+    to: ProductStatus,
+    action: AuditAction,
+    operator: &str,
+) -> Result<()> {
+    let id = before.id;
+    let ok = env
+        .products()
+        .update_product_status(id, before.status, to, action, operator)
+        .await?;
+    if !ok {
+        return Err(anyhow!("商品状态已被修改, 请刷新后重试, id: {}", id));
+    }
+    Ok(())
+}
+
+// trash
+
+pub async fn get_deleted_brands(env: Environment, paging: Paging) -> Result<impl warp::Reply> {
+    let total = env.brands().count_deleted_brands().await?;
+    let res = env.brands().get_deleted_brands(&paging).await?;
+    let reply = warp::reply::json(&RespData {
+        total: total as usize,
+        data: res,
+        next_cursor: None,
+    });
+    Ok(reply)
+}
+
+pub async fn get_deleted_products(env: Environment, paging: Paging) -> Result<impl warp::Reply> {
+    let total = env.products().count_deleted_products().await?;
+    let res = env.products().get_deleted_products(&paging).await?;
+    let reply = warp::reply::json(&RespData {
+        total: total as usize,
+        data: res,
+        next_cursor: None,
+    });
+    Ok(reply)
+}
+
+pub async fn restore_brand(env: Environment, id: u64, operator: &str) -> Result<impl warp::Reply> {
+    let deleted = env
+        .brands()
+        .get_deleted_brand(id)
+        .await?
+        .ok_or_else(|| anyhow!("回收站中没有该品牌, id: {}", id))?;
+    // another brand may have taken the name since
+    let names = vec![NewBrand { name: deleted.name }];
+    env.brands().is_brand_names_valid(&names).await?;
+    let ok = env.brands().restore_brand(id, operator).await?;
+    if !ok {
+        return Err(anyhow!("Restore brand failed, id: {}", id));
+    }
+    Ok(StatusCode::OK)
+}
+
+/// Bring a deleted product back off shelf, to be checked before publishing.
+/// Only the trash does this, no status change leads out of deleted.
+pub async fn restore_product(
+    env: Environment,
+    id: u64,
+    operator: &str,
+) -> Result<impl warp::Reply> {
+    let product = env
+        .products()
+        .get_product(id)
+        .await?
+        .ok_or_else(|| anyhow!("Product not exist, id: {}.", id))?;
+    if product.status != ProductStatus::Deleted {
+        return Err(anyhow!("回收站中没有该商品, id: {}", id));
+    }
+    move_product_status(
+        &env,
+        product,
+        ProductStatus::OffShelf,
+        AuditAction::Restore,
+        operator,
+    )
+    .await?;
+    Ok(StatusCode::OK)
+}
+
+/// Remove a deleted brand for good, refused while any product, deleted ones
+/// included, or the hot brand list still points at it.
+pub async fn purge_brand(env: Environment, id: u64, operator: &str) -> Result<impl warp::Reply> {
+    env.brands()
+        .get_deleted_brand(id)
+        .await?
+        .ok_or_else(|| anyhow!("回收站中没有该品牌, id: {}", id))?;
+    let count = env.products().count_all_brand_products(id).await?;
+    if count > 0 {
+        return Err(anyhow!("品牌下还有{}个商品, 不能彻底删除", count));
+    }
+    let hot_ids = env.brands().get_hot_brand_ids().await?;
+    if hot_ids.contains(&id) {
+        return Err(anyhow!("品牌在热门品牌中, 不能彻底删除"));
+    }
+    let ok = env.brands().purge_brand(id, operator).await?;
+    if !ok {
+        return Err(anyhow!("Purge brand failed, id: {}", id));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Remove a deleted product for good along with its skus, images and
+/// categories, refused while a hot list still shows it. The price history
+/// stays for the reports.
+pub async fn purge_product(env: Environment, id: u64, operator: &str) -> Result<impl warp::Reply> {
+    env.products()
+        .get_product(id)
+        .await?
+        .filter(|p| p.status == ProductStatus::Deleted)
+        .ok_or_else(|| anyhow!("回收站中没有该商品, id: {}", id))?;
+    let count = env.hot_products().count_hot_product_refs(id).await?;
+    if count > 0 {
+        return Err(anyhow!("商品还在{}个热门列表中, 不能彻底删除", count));
+    }
+    let images = env.products().get_product_images(id).await?;
+    let ok = env.products().purge_product(id, operator).await?;
+    if !ok {
+        return Err(anyhow!("Purge product failed, id: {}", id));
+    }
+    delete_image_files(&env, images.iter()).await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Replace the home list right away, kept for the single list admin page. The
+/// live list is ended rather than left to pile up under the new one.
 pub async fn add_hot_product(
     env: Environment,
     hot_products: Vec<u64>,
     operator: &str,
 ) -> Result<impl warp::Reply> {
-    sql::cosmetics::delete_hot_products(env.db(), operator).await?;
-    if !hot_products.is_empty() {
-        sql::cosmetics::create_hot_products(env.db(), hot_products, operator).await?;
+    let list = NewHotList {
+        slot: DEFAULT_HOT_SLOT.to_owned(),
+        product_ids: hot_products,
+        start_at: None,
+        end_at: None,
+    };
+    let product_ids = hot_list_product_ids(&env, &list).await?;
+    env.hot_products()
+        .replace_hot_list(&list, &product_ids, operator)
+        .await?;
+    Ok(warp::reply())
+}
+
+pub async fn create_hot_list(
+    env: Environment,
+    list: NewHotList,
+    operator: &str,
+) -> Result<impl warp::Reply> {
+    let product_ids = hot_list_product_ids(&env, &list).await?;
+    let id = env
+        .hot_products()
+        .create_hot_list(&list, &product_ids, operator)
+        .await?;
+    let reply = warp::reply::json(&json!({ "id": id }));
+    Ok(warp::reply::with_status(reply, StatusCode::CREATED))
+}
+
+/// Check a new list, its products without duplicates in the given order.
+async fn hot_list_product_ids(env: &Environment, list: &NewHotList) -> Result<Vec<u64>> {
+    list.validate()?;
+    let mut seen = HashSet::new();
+    let product_ids: Vec<u64> = list
+        .product_ids
+        .iter()
+        .copied()
+        .filter(|id| seen.insert(*id))
+        .collect();
+    for id in product_ids.iter() {
+        if !env.products().is_product_valid(*id).await? {
+            return Err(anyhow!("Product not exist, id: {}.", id));
+        }
+    }
+    Ok(product_ids)
+}
+
+pub async fn get_hot_lists(env: Environment, query: HotSlotQuery) -> Result<impl warp::Reply> {
+    let res = env.hot_products().get_hot_lists(&query.slot).await?;
+    let reply = warp::reply::json(&RespData {
+        total: res.len(),
+        data: res,
+        next_cursor: None,
+    });
+    Ok(reply)
+}
+
+pub async fn delete_hot_list(
+    env: Environment,
+    id: u64,
+    operator: &str,
+) -> Result<impl warp::Reply> {
+    let ok = env.hot_products().delete_hot_list(id, operator).await?;
+    if !ok {
+        return Err(anyhow!("Delete hot list failed, id: {}", id));
     }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn get_hot_products(env: Environment, query: HotSlotQuery) -> Result<impl warp::Reply> {
+    let res = env.hot_products().get_hot_products(&query.slot).await?;
+    let reply = warp::reply::json(&RespData {
+        total: res.len(),
+        data: res,
+        next_cursor: None,
+    });
+    Ok(reply)
+}
+
+pub async fn get_hot_product_cards(
+    env: Environment,
+    query: HotSlotQuery,
+) -> Result<impl warp::Reply> {
+    let res = env
+        .hot_products()
+        .get_hot_product_cards(&query.slot)
+        .await?;
+    let reply = warp::reply::json(&RespData {
+        total: res.len(),
+        data: res,
+        next_cursor: None,
+    });
+    Ok(reply)
+}
+
+// product image
+
+/// Store the uploaded images after the existing ones, the first image of a
+/// product is also its `img_url`.
+pub async fn upload_product_images(
+    env: Environment,
+    id: u64,
+    form: FormData,
+    operator: &str,
+) -> Result<impl warp::Reply> {
+    if env.products().get_product(id).await?.is_none() {
+        return Err(anyhow!("Product not exist, id: {}.", id));
+    }
+    let files = upload::read_files(form).await?;
+    if files.is_empty() {
+        return Err(anyhow!("请选择要上传的图片"));
+    }
+    // check every file before storing any of them
+    let images = tokio::task::spawn_blocking(move || {
+        files
+            .into_iter()
+            .map(upload::process_image)
+            .collect::<Result<Vec<_>>>()
+    })
+    .await??;
+
+    let mut stored = vec![];
+    for image in images.into_iter() {
+        let key = format!("products/{}/{}", id, random_name());
+        let url = env
+            .storage()
+            .put(&format!("{}.{}", key, image.ext), image.data)
+            .await?;
+        let thumb_url = env
+            .storage()
+            .put(&format!("{}_thumb.{}", key, image.thumb_ext), image.thumb)
+            .await?;
+        stored.push((url, thumb_url));
+    }
+
+    let (_, after) = env
+        .products()
+        .create_product_images(id, &stored, operator)
+        .await?;
+    let reply = warp::reply::json(&RespData {
+        total: after.len(),
+        data: after,
+        next_cursor: None,
+    });
+    Ok(warp::reply::with_status(reply, StatusCode::CREATED))
+}
+
+pub async fn get_product_images(
+    env: Environment,
+    id: u64,
+    only_valid: bool,
+) -> Result<impl warp::Reply> {
+    let res = if only_valid && !env.products().is_product_valid(id).await? {
+        vec![]
+    } else {
+        env.products().get_product_images(id).await?
+    };
+    let reply = warp::reply::json(&RespData {
+        total: res.len(),
+        data: res,
+        next_cursor: None,
+    });
+    Ok(reply)
+}
+
+pub async fn update_images_sequence(
+    env: Environment,
+    id: u64,
+    iss: Vec<ImageSequence>,
+    operator: &str,
+) -> Result<impl warp::Reply> {
+    if iss.is_empty() {
+        return Err(anyhow!("图片顺序修改数据不能为空"));
+    }
+    env.products()
+        .update_images_sequence(id, &iss, operator)
+        .await?;
+    Ok(StatusCode::OK)
+}
+
+pub async fn delete_product_image(
+    env: Environment,
+    id: u64,
+    image_id: u64,
+    operator: &str,
+) -> Result<impl warp::Reply> {
+    let (before, _) = env
+        .products()
+        .delete_product_image(id, image_id, operator)
+        .await?;
+    let deleted = before.iter().filter(|i| i.id == image_id);
+    delete_image_files(&env, deleted).await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Remove the files of images whose rows are gone, the rows are what counts so
+/// a file left behind is only logged.
+async fn delete_image_files(env: &Environment, images: impl Iterator<Item = &ProductImage>) {
+    for image in images {
+        for url in [&image.url, &image.thumb_url].iter() {
+            let key = match env.storage().key_of(url) {
+                Some(key) => key,
+                None => continue,
+            };
+            if let Err(e) = env.storage().delete(&key).await {
+                tracing::warn!("delete image file {} failed: {:#}", key, e);
+            }
+        }
+    }
+}
+
+fn random_name() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(16)
+        .collect::<String>()
+        .to_lowercase()
+}
+
+// category
+
+pub async fn get_category_tree(env: Environment) -> Result<impl warp::Reply> {
+    let categories = env.categories().get_categories().await?;
+    let tree = build_category_tree(&categories);
+    let reply = warp::reply::json(&RespData {
+        total: categories.len(),
+        data: tree,
+        next_cursor: None,
+    });
+    Ok(reply)
+}
+
+pub async fn create_category(
+    env: Environment,
+    category: NewCategory,
+    operator: &str,
+) -> Result<impl warp::Reply> {
+    category.validate()?;
+    let parent_path = category_path(&env, category.parent_id).await?;
+    let id = env
+        .categories()
+        .create_category(&category, &parent_path, operator)
+        .await?
+        .id;
+    let reply = warp::reply::json(&json!({ "id": id }));
+    let reply = warp::reply::with_status(reply, StatusCode::CREATED);
+    Ok(reply)
+}
+
+pub async fn update_category(
+    env: Environment,
+    id: u64,
+    category: NewCategory,
+    operator: &str,
+) -> Result<impl warp::Reply> {
+    category.validate()?;
+    let before = env
+        .categories()
+        .get_category(id)
+        .await?
+        .ok_or_else(|| anyhow!("分类不存在, id: {}", id))?;
+    let parent_path = category_path(&env, category.parent_id).await?;
+    if parent_path.starts_with(&before.path) {
+        return Err(anyhow!("不能将分类移动到其自身或子分类下"));
+    }
+    let updated = Category {
+        parent_id: category.parent_id,
+        name: category.name,
+        ..before.clone()
+    };
+    env.categories()
+        .update_category(&updated, &parent_path, operator)
+        .await?
+        .ok_or_else(|| anyhow!("Update category failed, id: {}.", id))?;
+    Ok(StatusCode::OK)
+}
+
+pub async fn update_categories_sequence(
+    env: Environment,
+    css: Vec<CategorySequence>,
+    operator: &str,
+) -> Result<impl warp::Reply> {
+    if css.is_empty() {
+        return Err(anyhow!("分类顺序修改数据不能为空"));
+    }
+    env.categories()
+        .update_categories_sequence(&css, operator)
+        .await?;
     Ok(warp::reply())
 }
 
-pub async fn get_hot_products(env: Environment) -> Result<impl warp::Reply> {
-    let res = sql::cosmetics::get_hot_products(env.db()).await?;
+pub async fn delete_category(
+    env: Environment,
+    id: u64,
+    operator: &str,
+) -> Result<impl warp::Reply> {
+    env.categories()
+        .get_category(id)
+        .await?
+        .ok_or_else(|| anyhow!("分类不存在, id: {}", id))?;
+    if env.categories().count_child_categories(id).await? > 0 {
+        return Err(anyhow!("请先删除子分类, id: {}", id));
+    }
+    let ok = env.categories().delete_category(id, operator).await?;
+    if !ok {
+        return Err(anyhow!("Delete category failed, id: {}", id));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn get_category_products(
+    env: Environment,
+    id: u64,
+    paging: Paging,
+) -> Result<impl warp::Reply> {
+    let cursor = paging.cursor()?;
+    let (total, res) = match env.categories().get_category(id).await? {
+        Some(category) => {
+            let total = env
+                .categories()
+                .count_category_products(&category.path)
+                .await?;
+            let res = env
+                .categories()
+                .get_category_products(&category.path, &paging, cursor)
+                .await?;
+            (total as usize, res)
+        }
+        None => (0, vec![]),
+    };
+    let next_cursor = match res.last() {
+        Some(p) if res.len() as u32 == paging.limit_or(MIN_ROWS) => {
+            Some(Cursor::new(0, p.id).encode())
+        }
+        _ => None,
+    };
+    let reply = warp::reply::json(&RespData {
+        total,
+        data: res,
+        next_cursor,
+    });
+    Ok(reply)
+}
+
+pub async fn get_product_categories(env: Environment, id: u64) -> Result<impl warp::Reply> {
+    let res = env.categories().get_product_category_ids(id).await?;
     let reply = warp::reply::json(&RespData {
         total: res.len(),
         data: res,
+        next_cursor: None,
     });
     Ok(reply)
 }
+
+pub async fn set_product_categories(
+    env: Environment,
+    id: u64,
+    category_ids: Vec<u64>,
+    operator: &str,
+) -> Result<impl warp::Reply> {
+    if !env.products().is_product_exist(id).await? {
+        return Err(anyhow!("Product not exist, id: {}.", id));
+    }
+    let mut category_ids = category_ids;
+    category_ids.sort();
+    category_ids.dedup();
+    for category_id in category_ids.iter() {
+        if env.categories().get_category(*category_id).await?.is_none() {
+            return Err(anyhow!("分类不存在, id: {}", category_id));
+        }
+    }
+    env.categories()
+        .set_product_categories(id, &category_ids, operator)
+        .await?;
+    Ok(StatusCode::OK)
+}
+
+// path of the parent category, `/` for top level ones
+async fn category_path(env: &Environment, parent_id: u64) -> Result<String> {
+    if parent_id == 0 {
+        return Ok("/".to_owned());
+    }
+    match env.categories().get_category(parent_id).await? {
+        Some(parent) => Ok(parent.path),
+        None => Err(anyhow!("父分类不存在, id: {}", parent_id)),
+    }
+}
@@ -1 +1,3 @@
 pub mod problem;
+pub mod spreadsheet;
+pub mod upload;
@@ -21,11 +21,23 @@ pub fn pack(err: anyhow::Error) -> Problem {
             AuthError::NoAuthHeaderError
             | AuthError::InvalidAuthHeaderError
             | AuthError::InvalidUserName
-            | AuthError::InvalidCredentials => {
+            | AuthError::InvalidCredentials
+            | AuthError::InvalidRefreshToken
+            | AuthError::SessionRevoked => {
                 return Problem::new("Invalid Auth or Credentials")
                     .set_status(http::StatusCode::UNAUTHORIZED)
                     .set_detail(format!("{:#}", err));
             }
+            AuthError::TooManyAttempts(_) => {
+                return Problem::new("Too Many Login Attempts")
+                    .set_status(http::StatusCode::TOO_MANY_REQUESTS)
+                    .set_detail(format!("{:#}", err));
+            }
+            AuthError::NoPermissionError | AuthError::PasswordChangeRequired => {
+                return Problem::new("Permission Denied")
+                    .set_status(http::StatusCode::FORBIDDEN)
+                    .set_detail(format!("{:#}", err));
+            }
             _ => (),
         }
     }
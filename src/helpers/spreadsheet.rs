@@ -0,0 +1,264 @@
+use anyhow::{anyhow, Result};
+use calamine::{Reader, Xlsx};
+use serde::Serialize;
+use simple_excel_writer::{Row as SheetRow, Workbook};
+use std::collections::HashMap;
+use std::io::Cursor;
+
+use crate::models::cosmetics::{Brand, FileFormat, ProductItem};
+
+/// A data row keyed by its header, along with its row number in the file.
+pub type Row = (usize, HashMap<String, String>);
+
+/// Read every data row of a spreadsheet, the first row holds the headers.
+pub fn read_rows(format: FileFormat, data: &[u8]) -> Result<Vec<Row>> {
+    let mut records = match format {
+        FileFormat::Csv => read_csv(data)?,
+        FileFormat::Xlsx => read_xlsx(data)?,
+        FileFormat::Jsonl => return Err(anyhow!("JSON Lines 文件只能导出，不能导入")),
+    }
+    .into_iter();
+
+    let headers: Vec<String> = match records.next() {
+        Some(h) => h.iter().map(|v| v.trim().to_lowercase()).collect(),
+        None => return Err(anyhow!("文件内容为空")),
+    };
+    let rows = records
+        .enumerate()
+        .filter(|(_, record)| record.iter().any(|v| !v.trim().is_empty()))
+        .map(|(i, record)| {
+            let row = headers
+                .iter()
+                .cloned()
+                .zip(record.into_iter().map(unescape_formula))
+                .collect();
+            (i + 2, row)
+        })
+        .collect();
+    Ok(rows)
+}
+
+fn read_csv(data: &[u8]) -> Result<Vec<Vec<String>>> {
+    // excel saves csv files with a BOM
+    let data = data.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(data);
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(data);
+    let mut records = vec![];
+    for record in reader.records() {
+        records.push(record?.iter().map(str::to_owned).collect());
+    }
+    Ok(records)
+}
+
+fn read_xlsx(data: &[u8]) -> Result<Vec<Vec<String>>> {
+    let mut workbook = Xlsx::new(Cursor::new(data))?;
+    let range = workbook
+        .worksheet_range_at(0)
+        .ok_or_else(|| anyhow!("表格中没有工作表"))??;
+    let records = range
+        .rows()
+        .map(|row| row.iter().map(|cell| cell.to_string()).collect())
+        .collect();
+    Ok(records)
+}
+
+// spreadsheet programs run cells starting with these as formulas
+const FORMULA_PREFIXES: &[char] = &['=', '+', '-', '@'];
+
+/// Quote a text cell which would otherwise be taken for a formula, numbers are
+/// left alone.
+fn escape_formula(cell: String) -> String {
+    if cell.starts_with(FORMULA_PREFIXES) && cell.parse::<f64>().is_err() {
+        format!("'{}", cell)
+    } else {
+        cell
+    }
+}
+
+/// Undo `escape_formula`, so an exported file can be imported as it is.
+fn unescape_formula(cell: String) -> String {
+    match cell.strip_prefix('\'') {
+        Some(rest) if rest.starts_with(FORMULA_PREFIXES) => rest.to_owned(),
+        _ => cell,
+    }
+}
+
+/// Rows which can be exported as a table.
+pub trait Tabular: Serialize {
+    fn headers() -> &'static [&'static str];
+    fn cells(&self) -> Vec<String>;
+}
+
+impl Tabular for Brand {
+    fn headers() -> &'static [&'static str] {
+        &[
+            "id",
+            "name",
+            "en_name",
+            "logo_url",
+            "country",
+            "description",
+            "website",
+            "sequence",
+            "is_hot",
+        ]
+    }
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.id.to_string(),
+            self.name.clone(),
+            self.en_name.clone(),
+            self.logo_url.clone(),
+            self.country.clone(),
+            self.description.clone(),
+            self.website.clone(),
+            self.sequence.to_string(),
+            self.is_hot.to_string(),
+        ]
+    }
+}
+
+impl Tabular for ProductItem {
+    fn headers() -> &'static [&'static str] {
+        &[
+            "id",
+            "name",
+            "alias",
+            "title",
+            "subtitle",
+            "brand_id",
+            "brand_name",
+            "spec",
+            "kind",
+            "sell_price",
+            "import_price",
+            "sequence",
+            "jd_id",
+            "jd_url",
+            "img_url",
+            "status",
+            "comment",
+        ]
+    }
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.id.to_string(),
+            self.name.clone(),
+            self.alias.clone(),
+            self.title.clone(),
+            self.subtitle.clone(),
+            self.brand_id.to_string(),
+            self.brand_name.clone(),
+            self.spec.clone(),
+            self.kind.to_string(),
+            self.sell_price.to_string(),
+            self.import_price.to_string(),
+            self.sequence.to_string(),
+            self.jd_id.clone(),
+            self.jd_url.clone(),
+            self.img_url.clone(),
+            self.status.as_str().to_owned(),
+            self.comment.clone(),
+        ]
+    }
+}
+
+pub fn content_type(format: FileFormat) -> &'static str {
+    match format {
+        FileFormat::Csv => "text/csv; charset=utf-8",
+        FileFormat::Xlsx => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        FileFormat::Jsonl => "application/x-ndjson",
+    }
+}
+
+pub fn extension(format: FileFormat) -> &'static str {
+    match format {
+        FileFormat::Csv => "csv",
+        FileFormat::Xlsx => "xlsx",
+        FileFormat::Jsonl => "jsonl",
+    }
+}
+
+/// The beginning of a csv or json lines file.
+pub fn write_header<T: Tabular>(format: FileFormat) -> Result<Vec<u8>> {
+    match format {
+        FileFormat::Csv => {
+            // the BOM makes excel read the file as utf-8
+            let mut buf = b"\xEF\xBB\xBF".to_vec();
+            buf.extend(write_csv(&[T::headers()
+                .iter()
+                .map(|h| h.to_string())
+                .collect()])?);
+            Ok(buf)
+        }
+        FileFormat::Jsonl => Ok(vec![]),
+        FileFormat::Xlsx => Err(anyhow!("xlsx can't be written in chunks")),
+    }
+}
+
+/// Encode a chunk of rows for a csv or json lines file.
+pub fn write_rows<T: Tabular>(format: FileFormat, rows: &[T]) -> Result<Vec<u8>> {
+    match format {
+        FileFormat::Csv => write_csv(&rows.iter().map(export_cells).collect::<Vec<_>>()),
+        FileFormat::Jsonl => {
+            let mut buf = vec![];
+            for row in rows.iter() {
+                serde_json::to_writer(&mut buf, row)?;
+                buf.push(b'\n');
+            }
+            Ok(buf)
+        }
+        FileFormat::Xlsx => Err(anyhow!("xlsx can't be written in chunks")),
+    }
+}
+
+/// Encode a whole file in any format.
+pub fn write_all<T: Tabular>(format: FileFormat, sheet: &str, rows: &[T]) -> Result<Vec<u8>> {
+    match format {
+        FileFormat::Xlsx => write_xlsx(sheet, rows),
+        _ => {
+            let mut buf = write_header::<T>(format)?;
+            buf.extend(write_rows(format, rows)?);
+            Ok(buf)
+        }
+    }
+}
+
+fn export_cells<T: Tabular>(row: &T) -> Vec<String> {
+    row.cells().into_iter().map(escape_formula).collect()
+}
+
+fn write_csv(records: &[Vec<String>]) -> Result<Vec<u8>> {
+    let mut writer = csv::Writer::from_writer(vec![]);
+    for record in records.iter() {
+        writer.write_record(record)?;
+    }
+    Ok(writer.into_inner().map_err(|e| anyhow!("{}", e))?)
+}
+
+fn write_xlsx<T: Tabular>(name: &str, rows: &[T]) -> Result<Vec<u8>> {
+    let mut workbook = Workbook::create_in_memory();
+    let mut sheet = workbook.create_sheet(name);
+    workbook.write_sheet(&mut sheet, |writer| {
+        let mut header = SheetRow::new();
+        for h in T::headers().iter() {
+            header.add_cell(h.to_string());
+        }
+        writer.append_row(header)?;
+        for row in rows.iter() {
+            let mut cells = SheetRow::new();
+            for cell in export_cells(row) {
+                cells.add_cell(cell);
+            }
+            writer.append_row(cells)?;
+        }
+        Ok(())
+    })?;
+    workbook
+        .close()?
+        .ok_or_else(|| anyhow!("xlsx workbook is not in memory"))
+}
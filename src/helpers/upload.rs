@@ -0,0 +1,94 @@
+use anyhow::{anyhow, Result};
+use futures::TryStreamExt;
+use image::io::Reader;
+use image::{ImageFormat, ImageOutputFormat};
+use std::io::Cursor;
+use warp::hyper::body::Buf;
+use warp::multipart::{FormData, Part};
+
+pub const MAX_IMAGE_BYTES: usize = 5 * 1024 * 1024;
+/// A small file can still decode into a huge bitmap, 25 megapixels take up
+/// 100MB as rgba.
+pub const MAX_IMAGE_PIXELS: u64 = 25_000_000;
+const THUMB_SIZE: u32 = 320;
+
+/// A file part of a multipart form.
+#[derive(Debug)]
+pub struct UploadFile {
+    pub content_type: String,
+    pub data: Vec<u8>,
+}
+
+/// A checked image along with its thumbnail.
+#[derive(Debug)]
+pub struct Image {
+    pub data: Vec<u8>,
+    pub ext: &'static str,
+    pub thumb: Vec<u8>,
+    pub thumb_ext: &'static str,
+}
+
+/// Collect the parts named `file` of a multipart form.
+pub async fn read_files(form: FormData) -> Result<Vec<UploadFile>> {
+    let parts: Vec<Part> = form.try_collect().await?;
+    let mut files = vec![];
+    for part in parts.into_iter().filter(|p| p.name() == "file") {
+        let content_type = part.content_type().unwrap_or_default().to_owned();
+        let data = part
+            .stream()
+            .try_fold(Vec::new(), |mut data, buf| {
+                data.extend_from_slice(buf.bytes());
+                async move { Ok(data) }
+            })
+            .await?;
+        files.push(UploadFile { content_type, data });
+    }
+    Ok(files)
+}
+
+/// Check the type and size of an uploaded image and make its thumbnail, this
+/// blocks for a while and should run on a blocking thread.
+pub fn process_image(file: UploadFile) -> Result<Image> {
+    let (format, ext) = match file.content_type.as_str() {
+        "image/jpeg" => (ImageFormat::Jpeg, "jpg"),
+        "image/png" => (ImageFormat::Png, "png"),
+        "image/webp" => (ImageFormat::WebP, "webp"),
+        t => return Err(anyhow!("不支持的图片格式: {}", t)),
+    };
+    if file.data.len() > MAX_IMAGE_BYTES {
+        return Err(anyhow!(
+            "图片大小不能超过{}MB",
+            MAX_IMAGE_BYTES / 1024 / 1024
+        ));
+    }
+    // only the header is read to get the size
+    let (width, height) = Reader::with_format(Cursor::new(&file.data), format)
+        .into_dimensions()
+        .map_err(|e| anyhow!("图片无法解析: {}", e))?;
+    if u64::from(width) * u64::from(height) > MAX_IMAGE_PIXELS {
+        return Err(anyhow!(
+            "图片尺寸不能超过{}万像素: {}x{}",
+            MAX_IMAGE_PIXELS / 10_000,
+            width,
+            height
+        ));
+    }
+    let img = image::load_from_memory_with_format(&file.data, format)
+        .map_err(|e| anyhow!("图片无法解析: {}", e))?;
+
+    // webp can't be encoded, so its thumbnail is a png
+    let (output, thumb_ext) = match format {
+        ImageFormat::Jpeg => (ImageOutputFormat::Jpeg(85), "jpg"),
+        _ => (ImageOutputFormat::Png, "png"),
+    };
+    let mut thumb = vec![];
+    img.thumbnail(THUMB_SIZE, THUMB_SIZE)
+        .write_to(&mut thumb, output)?;
+
+    Ok(Image {
+        data: file.data,
+        ext,
+        thumb,
+        thumb_ext,
+    })
+}
@@ -3,6 +3,7 @@ pub mod environment;
 pub mod handlers;
 pub mod helpers;
 pub mod models;
+pub mod repo;
 pub mod sql;
 
 // pub use self::environment::Environment;
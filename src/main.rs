@@ -1,15 +1,25 @@
+use anyhow::anyhow;
 use clap::Clap;
 use hyper::server::Server;
 use listenfd::ListenFd;
+use sqlx::mysql::MySqlPool;
 use std::convert::Infallible;
+use std::io;
 use warp::{http::Method, Filter};
 
 use kerria::{
     api,
-    environment::{Args, Environment},
+    environment::{Args, Command, Environment, MigrateAction, SessionStore, UserAction},
+    handlers::admin::hash_password,
     helpers::problem,
+    models::admin::{NewAdminUser, MIN_PASSWORD_LEN},
+    models::{Paging, Validate, MAX_ROWS},
+    sql::{self, migrate},
 };
 
+// what the cli leaves in `creator` and `modifier`
+const CLI_OPERATOR: &str = "cli";
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     tracing_subscriber::fmt::init();
@@ -17,6 +27,14 @@ async fn main() -> anyhow::Result<()> {
         eprintln!("Warning: Did not find .env file in current working directory!");
     }
     let args = Args::parse();
+    match &args.command {
+        Some(Command::Migrate(m)) => {
+            let db_pool = MySqlPool::connect(&args.database_url).await?;
+            return run_migrate(&db_pool, &m.action).await;
+        }
+        Some(Command::User(u)) => return run_user(&args, &u.action).await,
+        None => (),
+    }
     let env = Environment::new(&args).await?;
     // let env = warp::any().map(move || env.clone());
     let cors = warp::cors()
@@ -29,11 +47,18 @@ async fn main() -> anyhow::Result<()> {
     let status = api::status();
     let cosmetics = api::cosmetics(env.clone());
     let admin_filters = api::admin_filters(env.clone());
+    let uploads = storage_path(&args.storage_url)?
+        .split('/')
+        .fold(warp::any().boxed(), |filter, segment| {
+            filter.and(warp::path(segment.to_owned())).boxed()
+        })
+        .and(warp::fs::dir(args.storage_dir.clone()));
 
     let svc = warp::service(
         status
             .or(cosmetics)
             .or(admin_filters)
+            .or(uploads)
             .recover(problem::unpack)
             .with(cors)
             .with(log),
@@ -56,3 +81,139 @@ async fn main() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+/// The path the local storage is served under, taken from `STORAGE_URL` which
+/// may also be a full url when a proxy or cdn in front forwards to it.
+fn storage_path(url: &str) -> anyhow::Result<&str> {
+    let path = match url.find("://") {
+        Some(i) => url[i + 3..].find('/').map_or("", |j| &url[i + 3 + j..]),
+        None => url,
+    };
+    match path.trim_matches('/') {
+        "" => Err(anyhow!(
+            "STORAGE_URL needs a path to serve the files under: {}",
+            url
+        )),
+        path => Ok(path),
+    }
+}
+
+async fn run_migrate(db_pool: &MySqlPool, action: &MigrateAction) -> anyhow::Result<()> {
+    match action {
+        MigrateAction::Up => {
+            let applied = migrate::up(db_pool).await?;
+            for m in applied.iter() {
+                println!("applied {} {}", m.version, m.description);
+            }
+            if applied.is_empty() {
+                println!("database schema is up to date");
+            }
+        }
+        MigrateAction::Status => {
+            for m in migrate::status(db_pool).await? {
+                println!("{:<10} {} {}", m.state.as_str(), m.version, m.description);
+            }
+        }
+        MigrateAction::Down => match migrate::down(db_pool).await? {
+            Some(m) => println!("reverted {} {}", m.version, m.description),
+            None => println!("no migration to revert"),
+        },
+    }
+    Ok(())
+}
+
+async fn run_user(args: &Args, action: &UserAction) -> anyhow::Result<()> {
+    let db_pool = MySqlPool::connect(&args.database_url).await?;
+    migrate::check(&db_pool).await?;
+    match action {
+        UserAction::Create {
+            username,
+            role,
+            password_stdin,
+        } => {
+            let user = NewAdminUser {
+                username: username.trim().to_owned(),
+                password: read_password(*password_stdin)?,
+                role: *role,
+            };
+            user.validate()?;
+            let pw = hash_password(user.password.as_bytes())?;
+            let id =
+                sql::admin::create_user(&db_pool, &user.username, &pw, user.role, CLI_OPERATOR)
+                    .await?;
+            println!(
+                "created {} {} with id {}",
+                user.role.as_str(),
+                user.username,
+                id
+            );
+        }
+        UserAction::ResetPassword {
+            username,
+            password_stdin,
+        } => {
+            let user = sql::admin::get_user_by_name(&db_pool, username)
+                .await?
+                .ok_or_else(|| anyhow!("user {} not found.", username))?;
+            let password = read_password(*password_stdin)?;
+            if password.len() < MIN_PASSWORD_LEN {
+                return Err(anyhow!(
+                    "password length must not be shorter than {}.",
+                    MIN_PASSWORD_LEN
+                ));
+            }
+            // sign out everywhere like a reset through the api does, connect
+            // first so the password isn't changed when redis is unreachable
+            let redis = redis::Client::open(args.redis_url.as_str())?
+                .get_multiplexed_tokio_connection()
+                .await?;
+            let pw = hash_password(password.as_bytes())?;
+            let ok = sql::admin::reset_password(&db_pool, user.id, &pw, CLI_OPERATOR).await?;
+            if !ok {
+                return Err(anyhow!("Reset password failed, id: {}.", user.id));
+            }
+            SessionStore::redis(redis).revoke_user(user.id).await?;
+            println!("reset the password of {}", user.username);
+        }
+        UserAction::List => {
+            let mut paging = Paging {
+                offset: Some(0),
+                limit: Some(MAX_ROWS),
+                cursor: None,
+            };
+            loop {
+                let users = sql::admin::get_users(&db_pool, &paging).await?;
+                for u in users.iter() {
+                    println!(
+                        "{:<6} {:<32} {:<16} {:<10} {}",
+                        u.id,
+                        u.username,
+                        u.role.as_str(),
+                        u.status.as_str(),
+                        u.created_at.format("%Y-%m-%d %H:%M:%S"),
+                    );
+                }
+                if users.len() < MAX_ROWS as usize {
+                    break;
+                }
+                paging.offset = Some(paging.offset() + MAX_ROWS);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A single line from stdin, or typed twice at a prompt.
+fn read_password(from_stdin: bool) -> anyhow::Result<String> {
+    if from_stdin {
+        let mut line = String::new();
+        io::stdin().read_line(&mut line)?;
+        return Ok(line.trim_end_matches(&['\r', '\n'][..]).to_owned());
+    }
+    // rpassword keeps the password off the screen when stdin is a terminal
+    let password = rpassword::prompt_password_stderr("Password: ")?;
+    if rpassword::prompt_password_stderr("Confirm password: ")? != password {
+        return Err(anyhow!("passwords don't match."));
+    }
+    Ok(password)
+}
@@ -1,12 +1,126 @@
+use anyhow::anyhow;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+use super::{AuthError, Validate};
+
+pub const MIN_PASSWORD_LEN: usize = 12;
+/// The name is kept as creator/modifier of the rows the user touches.
+pub const MAX_USERNAME_LEN: usize = 32;
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Claims {
     pub sub: String,
     pub name: String,
+    #[serde(default)]
+    pub role: Role,
+    pub sid: String,
+    #[serde(default)]
+    pub must_change_password: bool,
     pub exp: usize,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[repr(i8)]
+pub enum Role {
+    Viewer = 0,
+    CatalogEditor = 1,
+    PricingManager = 2,
+    Superuser = 3,
+}
+
+impl Default for Role {
+    fn default() -> Self {
+        Role::Viewer
+    }
+}
+
+impl std::str::FromStr for Role {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "viewer" => Ok(Role::Viewer),
+            "catalog_editor" => Ok(Role::CatalogEditor),
+            "pricing_manager" => Ok(Role::PricingManager),
+            "superuser" => Ok(Role::Superuser),
+            _ => Err(anyhow!("unknown role: {}", s)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[repr(i8)]
+pub enum UserStatus {
+    Active = 0,
+    Deleted = 1,
+    Disabled = 2,
+}
+
+impl UserStatus {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            UserStatus::Active => "active",
+            UserStatus::Deleted => "deleted",
+            UserStatus::Disabled => "disabled",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Permission {
+    ReadCatalog,
+    WriteCatalog,
+    DeleteCatalog,
+    UpdatePrice,
+    ManageUsers,
+    PurgeCatalog,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Role::Viewer => "viewer",
+            Role::CatalogEditor => "catalog_editor",
+            Role::PricingManager => "pricing_manager",
+            Role::Superuser => "superuser",
+        }
+    }
+
+    pub fn permissions(&self) -> &'static [Permission] {
+        match *self {
+            Role::Viewer => &[Permission::ReadCatalog],
+            Role::CatalogEditor => &[
+                Permission::ReadCatalog,
+                Permission::WriteCatalog,
+                Permission::DeleteCatalog,
+            ],
+            Role::PricingManager => &[Permission::ReadCatalog, Permission::UpdatePrice],
+            Role::Superuser => &[
+                Permission::ReadCatalog,
+                Permission::WriteCatalog,
+                Permission::DeleteCatalog,
+                Permission::UpdatePrice,
+                Permission::ManageUsers,
+                Permission::PurgeCatalog,
+            ],
+        }
+    }
+
+    pub fn has_permission(&self, permission: Permission) -> bool {
+        self.permissions().contains(&permission)
+    }
+
+    pub fn require(&self, permission: Permission) -> Result<(), AuthError> {
+        if self.has_permission(permission) {
+            return Ok(());
+        }
+        Err(AuthError::NoPermissionError)
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct AdminLoginRequest {
     pub username: String,
@@ -16,22 +130,95 @@ pub struct AdminLoginRequest {
 #[derive(Serialize, Debug)]
 pub struct AdminLoginResponse {
     pub username: String,
+    pub role: Role,
     pub token: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_in: Option<i64>,
+    pub must_change_password: bool,
     pub avatar: Option<String>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct RefreshTokenRequest {
+    pub refresh_token: String,
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct AdminLoginUser {
     pub id: u64,
     pub username: String,
     #[serde(skip_serializing)]
     pub password: String,
+    pub role: Role,
+    pub must_change_password: bool,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct AdminUser {
     pub id: u64,
     pub username: String,
+    pub role: Role,
+    pub must_change_password: bool,
+    #[serde(skip)]
+    pub session_id: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct AdminUserItem {
+    pub id: u64,
+    pub username: String,
+    pub role: Role,
+    pub status: UserStatus,
+    pub must_change_password: bool,
+    pub creator: String,
+    pub modifier: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct NewAdminUser {
+    pub username: String,
+    pub password: String,
+    #[serde(default)]
+    pub role: Role,
+}
+
+impl Validate for NewAdminUser {
+    fn validate(&self) -> Result<(), anyhow::Error> {
+        if self.username.trim().is_empty() {
+            return Err(anyhow!("username can't be empty."));
+        }
+        if self.username.chars().count() > MAX_USERNAME_LEN {
+            return Err(anyhow!(
+                "username length must not be longer than {}.",
+                MAX_USERNAME_LEN
+            ));
+        }
+        // deleted users get `#{id}` appended to free up their name
+        if self.username.contains('#') {
+            return Err(anyhow!("username can't contain '#'."));
+        }
+        if self.password.len() < MIN_PASSWORD_LEN {
+            return Err(anyhow!(
+                "password length must not be shorter than {}.",
+                MIN_PASSWORD_LEN
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateAdminUser {
+    pub role: Role,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResetPassword {
+    pub password: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -0,0 +1,142 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AuditAction {
+    Create,
+    Update,
+    UpdatePrice,
+    UpdateSequence,
+    Delete,
+    Replace,
+    UpdateCategories,
+    UpdateImages,
+    Publish,
+    Unpublish,
+    Restore,
+    Purge,
+}
+
+impl AuditAction {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            AuditAction::Create => "create",
+            AuditAction::Update => "update",
+            AuditAction::UpdatePrice => "update_price",
+            AuditAction::UpdateSequence => "update_sequence",
+            AuditAction::Delete => "delete",
+            AuditAction::Replace => "replace",
+            AuditAction::UpdateCategories => "update_categories",
+            AuditAction::UpdateImages => "update_images",
+            AuditAction::Publish => "publish",
+            AuditAction::Unpublish => "unpublish",
+            AuditAction::Restore => "restore",
+            AuditAction::Purge => "purge",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EntityType {
+    Brand,
+    Product,
+    Sku,
+    HotProduct,
+    HotBrand,
+    Category,
+}
+
+impl EntityType {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            EntityType::Brand => "brand",
+            EntityType::Product => "product",
+            EntityType::Sku => "sku",
+            EntityType::HotProduct => "hot_product",
+            EntityType::HotBrand => "hot_brand",
+            EntityType::Category => "category",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct NewAuditLog {
+    pub operator: String,
+    pub action: AuditAction,
+    pub entity_type: EntityType,
+    pub entity_id: u64,
+    pub before_data: Option<Value>,
+    pub after_data: Option<Value>,
+    pub diff: Option<Value>,
+}
+
+impl NewAuditLog {
+    pub fn new(
+        operator: &str,
+        action: AuditAction,
+        entity_type: EntityType,
+        entity_id: u64,
+        before_data: Option<Value>,
+        after_data: Option<Value>,
+    ) -> Self {
+        let diff = diff(before_data.as_ref(), after_data.as_ref());
+        Self {
+            operator: operator.to_owned(),
+            action,
+            entity_type,
+            entity_id,
+            before_data,
+            after_data,
+            diff,
+        }
+    }
+}
+
+pub fn snapshot<T: Serialize>(entity: &T) -> Option<Value> {
+    serde_json::to_value(entity).ok()
+}
+
+/// Changed top level fields as `{"field": {"from": .., "to": ..}}`.
+pub fn diff(before: Option<&Value>, after: Option<&Value>) -> Option<Value> {
+    let empty = Map::new();
+    let (before, after) = match (before, after) {
+        (None, None) => return None,
+        (Some(Value::Object(b)), None) => (b, &empty),
+        (None, Some(Value::Object(a))) => (&empty, a),
+        (Some(Value::Object(b)), Some(Value::Object(a))) => (b, a),
+        (b, a) if b == a => return None,
+        (b, a) => return Some(json!({ "from": b, "to": a })),
+    };
+    let mut changes = Map::new();
+    for key in before.keys().chain(after.keys()) {
+        let from = before.get(key).unwrap_or(&Value::Null);
+        let to = after.get(key).unwrap_or(&Value::Null);
+        if from != to && !changes.contains_key(key) {
+            changes.insert(key.clone(), json!({ "from": from, "to": to }));
+        }
+    }
+    Some(Value::Object(changes))
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct AuditLog {
+    pub id: u64,
+    pub operator: String,
+    pub action: String,
+    pub entity_type: String,
+    pub entity_id: u64,
+    pub before_data: Option<Value>,
+    pub after_data: Option<Value>,
+    pub diff: Option<Value>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuditQuery {
+    pub entity_type: Option<String>,
+    pub entity_id: Option<u64>,
+    pub operator: Option<String>,
+    pub offset: Option<u32>,
+    pub limit: Option<u32>,
+}
@@ -1,12 +1,21 @@
 use super::Validate;
 use anyhow::anyhow;
-use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, Utc};
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
 use sqlx::types::Decimal;
+use std::collections::HashMap;
+use std::convert::TryFrom;
 
 #[derive(Clone, Debug, Serialize)]
 pub struct Brand {
     pub id: u64,
     pub name: String,
+    pub en_name: String,
+    pub logo_url: String,
+    pub country: String,
+    pub description: String,
+    pub website: String,
     pub sequence: i32,
     pub is_hot: bool,
 }
@@ -25,6 +34,39 @@ impl Validate for NewBrand {
     }
 }
 
+/// Name and profile of a brand, the sequence is changed separately.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct UpdateBrand {
+    pub name: String,
+    #[serde(default)]
+    pub en_name: String,
+    #[serde(default)]
+    pub logo_url: String,
+    #[serde(default)]
+    pub country: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub website: String,
+}
+
+impl Validate for UpdateBrand {
+    fn validate(&self) -> Result<(), anyhow::Error> {
+        if self.name.trim().is_empty() {
+            return Err(anyhow!("Brand name can't be empty."));
+        }
+        if self.description.chars().count() > 1024 {
+            return Err(anyhow!("品牌简介不能超过1024个字"));
+        }
+        for url in [&self.logo_url, &self.website].iter() {
+            if !url.is_empty() && !url.starts_with("http://") && !url.starts_with("https://") {
+                return Err(anyhow!("请检查链接地址: {}", url));
+            }
+        }
+        Ok(())
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct BrandSequence {
     pub id: u64,
@@ -40,6 +82,207 @@ pub struct BrandItem {
     pub img_url: String,
 }
 
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+pub enum ProductSort {
+    #[serde(rename = "id")]
+    IdAsc,
+    #[serde(rename = "-id")]
+    IdDesc,
+    #[serde(rename = "sell_price")]
+    PriceAsc,
+    #[serde(rename = "-sell_price")]
+    PriceDesc,
+    #[serde(rename = "sequence")]
+    SequenceAsc,
+    #[serde(rename = "-sequence")]
+    SequenceDesc,
+    #[serde(rename = "updated_at")]
+    UpdatedAtAsc,
+    #[serde(rename = "-updated_at")]
+    UpdatedAtDesc,
+}
+
+impl Default for ProductSort {
+    fn default() -> Self {
+        ProductSort::IdAsc
+    }
+}
+
+impl ProductSort {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            ProductSort::IdAsc => "id",
+            ProductSort::IdDesc => "-id",
+            ProductSort::PriceAsc => "sell_price",
+            ProductSort::PriceDesc => "-sell_price",
+            ProductSort::SequenceAsc => "sequence",
+            ProductSort::SequenceDesc => "-sequence",
+            ProductSort::UpdatedAtAsc => "updated_at",
+            ProductSort::UpdatedAtDesc => "-updated_at",
+        }
+    }
+}
+
+/// Lifecycle of a product. The values keep `0` as on shelf and `1` as deleted
+/// like `CommonStatus`, so rows written before drafts existed read the same.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[repr(i8)]
+pub enum ProductStatus {
+    OnShelf = 0,
+    Deleted = 1,
+    OffShelf = 2,
+    Draft = 3,
+}
+
+impl ProductStatus {
+    /// Whether the public api shows products in this status.
+    pub fn is_visible(&self) -> bool {
+        *self == ProductStatus::OnShelf
+    }
+
+    pub fn can_transition_to(&self, to: ProductStatus) -> bool {
+        use ProductStatus::*;
+        match (*self, to) {
+            (Draft, OnShelf) | (Draft, Deleted) => true,
+            (OnShelf, OffShelf) | (OnShelf, Deleted) => true,
+            (OffShelf, OnShelf) | (OffShelf, Deleted) => true,
+            _ => false,
+        }
+    }
+
+    pub fn transition_to(&self, to: ProductStatus) -> Result<(), anyhow::Error> {
+        if *self == to || self.can_transition_to(to) {
+            return Ok(());
+        }
+        Err(anyhow!(
+            "商品状态不能从{}变为{}",
+            self.as_str(),
+            to.as_str()
+        ))
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            ProductStatus::OnShelf => "on_shelf",
+            ProductStatus::Deleted => "deleted",
+            ProductStatus::OffShelf => "off_shelf",
+            ProductStatus::Draft => "draft",
+        }
+    }
+}
+
+/// New products start as drafts, hidden until they are published.
+impl Default for ProductStatus {
+    fn default() -> Self {
+        ProductStatus::Draft
+    }
+}
+
+/// Takes either the name or the stored number, spreadsheets exported before
+/// the names existed carry numbers.
+impl std::str::FromStr for ProductStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "on_shelf" | "0" => Ok(ProductStatus::OnShelf),
+            "deleted" | "1" => Ok(ProductStatus::Deleted),
+            "off_shelf" | "2" => Ok(ProductStatus::OffShelf),
+            "draft" | "3" => Ok(ProductStatus::Draft),
+            _ => Err(anyhow!("status格式错误: {}", s)),
+        }
+    }
+}
+
+/// Same as `FromStr`, clients written before the names existed send numbers.
+impl<'de> Deserialize<'de> for ProductStatus {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct StatusVisitor;
+
+        impl<'de> Visitor<'de> for StatusVisitor {
+            type Value = ProductStatus;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a product status name or number")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                v.parse().map_err(E::custom)
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+                self.visit_str(&v.to_string())
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+                self.visit_str(&v.to_string())
+            }
+        }
+
+        deserializer.deserialize_any(StatusVisitor)
+    }
+}
+
+/// A soft deleted brand or product.
+#[derive(Debug, Serialize)]
+pub struct TrashItem {
+    pub id: u64,
+    pub name: String,
+    pub deleted_by: String,
+    pub deleted_at: DateTime<Utc>,
+}
+
+/// Filters of the admin product list, every field is optional and deleted
+/// products are left out unless asked for by `status`. `sort` takes a
+/// column name, prefixed with `-` for descending order.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ProductFilter {
+    pub brand_id: Option<u64>,
+    pub status: Option<ProductStatus>,
+    pub kind: Option<u8>,
+    pub min_price: Option<Decimal>,
+    pub max_price: Option<Decimal>,
+    pub creator: Option<String>,
+    pub created_from: Option<NaiveDate>,
+    pub created_to: Option<NaiveDate>,
+    pub updated_from: Option<NaiveDate>,
+    pub updated_to: Option<NaiveDate>,
+    #[serde(default)]
+    pub sort: ProductSort,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SearchQuery {
+    pub q: String,
+}
+
+impl Validate for SearchQuery {
+    fn validate(&self) -> Result<(), anyhow::Error> {
+        let len = self.q.trim().chars().count();
+        if len == 0 {
+            return Err(anyhow!("搜索关键词不能为空"));
+        }
+        if len > 64 {
+            return Err(anyhow!("搜索关键词不能超过64个字符"));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct SearchItem {
+    pub id: u64,
+    pub name: String,
+    pub title: String,
+    pub subtitle: String,
+    pub img_url: String,
+    pub brand_id: u64,
+    pub brand_name: String,
+    #[serde(skip_serializing)]
+    pub score: f64,
+}
+
 #[derive(Clone, Debug, Serialize)]
 pub struct ProductItem {
     pub id: u64,
@@ -57,7 +300,7 @@ pub struct ProductItem {
     pub jd_id: String,
     pub jd_url: String,
     pub img_url: String,
-    pub status: u8,
+    pub status: ProductStatus,
     pub comment: String,
 }
 
@@ -76,7 +319,9 @@ pub struct NewProduct {
     pub sequence: i32,
     pub jd_id: String,
     pub jd_url: String,
-    pub status: u8,
+    /// Only taken when creating, publish and unpublish change it afterwards.
+    #[serde(default)]
+    pub status: ProductStatus,
     pub comment: String,
 
     #[serde(skip_deserializing)]
@@ -96,14 +341,464 @@ impl Validate for NewProduct {
         if self.sell_price < Decimal::new(0, 2) {
             return Err(anyhow!("商品售价应为正数"));
         }
-        if self.status != 0 && self.status != 1 {
+        if self.status == ProductStatus::Deleted {
             return Err(anyhow!("请检查商品状态"));
         }
         Ok(())
     }
 }
 
+impl NewProduct {
+    /// Build a product from a spreadsheet row keyed by the header names, the
+    /// headers are the same as the json field names.
+    pub fn from_row(row: &HashMap<String, String>) -> Result<Self, anyhow::Error> {
+        let text = |key: &str| {
+            row.get(key)
+                .map(|v| v.trim().to_owned())
+                .unwrap_or_default()
+        };
+        let id = match text("id").as_str() {
+            "" => None,
+            v => Some(v.parse().map_err(|_| anyhow!("商品ID格式错误: {}", v))?),
+        };
+        let price = |key: &str| -> Result<Decimal, anyhow::Error> {
+            let v = text(key);
+            if v.is_empty() {
+                return Ok(Decimal::new(0, 2));
+            }
+            v.parse().map_err(|_| anyhow!("价格格式错误: {}", v))
+        };
+        let number = |key: &str| -> Result<i64, anyhow::Error> {
+            let v = text(key);
+            if v.is_empty() {
+                return Ok(0);
+            }
+            // spreadsheets tend to store integers as floats, e.g. `1.0`
+            v.parse::<f64>()
+                .ok()
+                .filter(|n| n.fract() == 0.0)
+                .map(|n| n as i64)
+                .ok_or_else(|| anyhow!("{}格式错误: {}", key, v))
+        };
+        let kind = number("kind")?;
+        let kind = u8::try_from(kind).map_err(|_| anyhow!("kind超出范围: {}", kind))?;
+        let sequence = number("sequence")?;
+        let sequence =
+            i32::try_from(sequence).map_err(|_| anyhow!("sequence超出范围: {}", sequence))?;
+
+        Ok(Self {
+            id,
+            name: text("name"),
+            alias: text("alias"),
+            title: text("title"),
+            subtitle: text("subtitle"),
+            brand_name: text("brand_name"),
+            spec: text("spec"),
+            kind,
+            sell_price: price("sell_price")?,
+            import_price: price("import_price")?,
+            sequence,
+            jd_id: text("jd_id"),
+            jd_url: text("jd_url"),
+            status: match text("status").as_str() {
+                "" => ProductStatus::default(),
+                v => v.parse()?,
+            },
+            comment: text("comment"),
+            img_url: String::new(),
+            brand_id: 0,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FileFormat {
+    Csv,
+    Xlsx,
+    Jsonl,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImportQuery {
+    pub format: FileFormat,
+    /// Only validate the rows unless this is set.
+    #[serde(default)]
+    pub commit: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExportQuery {
+    pub format: FileFormat,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportError {
+    /// Row number in the spreadsheet, the header is row 1.
+    pub row: usize,
+    pub message: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ImportReport {
+    pub total: usize,
+    pub created: usize,
+    pub updated: usize,
+    pub committed: bool,
+    pub errors: Vec<ImportError>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ProductPrice {
+    pub sell_price: Decimal,
+    pub import_price: Decimal,
+}
+
+impl Validate for ProductPrice {
+    fn validate(&self) -> Result<(), anyhow::Error> {
+        if self.sell_price < Decimal::new(0, 2) {
+            return Err(anyhow!("商品售价应为正数"));
+        }
+        if self.import_price < Decimal::new(0, 2) {
+            return Err(anyhow!("商品进货价应为正数"));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[repr(i8)]
+pub enum SkuStatus {
+    OnSale = 0,
+    Deleted = 1,
+    Disabled = 2,
+}
+
+impl Default for SkuStatus {
+    fn default() -> Self {
+        SkuStatus::OnSale
+    }
+}
+
+/// A variant of a product, the first one by sequence is the default and its
+/// spec and prices are mirrored on the product.
+#[derive(Clone, Debug, Serialize)]
+pub struct Sku {
+    pub id: u64,
+    pub product_id: u64,
+    pub spec: String,
+    pub kind: u8,
+    pub barcode: String,
+    pub sell_price: Decimal,
+    pub import_price: Decimal,
+    pub sequence: i32,
+    pub status: SkuStatus,
+}
+
+/// The public part of a sku.
+#[derive(Clone, Debug, Serialize)]
+pub struct SkuItem {
+    pub id: u64,
+    pub spec: String,
+    pub kind: u8,
+    pub barcode: String,
+    pub sell_price: Decimal,
+    pub sequence: i32,
+}
+
+impl From<Sku> for SkuItem {
+    fn from(sku: Sku) -> Self {
+        Self {
+            id: sku.id,
+            spec: sku.spec,
+            kind: sku.kind,
+            barcode: sku.barcode,
+            sell_price: sku.sell_price,
+            sequence: sku.sequence,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct NewSku {
+    pub spec: String,
+    #[serde(default)]
+    pub kind: u8,
+    #[serde(default)]
+    pub barcode: String,
+    pub sell_price: Decimal,
+    pub import_price: Decimal,
+    /// Placed after the existing skus when not given.
+    #[serde(default)]
+    pub sequence: i32,
+    #[serde(default)]
+    pub status: SkuStatus,
+}
+
+impl Validate for NewSku {
+    fn validate(&self) -> Result<(), anyhow::Error> {
+        if self.spec.trim().is_empty() {
+            return Err(anyhow!("商品规格不能为空"));
+        }
+        if self.kind != 0 && self.kind != 1 {
+            return Err(anyhow!("请检查商品类型"));
+        }
+        // EAN-8, UPC-A, EAN-13 and GTIN-14
+        let len = self.barcode.len();
+        if !self.barcode.is_empty()
+            && (![8, 12, 13, 14].contains(&len)
+                || !self.barcode.chars().all(|c| c.is_ascii_digit()))
+        {
+            return Err(anyhow!("商品条码格式错误: {}", self.barcode));
+        }
+        if self.status == SkuStatus::Deleted {
+            return Err(anyhow!("请检查规格状态"));
+        }
+        ProductPrice {
+            sell_price: self.sell_price,
+            import_price: self.import_price,
+        }
+        .validate()
+    }
+}
+
+/// A product along with its skus.
+#[derive(Debug, Serialize)]
+pub struct ProductDetail<T: Serialize> {
+    #[serde(flatten)]
+    pub product: ProductItem,
+    pub skus: Vec<T>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PriceRecord {
+    pub sell_price: Decimal,
+    pub import_price: Decimal,
+    pub price_time: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PriceGranularity {
+    Raw,
+    Day,
+    Week,
+}
+
+impl Default for PriceGranularity {
+    fn default() -> Self {
+        PriceGranularity::Raw
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PriceHistoryQuery {
+    pub sku_id: Option<u64>,
+    pub start: Option<NaiveDate>,
+    pub end: Option<NaiveDate>,
+    #[serde(default)]
+    pub granularity: PriceGranularity,
+}
+
+/// A point of the price chart, for day and week granularity `price_time` is
+/// the start of the period and `sell_price` the last price within it.
+#[derive(Debug, Clone, Serialize)]
+pub struct PricePoint {
+    pub price_time: NaiveDateTime,
+    pub sell_price: Decimal,
+    pub min_sell_price: Decimal,
+    pub max_sell_price: Decimal,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub import_price: Option<Decimal>,
+}
+
+impl PriceGranularity {
+    fn period_start(&self, time: NaiveDateTime) -> NaiveDateTime {
+        match *self {
+            PriceGranularity::Raw => time,
+            PriceGranularity::Day => time.date().and_hms(0, 0, 0),
+            PriceGranularity::Week => {
+                let date = time.date();
+                let monday = date - Duration::days(date.weekday().num_days_from_monday() as i64);
+                monday.and_hms(0, 0, 0)
+            }
+        }
+    }
+
+    /// Fold records ordered by `price_time` into chart points.
+    pub fn aggregate(&self, records: &[PriceRecord], with_import_price: bool) -> Vec<PricePoint> {
+        let mut points: Vec<PricePoint> = Vec::new();
+        for r in records.iter() {
+            let period = self.period_start(r.price_time);
+            let import_price = if with_import_price {
+                Some(r.import_price)
+            } else {
+                None
+            };
+            match points.last_mut() {
+                Some(p) if *self != PriceGranularity::Raw && p.price_time == period => {
+                    p.sell_price = r.sell_price;
+                    p.min_sell_price = p.min_sell_price.min(r.sell_price);
+                    p.max_sell_price = p.max_sell_price.max(r.sell_price);
+                    p.import_price = import_price;
+                }
+                _ => points.push(PricePoint {
+                    price_time: period,
+                    sell_price: r.sell_price,
+                    min_sell_price: r.sell_price,
+                    max_sell_price: r.sell_price,
+                    import_price,
+                }),
+            }
+        }
+        points
+    }
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct HotProduct {
     pub product_id: u64,
 }
+
+/// What the front end needs to render a product in a list.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProductCard {
+    pub id: u64,
+    pub name: String,
+    pub title: String,
+    pub subtitle: String,
+    pub img_url: String,
+    pub sell_price: Decimal,
+    pub brand_id: u64,
+    pub brand_name: String,
+}
+
+/// Slot of the legacy single hot product list.
+pub const DEFAULT_HOT_SLOT: &str = "home";
+const MAX_HOT_PRODUCTS: usize = 100;
+
+fn default_hot_slot() -> String {
+    DEFAULT_HOT_SLOT.to_owned()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HotSlotQuery {
+    #[serde(default = "default_hot_slot")]
+    pub slot: String,
+}
+
+/// A scheduled hot product list of a slot. The latest started list which has
+/// not ended is live, so a new list takes over at its `start_at`.
+#[derive(Debug, Clone, Serialize)]
+pub struct HotList {
+    pub id: u64,
+    pub slot: String,
+    pub start_at: NaiveDateTime,
+    pub end_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HotListItem {
+    #[serde(flatten)]
+    pub list: HotList,
+    pub product_ids: Vec<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewHotList {
+    #[serde(default = "default_hot_slot")]
+    pub slot: String,
+    pub product_ids: Vec<u64>,
+    pub start_at: Option<NaiveDateTime>,
+    pub end_at: Option<NaiveDateTime>,
+}
+
+impl Validate for NewHotList {
+    fn validate(&self) -> Result<(), anyhow::Error> {
+        let is_slug = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_';
+        if self.slot.is_empty() || self.slot.len() > 32 || !self.slot.chars().all(is_slug) {
+            return Err(anyhow!("展示位名称只能包含小写字母、数字和下划线"));
+        }
+        if self.product_ids.len() > MAX_HOT_PRODUCTS {
+            return Err(anyhow!("每个展示位最多{}个商品", MAX_HOT_PRODUCTS));
+        }
+        if let (Some(start), Some(end)) = (self.start_at, self.end_at) {
+            if end <= start {
+                return Err(anyhow!("结束时间应晚于开始时间"));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ProductImage {
+    pub id: u64,
+    pub product_id: u64,
+    pub url: String,
+    pub thumb_url: String,
+    pub sequence: i32,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ImageSequence {
+    pub id: u64,
+    pub sequence: i32,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Category {
+    pub id: u64,
+    pub parent_id: u64,
+    pub path: String,
+    pub name: String,
+    pub sequence: i32,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct NewCategory {
+    pub name: String,
+    #[serde(default)]
+    pub parent_id: u64,
+}
+
+impl Validate for NewCategory {
+    fn validate(&self) -> Result<(), anyhow::Error> {
+        if self.name.trim().len() == 0 {
+            return Err(anyhow!("分类名称不能为空"));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CategorySequence {
+    pub id: u64,
+    pub sequence: i32,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct CategoryNode {
+    pub id: u64,
+    pub name: String,
+    pub sequence: i32,
+    pub children: Vec<CategoryNode>,
+}
+
+/// Build the category forest from a flat list ordered by `sequence`.
+pub fn build_category_tree(categories: &[Category]) -> Vec<CategoryNode> {
+    fn children_of(parent_id: u64, categories: &[Category]) -> Vec<CategoryNode> {
+        categories
+            .iter()
+            .filter(|c| c.parent_id == parent_id)
+            .map(|c| CategoryNode {
+                id: c.id,
+                name: c.name.clone(),
+                sequence: c.sequence,
+                children: children_of(c.id, categories),
+            })
+            .collect()
+    }
+    children_of(0, categories)
+}
@@ -1,8 +1,10 @@
 pub mod admin;
+pub mod audit;
 pub mod cosmetics;
 
 use std::fmt;
 
+use anyhow::anyhow;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
@@ -13,12 +15,68 @@ pub const MAX_ROWS: u32 = 100;
 pub struct Paging {
     pub offset: Option<u32>,
     pub limit: Option<u32>,
+    pub cursor: Option<String>,
+}
+
+impl Paging {
+    pub fn limit_or(&self, default: u32) -> u32 {
+        self.limit.unwrap_or(default).min(MAX_ROWS)
+    }
+
+    /// A cursor takes the place of the offset.
+    pub fn offset(&self) -> u32 {
+        match self.cursor {
+            Some(_) => 0,
+            None => self.offset.unwrap_or(0),
+        }
+    }
+
+    pub fn cursor(&self) -> Result<Option<Cursor>, anyhow::Error> {
+        self.cursor.as_deref().map(Cursor::decode).transpose()
+    }
+}
+
+/// Opaque keyset cursor pointing right after the last row of a page, rows are
+/// ordered by `(sequence, id)`, or by `id` alone with `sequence` left as 0.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Cursor {
+    pub sequence: i64,
+    pub id: u64,
+}
+
+impl Cursor {
+    pub fn new(sequence: i64, id: u64) -> Self {
+        Self { sequence, id }
+    }
+
+    pub fn encode(&self) -> String {
+        let raw = format!("{}:{}", self.sequence, self.id);
+        base64::encode_config(raw, base64::URL_SAFE_NO_PAD)
+    }
+
+    pub fn decode(cursor: &str) -> Result<Self, anyhow::Error> {
+        let invalid = || anyhow!("Invalid cursor: {}", cursor);
+        let raw = base64::decode_config(cursor, base64::URL_SAFE_NO_PAD).map_err(|_| invalid())?;
+        let raw = String::from_utf8(raw).map_err(|_| invalid())?;
+        let mut parts = raw.splitn(2, ':');
+        let sequence = parts
+            .next()
+            .and_then(|v| v.parse().ok())
+            .ok_or_else(invalid)?;
+        let id = parts
+            .next()
+            .and_then(|v| v.parse().ok())
+            .ok_or_else(invalid)?;
+        Ok(Self { sequence, id })
+    }
 }
 
 #[derive(Debug, Serialize)]
 pub struct RespData<T: Serialize> {
     pub total: usize,
     pub data: T,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
 }
 
 #[derive(Error, Debug)]
@@ -35,10 +93,18 @@ pub enum AuthError {
     EncryptError,
     #[error("jwt token not valid")]
     JWTTokenError,
+    #[error("refresh token not valid")]
+    InvalidRefreshToken,
+    #[error("session has been revoked")]
+    SessionRevoked,
     #[error("jwt token creation error")]
     JWTTokenCreationError,
     #[error("no permission")]
     NoPermissionError,
+    #[error("password must be changed before continuing")]
+    PasswordChangeRequired,
+    #[error("too many failed login attempts, retry after {0} seconds")]
+    TooManyAttempts(u64),
 }
 
 #[derive(Debug)]
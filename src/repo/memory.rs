@@ -0,0 +1,2080 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, Utc};
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use super::{
+    category_not_found, image_not_found, removable_sku, AdminUserRepo, AuditLogRepo, BrandRepo,
+    CategoryRepo, Change, HotProductRepo, ProductRepo,
+};
+use crate::models::admin::{AdminLoginUser, AdminUserItem, Role, UserStatus};
+use crate::models::audit::{snapshot, AuditAction, AuditLog, AuditQuery, EntityType, NewAuditLog};
+use crate::models::cosmetics::{
+    Brand, BrandItem, BrandSequence, Category, CategorySequence, HotList, HotListItem, HotProduct,
+    ImageSequence, NewBrand, NewCategory, NewHotList, NewProduct, NewSku, PriceHistoryQuery,
+    PriceRecord, ProductCard, ProductFilter, ProductImage, ProductItem, ProductPrice, ProductSort,
+    ProductStatus, SearchItem, Sku, SkuStatus, TrashItem, UpdateBrand,
+};
+use crate::models::{Cursor, Paging, MAX_ROWS, MIN_ROWS};
+
+/// Everything in process memory, for tests behind the `test-util` feature. It
+/// follows the MySQL repo row for row, except that search is a substring match
+/// instead of the full-text index.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryRepo {
+    data: Arc<Mutex<Data>>,
+}
+
+impl MemoryRepo {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn data(&self) -> MutexGuard<'_, Data> {
+        self.data.lock().unwrap()
+    }
+}
+
+#[derive(Debug, Default)]
+struct Data {
+    ids: HashMap<&'static str, u64>,
+    brands: Vec<BrandRow>,
+    products: Vec<ProductRow>,
+    skus: Vec<Sku>,
+    images: Vec<ImageRow>,
+    prices: Vec<PriceRow>,
+    hot_brands: Vec<HotBrandRow>,
+    hot_lists: Vec<HotListRow>,
+    hot_products: Vec<HotProductRow>,
+    categories: Vec<CategoryRow>,
+    product_categories: Vec<(u64, u64)>,
+    users: Vec<UserRow>,
+    audit_logs: Vec<AuditLog>,
+}
+
+#[derive(Debug)]
+struct BrandRow {
+    brand: Brand,
+    valid: bool,
+    modifier: String,
+    updated_at: DateTime<Utc>,
+    deleted: Option<Deleted>,
+}
+
+#[derive(Debug)]
+struct ProductRow {
+    id: u64,
+    product: NewProduct,
+    creator: String,
+    modifier: String,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+    deleted: Option<Deleted>,
+}
+
+/// Who deleted a row and when, later writes to the row leave it alone.
+#[derive(Debug)]
+struct Deleted {
+    by: String,
+    at: DateTime<Utc>,
+}
+
+impl Deleted {
+    fn now(operator: &str) -> Option<Self> {
+        Some(Deleted {
+            by: operator.to_owned(),
+            at: Utc::now(),
+        })
+    }
+}
+
+#[derive(Debug)]
+struct ImageRow {
+    image: ProductImage,
+    valid: bool,
+}
+
+#[derive(Debug)]
+struct PriceRow {
+    id: u64,
+    product_id: u64,
+    sku_id: u64,
+    record: PriceRecord,
+}
+
+#[derive(Debug)]
+struct HotBrandRow {
+    id: u64,
+    brand_id: u64,
+    sequence: i32,
+    valid: bool,
+}
+
+#[derive(Debug)]
+struct HotListRow {
+    list: HotList,
+    valid: bool,
+}
+
+#[derive(Debug)]
+struct HotProductRow {
+    id: u64,
+    list_id: u64,
+    product_id: u64,
+    sequence: i32,
+}
+
+#[derive(Debug)]
+struct CategoryRow {
+    category: Category,
+    valid: bool,
+}
+
+#[derive(Debug)]
+struct UserRow {
+    user: AdminUserItem,
+    password: String,
+}
+
+impl Data {
+    // auto increment ids, each table counts on its own
+    fn next_id(&mut self, table: &'static str) -> u64 {
+        let id = self.ids.entry(table).or_insert(0);
+        *id += 1;
+        *id
+    }
+
+    fn brand(&self, id: u64) -> Option<&BrandRow> {
+        self.brands.iter().find(|b| b.brand.id == id)
+    }
+
+    fn brand_mut(&mut self, id: u64) -> Option<&mut BrandRow> {
+        self.brands.iter_mut().find(|b| b.brand.id == id)
+    }
+
+    fn is_valid_brand(&self, id: u64) -> bool {
+        self.brand(id).map_or(false, |b| b.valid)
+    }
+
+    fn to_brand(&self, row: &BrandRow) -> Brand {
+        let is_hot = self
+            .hot_brands
+            .iter()
+            .any(|h| h.valid && h.brand_id == row.brand.id);
+        Brand {
+            is_hot,
+            ..row.brand.clone()
+        }
+    }
+
+    // valid brands by `(sequence, id)`
+    fn valid_brands(&self) -> Vec<&BrandRow> {
+        let mut rows: Vec<&BrandRow> = self.brands.iter().filter(|b| b.valid).collect();
+        rows.sort_by_key(|b| (b.brand.sequence, b.brand.id));
+        rows
+    }
+
+    fn check_brand_names(&self, brands: &[NewBrand]) -> Result<()> {
+        let names: BTreeSet<&str> = brands.iter().map(|b| b.name.as_str()).collect();
+        let taken: Vec<&str> = names
+            .into_iter()
+            .filter(|name| self.brands.iter().any(|b| b.valid && b.brand.name == *name))
+            .collect();
+        if !taken.is_empty() {
+            return Err(anyhow!("Brand names already exist: {:?}", taken.join(", ")));
+        }
+        Ok(())
+    }
+
+    fn check_brand_ids(&self, ids: &[u64]) -> Result<()> {
+        if ids.iter().any(|id| !self.is_valid_brand(*id)) {
+            return Err(anyhow!("Brand ids not match: {:?}", ids));
+        }
+        Ok(())
+    }
+
+    fn product(&self, id: u64) -> Option<&ProductRow> {
+        self.products.iter().find(|p| p.id == id)
+    }
+
+    fn product_mut(&mut self, id: u64) -> Option<&mut ProductRow> {
+        self.products.iter_mut().find(|p| p.id == id)
+    }
+
+    // a product joined with its brand
+    fn to_item(&self, row: &ProductRow) -> Option<ProductItem> {
+        let brand = self.brand(row.product.brand_id)?;
+        let p = &row.product;
+        Some(ProductItem {
+            id: row.id,
+            name: p.name.clone(),
+            alias: p.alias.clone(),
+            title: p.title.clone(),
+            subtitle: p.subtitle.clone(),
+            brand_id: p.brand_id as u32,
+            brand_name: brand.brand.name.clone(),
+            spec: p.spec.clone(),
+            kind: p.kind,
+            sell_price: p.sell_price,
+            import_price: p.import_price,
+            sequence: p.sequence,
+            jd_id: p.jd_id.clone(),
+            jd_url: p.jd_url.clone(),
+            img_url: p.img_url.clone(),
+            status: p.status,
+            comment: p.comment.clone(),
+        })
+    }
+
+    fn get_product(&self, id: u64) -> Option<ProductItem> {
+        self.product(id).and_then(|p| self.to_item(p))
+    }
+
+    fn status_of(&self, id: u64) -> Option<ProductStatus> {
+        self.product(id).map(|p| p.product.status)
+    }
+
+    fn insert_product(&mut self, product: &NewProduct, brand_id: u64, operator: &str) -> u64 {
+        let id = self.next_id("product");
+        let now = Utc::now();
+        self.products.push(ProductRow {
+            id,
+            product: NewProduct {
+                id: Some(id),
+                brand_id,
+                ..product.clone()
+            },
+            creator: operator.to_owned(),
+            modifier: String::new(),
+            created_at: now,
+            updated_at: now,
+            deleted: None,
+        });
+        id
+    }
+
+    fn create_product(
+        &mut self,
+        product: &NewProduct,
+        brand_id: u64,
+        operator: &str,
+    ) -> Result<ProductItem> {
+        let id = self.insert_product(product, brand_id, operator);
+        let after = self
+            .get_product(id)
+            .ok_or_else(|| anyhow!("Create product failed."))?;
+        self.sync_default_sku(None, &after);
+        self.push_audit_log(NewAuditLog::new(
+            operator,
+            AuditAction::Create,
+            EntityType::Product,
+            id,
+            None,
+            snapshot(&after),
+        ));
+        Ok(after)
+    }
+
+    fn update_product(
+        &mut self,
+        id: u64,
+        product: NewProduct,
+        operator: &str,
+    ) -> Option<ProductItem> {
+        let before = self.get_product(id);
+        let row = self.product_mut(id)?;
+        row.product = NewProduct {
+            id: Some(id),
+            img_url: row.product.img_url.clone(),
+            ..product
+        };
+        row.modifier = operator.to_owned();
+        row.updated_at = Utc::now();
+        let after = self.get_product(id)?;
+        self.sync_default_sku(before.as_ref(), &after);
+        self.push_audit_log(NewAuditLog::new(
+            operator,
+            AuditAction::Update,
+            EntityType::Product,
+            id,
+            before.as_ref().and_then(snapshot),
+            snapshot(&after),
+        ));
+        Some(after)
+    }
+
+    // keep the default sku in step with the product, and record a price
+    // history row for it whenever the prices are set
+    fn sync_default_sku(&mut self, before: Option<&ProductItem>, after: &ProductItem) {
+        let price = ProductPrice {
+            sell_price: after.sell_price,
+            import_price: after.import_price,
+        };
+        let default = self.default_sku(after.id).map(|s| s.id);
+        let sku_id = match default {
+            Some(sku_id) => {
+                let sku = self.skus.iter_mut().find(|s| s.id == sku_id).unwrap();
+                sku.spec = after.spec.clone();
+                sku.kind = after.kind;
+                sku.sell_price = price.sell_price;
+                sku.import_price = price.import_price;
+                sku_id
+            }
+            None => {
+                let sku_id = self.next_id("product_sku");
+                self.skus.push(Sku {
+                    id: sku_id,
+                    product_id: after.id,
+                    spec: after.spec.clone(),
+                    kind: after.kind,
+                    barcode: String::new(),
+                    sell_price: price.sell_price,
+                    import_price: price.import_price,
+                    sequence: 1,
+                    status: SkuStatus::OnSale,
+                });
+                sku_id
+            }
+        };
+        if let Some(b) = before {
+            if b.sell_price == after.sell_price && b.import_price == after.import_price {
+                return;
+            }
+        }
+        self.create_price_history(after.id, sku_id, &price);
+    }
+
+    // mirror the default sku back onto the product
+    fn sync_product_from_sku(&mut self, product_id: u64, operator: &str) {
+        let sku = match self.default_sku(product_id) {
+            Some(s) => s.clone(),
+            None => return,
+        };
+        if let Some(row) = self.product_mut(product_id) {
+            row.product.spec = sku.spec;
+            row.product.kind = sku.kind;
+            row.product.sell_price = sku.sell_price;
+            row.product.import_price = sku.import_price;
+            row.modifier = operator.to_owned();
+            row.updated_at = Utc::now();
+        }
+    }
+
+    fn create_price_history(&mut self, product_id: u64, sku_id: u64, price: &ProductPrice) {
+        let id = self.next_id("price_history");
+        self.prices.push(PriceRow {
+            id,
+            product_id,
+            sku_id,
+            record: PriceRecord {
+                sell_price: price.sell_price,
+                import_price: price.import_price,
+                price_time: now(),
+            },
+        });
+    }
+
+    // skus which are not deleted, the default one first
+    fn live_skus(&self, product_id: u64) -> Vec<&Sku> {
+        let mut skus: Vec<&Sku> = self
+            .skus
+            .iter()
+            .filter(|s| s.product_id == product_id && s.status != SkuStatus::Deleted)
+            .collect();
+        skus.sort_by_key(|s| (s.sequence, s.id));
+        skus
+    }
+
+    // the first sku on sale, or else the first live one
+    fn default_sku(&self, product_id: u64) -> Option<&Sku> {
+        self.live_skus(product_id)
+            .into_iter()
+            .min_by_key(|s| s.status != SkuStatus::OnSale)
+    }
+
+    fn get_sku(&self, product_id: u64, id: u64) -> Option<Sku> {
+        self.live_skus(product_id)
+            .into_iter()
+            .find(|s| s.id == id)
+            .cloned()
+    }
+
+    fn product_images(&self, product_id: u64) -> Vec<ProductImage> {
+        let mut images: Vec<ProductImage> = self
+            .images
+            .iter()
+            .filter(|i| i.valid && i.image.product_id == product_id)
+            .map(|i| i.image.clone())
+            .collect();
+        images.sort_by_key(|i| (i.sequence, i.id));
+        images
+    }
+
+    // use the first image as `product.img_url`, or clear it when there is none
+    fn sync_product_cover(&mut self, product_id: u64) {
+        let url = self
+            .product_images(product_id)
+            .first()
+            .map(|i| i.url.clone())
+            .unwrap_or_default();
+        if let Some(row) = self.product_mut(product_id) {
+            row.product.img_url = url;
+        }
+    }
+
+    // the list of a slot which is live right now
+    fn live_hot_list(&self, slot: &str) -> Option<u64> {
+        let now = now();
+        self.hot_lists
+            .iter()
+            .filter(|l| l.valid && l.list.slot == slot && l.list.start_at <= now)
+            .filter(|l| l.list.end_at.map_or(true, |end| end > now))
+            .max_by_key(|l| (l.list.start_at, l.list.id))
+            .map(|l| l.list.id)
+    }
+
+    fn push_hot_list(&mut self, list: &NewHotList, product_ids: &[u64]) -> u64 {
+        let id = self.next_id("hot_list");
+        self.hot_lists.push(HotListRow {
+            list: HotList {
+                id,
+                slot: list.slot.clone(),
+                start_at: list.start_at.unwrap_or_else(now),
+                end_at: list.end_at,
+            },
+            valid: true,
+        });
+        for (i, product_id) in product_ids.iter().enumerate() {
+            let row_id = self.next_id("hot_product");
+            self.hot_products.push(HotProductRow {
+                id: row_id,
+                list_id: id,
+                product_id: *product_id,
+                sequence: i as i32 + 1,
+            });
+        }
+        id
+    }
+
+    fn hot_list_product_ids(&self, list_id: u64) -> Vec<u64> {
+        let mut rows: Vec<&HotProductRow> = self
+            .hot_products
+            .iter()
+            .filter(|h| h.list_id == list_id)
+            .collect();
+        rows.sort_by_key(|h| (h.sequence, h.id));
+        rows.into_iter().map(|h| h.product_id).collect()
+    }
+
+    // products of the live list of a slot, in ranked order
+    fn live_hot_products(&self, slot: &str) -> Vec<&ProductRow> {
+        let list_id = match self.live_hot_list(slot) {
+            Some(id) => id,
+            None => return vec![],
+        };
+        self.hot_list_product_ids(list_id)
+            .into_iter()
+            .filter_map(|id| self.product(id))
+            .collect()
+    }
+
+    fn hot_list_item(&self, id: u64) -> Option<HotListItem> {
+        let row = self.hot_lists.iter().find(|l| l.valid && l.list.id == id)?;
+        Some(HotListItem {
+            list: row.list.clone(),
+            product_ids: self.hot_list_product_ids(id),
+        })
+    }
+
+    fn category(&self, id: u64) -> Option<&Category> {
+        self.categories
+            .iter()
+            .find(|c| c.valid && c.category.id == id)
+            .map(|c| &c.category)
+    }
+
+    // on shelf products linked to the category at `path` or its descendants
+    fn category_products(&self, path: &str) -> Vec<&ProductRow> {
+        let ids: BTreeSet<u64> = self
+            .product_categories
+            .iter()
+            .filter(|(_, category_id)| {
+                self.category(*category_id)
+                    .map_or(false, |c| c.path.starts_with(path))
+            })
+            .map(|(product_id, _)| *product_id)
+            .collect();
+        ids.into_iter()
+            .filter_map(|id| self.product(id))
+            .filter(|p| p.product.status == ProductStatus::OnShelf)
+            .collect()
+    }
+
+    fn user(&self, id: u64) -> Option<&UserRow> {
+        self.users
+            .iter()
+            .find(|u| u.user.id == id && u.user.status != UserStatus::Deleted)
+    }
+
+    fn user_mut(&mut self, id: u64) -> Option<&mut UserRow> {
+        self.users
+            .iter_mut()
+            .find(|u| u.user.id == id && u.user.status != UserStatus::Deleted)
+    }
+
+    fn push_audit_log(&mut self, log: NewAuditLog) -> u64 {
+        let id = self.next_id("audit_log");
+        self.audit_logs.push(AuditLog {
+            id,
+            operator: log.operator,
+            action: log.action.as_str().to_owned(),
+            entity_type: log.entity_type.as_str().to_owned(),
+            entity_id: log.entity_id,
+            before_data: log.before_data,
+            after_data: log.after_data,
+            diff: log.diff,
+            created_at: Utc::now(),
+        });
+        id
+    }
+
+    fn matching_audit_logs(&self, q: &AuditQuery) -> Vec<&AuditLog> {
+        self.audit_logs
+            .iter()
+            .rev()
+            .filter(|l| q.entity_type.as_ref().map_or(true, |t| l.entity_type == *t))
+            .filter(|l| q.entity_id.map_or(true, |id| l.entity_id == id))
+            .filter(|l| q.operator.as_ref().map_or(true, |o| l.operator == *o))
+            .collect()
+    }
+}
+
+#[async_trait]
+impl BrandRepo for MemoryRepo {
+    async fn create_brand(&self, brand: Brand, operator: &str) -> Result<u64> {
+        let mut d = self.data();
+        let id = d.next_id("brand");
+        d.brands.push(BrandRow {
+            brand: Brand {
+                id,
+                name: brand.name,
+                en_name: String::new(),
+                logo_url: String::new(),
+                country: String::new(),
+                description: String::new(),
+                website: String::new(),
+                sequence: brand.sequence,
+                is_hot: false,
+            },
+            valid: true,
+            modifier: operator.to_owned(),
+            updated_at: Utc::now(),
+            deleted: None,
+        });
+        Ok(id)
+    }
+
+    async fn create_brands(&self, brands: &[NewBrand], operator: &str) -> Result<Vec<Brand>> {
+        let mut d = self.data();
+        d.check_brand_names(brands)?;
+        let mut sequence = d
+            .brands
+            .iter()
+            .filter(|b| b.valid)
+            .map(|b| b.brand.sequence)
+            .max()
+            .unwrap_or(0);
+        let mut created = vec![];
+        for b in brands.iter() {
+            sequence += 1;
+            let brand = Brand {
+                id: d.next_id("brand"),
+                name: b.name.clone(),
+                en_name: String::new(),
+                logo_url: String::new(),
+                country: String::new(),
+                description: String::new(),
+                website: String::new(),
+                sequence,
+                is_hot: false,
+            };
+            d.brands.push(BrandRow {
+                brand: brand.clone(),
+                valid: true,
+                modifier: operator.to_owned(),
+                updated_at: Utc::now(),
+                deleted: None,
+            });
+            d.push_audit_log(NewAuditLog::new(
+                operator,
+                AuditAction::Create,
+                EntityType::Brand,
+                brand.id,
+                None,
+                snapshot(&brand),
+            ));
+            created.push(brand);
+        }
+        Ok(created)
+    }
+
+    async fn get_brands(&self, paging: &Paging, cursor: Option<Cursor>) -> Result<Vec<Brand>> {
+        let d = self.data();
+        let after = |b: &Brand| match cursor {
+            Some(c) => (b.sequence as i64, b.id) > (c.sequence, c.id),
+            None => true,
+        };
+        Ok(d.valid_brands()
+            .into_iter()
+            .filter(|row| after(&row.brand))
+            .skip(paging.offset() as usize)
+            .take(paging.limit_or(MAX_ROWS) as usize)
+            .map(|row| d.to_brand(row))
+            .collect())
+    }
+
+    async fn count_brands(&self) -> Result<i64> {
+        Ok(self.data().valid_brands().len() as i64)
+    }
+
+    async fn get_all_brands(&self) -> Result<Vec<Brand>> {
+        let d = self.data();
+        Ok(d.valid_brands()
+            .into_iter()
+            .map(|row| d.to_brand(row))
+            .collect())
+    }
+
+    async fn get_brand(&self, id: u64) -> Result<Option<Brand>> {
+        let d = self.data();
+        Ok(d.brand(id).map(|row| d.to_brand(row)))
+    }
+
+    async fn get_brand_id(&self, name: &str) -> Result<u64> {
+        self.data()
+            .brands
+            .iter()
+            .find(|b| b.valid && b.brand.name == name)
+            .map(|b| b.brand.id)
+            .ok_or_else(|| anyhow!("品牌名'{}'不存在", name))
+    }
+
+    async fn is_brand_names_valid(&self, brands: &[NewBrand]) -> Result<bool> {
+        self.data().check_brand_names(brands)?;
+        Ok(true)
+    }
+
+    async fn update_brand(&self, id: u64, brand: &UpdateBrand, operator: &str) -> Result<bool> {
+        let mut d = self.data();
+        let before = d.brand(id).map(|row| d.to_brand(row));
+        let row = match d.brand_mut(id).filter(|b| b.valid) {
+            Some(row) => row,
+            None => return Ok(false),
+        };
+        row.brand.name = brand.name.clone();
+        row.brand.en_name = brand.en_name.clone();
+        row.brand.logo_url = brand.logo_url.clone();
+        row.brand.country = brand.country.clone();
+        row.brand.description = brand.description.clone();
+        row.brand.website = brand.website.clone();
+        row.modifier = operator.to_owned();
+        row.updated_at = Utc::now();
+        let after = d.brand(id).map(|row| d.to_brand(row));
+        d.push_audit_log(NewAuditLog::new(
+            operator,
+            AuditAction::Update,
+            EntityType::Brand,
+            id,
+            before.as_ref().and_then(snapshot),
+            after.as_ref().and_then(snapshot),
+        ));
+        Ok(true)
+    }
+
+    async fn update_brands_sequence(
+        &self,
+        bss: &[BrandSequence],
+        operator: &str,
+    ) -> Result<Vec<(u64, Change<Option<Brand>>)>> {
+        let mut d = self.data();
+        let ids: Vec<u64> = bss.iter().map(|bs| bs.id).collect();
+        d.check_brand_ids(&ids)?;
+        let mut changes = vec![];
+        for bs in bss.iter() {
+            let before = d.brand(bs.id).map(|row| d.to_brand(row));
+            if let Some(row) = d.brand_mut(bs.id) {
+                row.brand.sequence = bs.sequence;
+                row.modifier = operator.to_owned();
+                row.updated_at = Utc::now();
+            }
+            let after = d.brand(bs.id).map(|row| d.to_brand(row));
+            d.push_audit_log(NewAuditLog::new(
+                operator,
+                AuditAction::UpdateSequence,
+                EntityType::Brand,
+                bs.id,
+                before.as_ref().and_then(snapshot),
+                after.as_ref().and_then(snapshot),
+            ));
+            changes.push((bs.id, (before, after)));
+        }
+        Ok(changes)
+    }
+
+    async fn delete_brand(&self, id: u32, operator: &str) -> Result<bool> {
+        let mut d = self.data();
+        let id = id as u64;
+        let before = d.brand(id).map(|row| d.to_brand(row));
+        let row = match d.brand_mut(id) {
+            Some(row) => row,
+            None => return Ok(false),
+        };
+        row.valid = false;
+        row.modifier = operator.to_owned();
+        row.updated_at = Utc::now();
+        row.deleted = Deleted::now(operator);
+        d.push_audit_log(NewAuditLog::new(
+            operator,
+            AuditAction::Delete,
+            EntityType::Brand,
+            id,
+            before.as_ref().and_then(snapshot),
+            None,
+        ));
+        Ok(true)
+    }
+
+    async fn restore_brand(&self, id: u64, operator: &str) -> Result<bool> {
+        let mut d = self.data();
+        let row = match d.brand_mut(id).filter(|b| !b.valid) {
+            Some(row) => row,
+            None => return Ok(false),
+        };
+        row.valid = true;
+        row.modifier = operator.to_owned();
+        row.updated_at = Utc::now();
+        row.deleted = None;
+        let after = d.brand(id).map(|row| d.to_brand(row));
+        d.push_audit_log(NewAuditLog::new(
+            operator,
+            AuditAction::Restore,
+            EntityType::Brand,
+            id,
+            None,
+            after.as_ref().and_then(snapshot),
+        ));
+        Ok(true)
+    }
+
+    async fn purge_brand(&self, id: u64, operator: &str) -> Result<bool> {
+        let mut d = self.data();
+        let before = match d.brand(id).filter(|b| !b.valid) {
+            Some(row) => brand_trash_item(row),
+            None => return Ok(false),
+        };
+        if d.products.iter().any(|p| p.product.brand_id == id) {
+            return Ok(false);
+        }
+        d.brands.retain(|b| b.brand.id != id);
+        d.hot_brands.retain(|h| h.brand_id != id);
+        d.push_audit_log(NewAuditLog::new(
+            operator,
+            AuditAction::Purge,
+            EntityType::Brand,
+            id,
+            snapshot(&before),
+            None,
+        ));
+        Ok(true)
+    }
+
+    async fn get_deleted_brands(&self, paging: &Paging) -> Result<Vec<TrashItem>> {
+        let d = self.data();
+        let mut rows: Vec<&BrandRow> = d.brands.iter().filter(|b| !b.valid).collect();
+        let mut items: Vec<TrashItem> = rows.into_iter().map(brand_trash_item).collect();
+        items.sort_by_key(|t| Reverse((t.deleted_at, t.id)));
+        Ok(items
+            .into_iter()
+            .skip(paging.offset.unwrap_or(0) as usize)
+            .take(paging.limit_or(MIN_ROWS) as usize)
+            .collect())
+    }
+
+    async fn count_deleted_brands(&self) -> Result<i64> {
+        Ok(self.data().brands.iter().filter(|b| !b.valid).count() as i64)
+    }
+
+    async fn get_deleted_brand(&self, id: u64) -> Result<Option<TrashItem>> {
+        Ok(self
+            .data()
+            .brand(id)
+            .filter(|b| !b.valid)
+            .map(brand_trash_item))
+    }
+
+    async fn get_hot_brands(&self) -> Result<Vec<Brand>> {
+        let d = self.data();
+        let mut rows: Vec<&HotBrandRow> = d
+            .hot_brands
+            .iter()
+            .filter(|h| h.valid && d.is_valid_brand(h.brand_id))
+            .collect();
+        rows.sort_by_key(|h| (h.sequence, h.id));
+        Ok(rows
+            .into_iter()
+            .filter_map(|h| d.brand(h.brand_id))
+            .map(|row| d.to_brand(row))
+            .collect())
+    }
+
+    async fn get_hot_brand_ids(&self) -> Result<Vec<u64>> {
+        let d = self.data();
+        let mut rows: Vec<&HotBrandRow> = d.hot_brands.iter().filter(|h| h.valid).collect();
+        rows.sort_by_key(|h| (h.sequence, h.id));
+        Ok(rows.into_iter().map(|h| h.brand_id).collect())
+    }
+
+    async fn set_hot_brands(&self, brand_ids: &[u64], operator: &str) -> Result<Vec<u64>> {
+        let mut d = self.data();
+        d.check_brand_ids(brand_ids)?;
+        let mut rows: Vec<&HotBrandRow> = d.hot_brands.iter().filter(|h| h.valid).collect();
+        rows.sort_by_key(|h| (h.sequence, h.id));
+        let before: Vec<u64> = rows.into_iter().map(|h| h.brand_id).collect();
+        for h in d.hot_brands.iter_mut() {
+            h.valid = false;
+        }
+        for (i, brand_id) in brand_ids.iter().enumerate() {
+            let id = d.next_id("hot_brand");
+            d.hot_brands.push(HotBrandRow {
+                id,
+                brand_id: *brand_id,
+                sequence: i as i32 + 1,
+                valid: true,
+            });
+        }
+        d.push_audit_log(NewAuditLog::new(
+            operator,
+            AuditAction::Replace,
+            EntityType::HotBrand,
+            0,
+            snapshot(&before),
+            snapshot(&brand_ids),
+        ));
+        Ok(before)
+    }
+}
+
+#[async_trait]
+impl ProductRepo for MemoryRepo {
+    async fn create_product(
+        &self,
+        product: &NewProduct,
+        brand_id: u64,
+        operator: &str,
+    ) -> Result<ProductItem> {
+        self.data().create_product(product, brand_id, operator)
+    }
+
+    async fn get_product(&self, id: u64) -> Result<Option<ProductItem>> {
+        Ok(self.data().get_product(id))
+    }
+
+    async fn get_valid_product(&self, id: u64) -> Result<Option<ProductItem>> {
+        let d = self.data();
+        Ok(d.get_product(id)
+            .filter(|p| p.status == ProductStatus::OnShelf)
+            .filter(|p| d.is_valid_brand(p.brand_id as u64)))
+    }
+
+    async fn get_products(
+        &self,
+        filter: &ProductFilter,
+        offset: u32,
+        limit: u32,
+    ) -> Result<Vec<ProductItem>> {
+        let d = self.data();
+        let mut rows: Vec<&ProductRow> = d
+            .products
+            .iter()
+            .filter(|p| matches_filter(p, filter))
+            .collect();
+        sort_products(&mut rows, filter.sort);
+        Ok(rows
+            .into_iter()
+            .filter_map(|p| d.to_item(p))
+            .skip(offset as usize)
+            .take(limit as usize)
+            .collect())
+    }
+
+    async fn count_products(&self, filter: &ProductFilter) -> Result<i64> {
+        let d = self.data();
+        Ok(d.products
+            .iter()
+            .filter(|p| matches_filter(p, filter))
+            .filter_map(|p| d.to_item(p))
+            .count() as i64)
+    }
+
+    async fn search_products(&self, q: &str, paging: &Paging) -> Result<Vec<SearchItem>> {
+        let d = self.data();
+        let mut res: Vec<(SearchItem, i32)> = d
+            .products
+            .iter()
+            .filter_map(|p| search_item(&d, p, q).map(|item| (item, p.product.sequence)))
+            .collect();
+        res.sort_by(|(a, a_seq), (b, b_seq)| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap()
+                .then(a_seq.cmp(b_seq))
+                .then(a.id.cmp(&b.id))
+        });
+        Ok(res
+            .into_iter()
+            .map(|(item, _)| item)
+            .skip(paging.offset.unwrap_or(0) as usize)
+            .take(paging.limit_or(MIN_ROWS) as usize)
+            .collect())
+    }
+
+    async fn count_search_products(&self, q: &str) -> Result<i64> {
+        let d = self.data();
+        Ok(d.products
+            .iter()
+            .filter_map(|p| search_item(&d, p, q))
+            .count() as i64)
+    }
+
+    async fn get_brand_products(
+        &self,
+        brand_id: u32,
+        paging: &Paging,
+        cursor: Option<Cursor>,
+    ) -> Result<Vec<BrandItem>> {
+        let d = self.data();
+        let after_id = cursor.map_or(0, |c| c.id);
+        let mut rows: Vec<&ProductRow> = d
+            .products
+            .iter()
+            .filter(|p| p.product.brand_id == brand_id as u64 && p.id > after_id)
+            .filter(|p| p.product.status == ProductStatus::OnShelf)
+            .collect();
+        rows.sort_by_key(|p| p.id);
+        Ok(rows
+            .into_iter()
+            .skip(paging.offset() as usize)
+            .take(paging.limit_or(MIN_ROWS) as usize)
+            .map(brand_item)
+            .collect())
+    }
+
+    async fn count_brand_products(&self, brand_id: u32) -> Result<i64> {
+        Ok(self
+            .data()
+            .products
+            .iter()
+            .filter(|p| p.product.brand_id == brand_id as u64)
+            .filter(|p| p.product.status == ProductStatus::OnShelf)
+            .count() as i64)
+    }
+
+    async fn count_all_brand_products(&self, brand_id: u64) -> Result<i64> {
+        Ok(self
+            .data()
+            .products
+            .iter()
+            .filter(|p| p.product.brand_id == brand_id)
+            .count() as i64)
+    }
+
+    async fn update_product(
+        &self,
+        id: u64,
+        product: NewProduct,
+        operator: &str,
+    ) -> Result<Option<ProductItem>> {
+        Ok(self.data().update_product(id, product, operator))
+    }
+
+    async fn update_product_price(
+        &self,
+        id: u64,
+        price: &ProductPrice,
+        operator: &str,
+    ) -> Result<Option<ProductItem>> {
+        let mut d = self.data();
+        let before = d.get_product(id);
+        let row = match d
+            .product_mut(id)
+            .filter(|p| p.product.status != ProductStatus::Deleted)
+        {
+            Some(row) => row,
+            None => return Ok(None),
+        };
+        row.product.sell_price = price.sell_price;
+        row.product.import_price = price.import_price;
+        row.modifier = operator.to_owned();
+        row.updated_at = Utc::now();
+        let after = d.get_product(id);
+        if let Some(after) = &after {
+            d.sync_default_sku(before.as_ref(), after);
+        }
+        d.push_audit_log(NewAuditLog::new(
+            operator,
+            AuditAction::UpdatePrice,
+            EntityType::Product,
+            id,
+            before.as_ref().and_then(snapshot),
+            after.as_ref().and_then(snapshot),
+        ));
+        Ok(after)
+    }
+
+    async fn update_product_status(
+        &self,
+        id: u64,
+        from: ProductStatus,
+        to: ProductStatus,
+        action: AuditAction,
+        operator: &str,
+    ) -> Result<bool> {
+        let mut d = self.data();
+        let before = d.get_product(id);
+        let row = match d.product_mut(id).filter(|p| p.product.status == from) {
+            Some(row) => row,
+            None => return Ok(false),
+        };
+        row.product.status = to;
+        row.modifier = operator.to_owned();
+        row.updated_at = Utc::now();
+        row.deleted = match to {
+            ProductStatus::Deleted => Deleted::now(operator),
+            _ => None,
+        };
+        let after = d.get_product(id);
+        d.push_audit_log(NewAuditLog::new(
+            operator,
+            action,
+            EntityType::Product,
+            id,
+            before.as_ref().and_then(snapshot),
+            after.as_ref().and_then(snapshot),
+        ));
+        Ok(true)
+    }
+
+    async fn import_products(&self, products: Vec<NewProduct>, operator: &str) -> Result<Vec<u64>> {
+        let mut d = self.data();
+        // all or nothing, check the updated products before writing any row
+        for id in products.iter().filter_map(|p| p.id) {
+            if d.product(id).is_none() {
+                return Err(anyhow!("Product not exist, id: {}.", id));
+            }
+        }
+        let mut ids = vec![];
+        for product in products.into_iter() {
+            let id = match product.id {
+                Some(id) => {
+                    d.update_product(id, product, operator);
+                    id
+                }
+                None => d.create_product(&product, product.brand_id, operator)?.id,
+            };
+            ids.push(id);
+        }
+        Ok(ids)
+    }
+
+    async fn purge_product(&self, id: u64, operator: &str) -> Result<bool> {
+        let mut d = self.data();
+        let before = match d.get_product(id) {
+            Some(p) if p.status == ProductStatus::Deleted => p,
+            _ => return Ok(false),
+        };
+        d.products.retain(|p| p.id != id);
+        d.skus.retain(|s| s.product_id != id);
+        d.images.retain(|i| i.image.product_id != id);
+        d.product_categories
+            .retain(|(product_id, _)| *product_id != id);
+        d.hot_products.retain(|h| h.product_id != id);
+        d.push_audit_log(NewAuditLog::new(
+            operator,
+            AuditAction::Purge,
+            EntityType::Product,
+            id,
+            snapshot(&before),
+            None,
+        ));
+        Ok(true)
+    }
+
+    async fn is_product_exist(&self, id: u64) -> Result<bool> {
+        Ok(self
+            .data()
+            .status_of(id)
+            .map_or(false, |s| s != ProductStatus::Deleted))
+    }
+
+    async fn is_product_valid(&self, id: u64) -> Result<bool> {
+        let d = self.data();
+        Ok(d.product(id).map_or(false, |p| {
+            p.product.status == ProductStatus::OnShelf && d.is_valid_brand(p.product.brand_id)
+        }))
+    }
+
+    async fn get_deleted_products(&self, paging: &Paging) -> Result<Vec<TrashItem>> {
+        let d = self.data();
+        let mut rows: Vec<&ProductRow> = d
+            .products
+            .iter()
+            .filter(|p| p.product.status == ProductStatus::Deleted)
+            .collect();
+        let mut items: Vec<TrashItem> = rows
+            .into_iter()
+            .map(|p| trash_item(p.id, &p.product.name, &p.modifier, p.updated_at, &p.deleted))
+            .collect();
+        items.sort_by_key(|t| Reverse((t.deleted_at, t.id)));
+        Ok(items
+            .into_iter()
+            .skip(paging.offset.unwrap_or(0) as usize)
+            .take(paging.limit_or(MIN_ROWS) as usize)
+            .collect())
+    }
+
+    async fn count_deleted_products(&self) -> Result<i64> {
+        Ok(self
+            .data()
+            .products
+            .iter()
+            .filter(|p| p.product.status == ProductStatus::Deleted)
+            .count() as i64)
+    }
+
+    async fn get_product_skus(&self, product_id: u64) -> Result<Vec<Sku>> {
+        Ok(self
+            .data()
+            .live_skus(product_id)
+            .into_iter()
+            .cloned()
+            .collect())
+    }
+
+    async fn get_valid_skus(&self, product_id: u64) -> Result<Vec<Sku>> {
+        Ok(self
+            .data()
+            .live_skus(product_id)
+            .into_iter()
+            .filter(|s| s.status == SkuStatus::OnSale)
+            .cloned()
+            .collect())
+    }
+
+    async fn get_sku(&self, product_id: u64, id: u64) -> Result<Option<Sku>> {
+        Ok(self.data().get_sku(product_id, id))
+    }
+
+    async fn is_barcode_taken(&self, barcode: &str, exclude_id: u64) -> Result<bool> {
+        Ok(self
+            .data()
+            .skus
+            .iter()
+            .filter(|s| s.status != SkuStatus::Deleted && s.id != exclude_id)
+            .any(|s| s.barcode == barcode))
+    }
+
+    async fn create_sku(&self, product_id: u64, mut sku: NewSku, operator: &str) -> Result<Sku> {
+        let mut d = self.data();
+        if sku.sequence == 0 {
+            sku.sequence = d
+                .live_skus(product_id)
+                .iter()
+                .map(|s| s.sequence)
+                .max()
+                .unwrap_or(0)
+                + 1;
+        }
+        let id = d.next_id("product_sku");
+        d.skus.push(Sku {
+            id,
+            product_id,
+            spec: sku.spec,
+            kind: sku.kind,
+            barcode: sku.barcode,
+            sell_price: sku.sell_price,
+            import_price: sku.import_price,
+            sequence: sku.sequence,
+            status: sku.status,
+        });
+        let price = ProductPrice {
+            sell_price: sku.sell_price,
+            import_price: sku.import_price,
+        };
+        d.create_price_history(product_id, id, &price);
+        d.sync_product_from_sku(product_id, operator);
+        let after = d
+            .get_sku(product_id, id)
+            .ok_or_else(|| anyhow!("规格创建失败"))?;
+        d.push_audit_log(NewAuditLog::new(
+            operator,
+            AuditAction::Create,
+            EntityType::Sku,
+            id,
+            None,
+            snapshot(&after),
+        ));
+        Ok(after)
+    }
+
+    async fn update_sku(
+        &self,
+        product_id: u64,
+        id: u64,
+        sku: &NewSku,
+        operator: &str,
+    ) -> Result<Option<Sku>> {
+        let mut d = self.data();
+        let before = match d.get_sku(product_id, id) {
+            Some(s) => s,
+            None => return Ok(None),
+        };
+        let row = d.skus.iter_mut().find(|s| s.id == id).unwrap();
+        row.spec = sku.spec.clone();
+        row.kind = sku.kind;
+        row.barcode = sku.barcode.clone();
+        row.sell_price = sku.sell_price;
+        row.import_price = sku.import_price;
+        row.sequence = sku.sequence;
+        row.status = sku.status;
+        if before.sell_price != sku.sell_price || before.import_price != sku.import_price {
+            let price = ProductPrice {
+                sell_price: sku.sell_price,
+                import_price: sku.import_price,
+            };
+            d.create_price_history(product_id, id, &price);
+        }
+        d.sync_product_from_sku(product_id, operator);
+        let after = d.get_sku(product_id, id);
+        d.push_audit_log(NewAuditLog::new(
+            operator,
+            AuditAction::Update,
+            EntityType::Sku,
+            id,
+            snapshot(&before),
+            after.as_ref().and_then(snapshot),
+        ));
+        Ok(after)
+    }
+
+    async fn delete_sku(&self, product_id: u64, id: u64, operator: &str) -> Result<Sku> {
+        let mut d = self.data();
+        let skus: Vec<Sku> = d.live_skus(product_id).into_iter().cloned().collect();
+        let before = removable_sku(&skus, id)?;
+        if let Some(row) = d.skus.iter_mut().find(|s| s.id == id) {
+            row.status = SkuStatus::Deleted;
+        }
+        d.sync_product_from_sku(product_id, operator);
+        d.push_audit_log(NewAuditLog::new(
+            operator,
+            AuditAction::Delete,
+            EntityType::Sku,
+            id,
+            snapshot(&before),
+            None,
+        ));
+        Ok(before)
+    }
+
+    async fn get_price_history(
+        &self,
+        product_id: u64,
+        q: &PriceHistoryQuery,
+    ) -> Result<Vec<PriceRecord>> {
+        let d = self.data();
+        let mut rows: Vec<&PriceRow> = d
+            .prices
+            .iter()
+            .filter(|p| p.product_id == product_id)
+            .filter(|p| q.sku_id.map_or(true, |id| p.sku_id == id))
+            .filter(|p| in_dates(p.record.price_time.date(), q.start, q.end))
+            .collect();
+        rows.sort_by_key(|p| (p.record.price_time, p.id));
+        Ok(rows.into_iter().map(|p| p.record.clone()).collect())
+    }
+
+    async fn get_product_images(&self, product_id: u64) -> Result<Vec<ProductImage>> {
+        Ok(self.data().product_images(product_id))
+    }
+
+    async fn create_product_images(
+        &self,
+        product_id: u64,
+        images: &[(String, String)],
+        operator: &str,
+    ) -> Result<Change<Vec<ProductImage>>> {
+        let mut d = self.data();
+        let before = d.product_images(product_id);
+        let mut sequence = before.iter().map(|i| i.sequence).max().unwrap_or(0);
+        for (url, thumb_url) in images.iter() {
+            sequence += 1;
+            let id = d.next_id("product_image");
+            d.images.push(ImageRow {
+                image: ProductImage {
+                    id,
+                    product_id,
+                    url: url.clone(),
+                    thumb_url: thumb_url.clone(),
+                    sequence,
+                },
+                valid: true,
+            });
+        }
+        d.sync_product_cover(product_id);
+        let after = d.product_images(product_id);
+        d.push_audit_log(NewAuditLog::new(
+            operator,
+            AuditAction::UpdateImages,
+            EntityType::Product,
+            product_id,
+            snapshot(&before),
+            snapshot(&after),
+        ));
+        Ok((before, after))
+    }
+
+    async fn update_images_sequence(
+        &self,
+        product_id: u64,
+        iss: &[ImageSequence],
+        operator: &str,
+    ) -> Result<Change<Vec<ProductImage>>> {
+        let mut d = self.data();
+        let before = d.product_images(product_id);
+        // all or nothing, like the transaction of the MySQL repo
+        for is in iss.iter() {
+            if !before.iter().any(|i| i.id == is.id) {
+                return Err(image_not_found(is.id));
+            }
+        }
+        for is in iss.iter() {
+            if let Some(row) = d.images.iter_mut().find(|i| i.image.id == is.id) {
+                row.image.sequence = is.sequence;
+            }
+        }
+        d.sync_product_cover(product_id);
+        let after = d.product_images(product_id);
+        d.push_audit_log(NewAuditLog::new(
+            operator,
+            AuditAction::UpdateImages,
+            EntityType::Product,
+            product_id,
+            snapshot(&before),
+            snapshot(&after),
+        ));
+        Ok((before, after))
+    }
+
+    async fn delete_product_image(
+        &self,
+        product_id: u64,
+        id: u64,
+        operator: &str,
+    ) -> Result<Change<Vec<ProductImage>>> {
+        let mut d = self.data();
+        let before = d.product_images(product_id);
+        let row = d
+            .images
+            .iter_mut()
+            .find(|i| i.valid && i.image.id == id && i.image.product_id == product_id)
+            .ok_or_else(|| image_not_found(id))?;
+        row.valid = false;
+        d.sync_product_cover(product_id);
+        let after = d.product_images(product_id);
+        d.push_audit_log(NewAuditLog::new(
+            operator,
+            AuditAction::UpdateImages,
+            EntityType::Product,
+            product_id,
+            snapshot(&before),
+            snapshot(&after),
+        ));
+        Ok((before, after))
+    }
+}
+
+#[async_trait]
+impl HotProductRepo for MemoryRepo {
+    async fn create_hot_list(
+        &self,
+        list: &NewHotList,
+        product_ids: &[u64],
+        operator: &str,
+    ) -> Result<u64> {
+        let mut d = self.data();
+        let id = d.push_hot_list(list, product_ids);
+        let after = d.hot_list_item(id);
+        d.push_audit_log(NewAuditLog::new(
+            operator,
+            AuditAction::Create,
+            EntityType::HotProduct,
+            id,
+            None,
+            after.as_ref().and_then(snapshot),
+        ));
+        Ok(id)
+    }
+
+    async fn replace_hot_list(
+        &self,
+        list: &NewHotList,
+        product_ids: &[u64],
+        operator: &str,
+    ) -> Result<u64> {
+        let mut d = self.data();
+        let now = now();
+        let ended: Vec<u64> = d
+            .hot_lists
+            .iter()
+            .filter(|l| l.valid && l.list.slot == list.slot && l.list.start_at <= now)
+            .filter(|l| l.list.end_at.map_or(true, |end| end > now))
+            .map(|l| l.list.id)
+            .collect();
+        for ended_id in ended.iter() {
+            let before = d.hot_list_item(*ended_id);
+            if let Some(l) = d.hot_lists.iter_mut().find(|l| l.list.id == *ended_id) {
+                l.list.end_at = Some(now);
+            }
+            let after = d.hot_list_item(*ended_id);
+            d.push_audit_log(NewAuditLog::new(
+                operator,
+                AuditAction::Update,
+                EntityType::HotProduct,
+                *ended_id,
+                before.as_ref().and_then(snapshot),
+                after.as_ref().and_then(snapshot),
+            ));
+        }
+        let id = d.push_hot_list(list, product_ids);
+        let after = d.hot_list_item(id);
+        d.push_audit_log(NewAuditLog::new(
+            operator,
+            AuditAction::Create,
+            EntityType::HotProduct,
+            id,
+            None,
+            after.as_ref().and_then(snapshot),
+        ));
+        Ok(id)
+    }
+
+    async fn get_hot_lists(&self, slot: &str) -> Result<Vec<HotListItem>> {
+        let d = self.data();
+        let now = now();
+        let mut lists: Vec<&HotListRow> = d
+            .hot_lists
+            .iter()
+            .filter(|l| l.valid && l.list.slot == slot)
+            .filter(|l| l.list.end_at.map_or(true, |end| end > now))
+            .collect();
+        lists.sort_by_key(|l| Reverse((l.list.start_at, l.list.id)));
+        Ok(lists
+            .into_iter()
+            .map(|l| HotListItem {
+                list: l.list.clone(),
+                product_ids: d.hot_list_product_ids(l.list.id),
+            })
+            .collect())
+    }
+
+    async fn get_hot_list(&self, id: u64) -> Result<Option<HotListItem>> {
+        Ok(self.data().hot_list_item(id))
+    }
+
+    async fn delete_hot_list(&self, id: u64, operator: &str) -> Result<bool> {
+        let mut d = self.data();
+        let before = d.hot_list_item(id);
+        match d.hot_lists.iter_mut().find(|l| l.valid && l.list.id == id) {
+            Some(row) => row.valid = false,
+            None => return Ok(false),
+        }
+        d.push_audit_log(NewAuditLog::new(
+            operator,
+            AuditAction::Delete,
+            EntityType::HotProduct,
+            id,
+            before.as_ref().and_then(snapshot),
+            None,
+        ));
+        Ok(true)
+    }
+
+    async fn get_hot_products(&self, slot: &str) -> Result<Vec<HotProduct>> {
+        let d = self.data();
+        let list_id = match d.live_hot_list(slot) {
+            Some(id) => id,
+            None => return Ok(vec![]),
+        };
+        Ok(d.hot_list_product_ids(list_id)
+            .into_iter()
+            .map(|product_id| HotProduct { product_id })
+            .collect())
+    }
+
+    async fn get_hot_product_items(&self, slot: &str) -> Result<Vec<ProductItem>> {
+        let d = self.data();
+        Ok(d.live_hot_products(slot)
+            .into_iter()
+            .filter_map(|p| d.to_item(p))
+            .collect())
+    }
+
+    async fn get_hot_product_cards(&self, slot: &str) -> Result<Vec<ProductCard>> {
+        let d = self.data();
+        Ok(d.live_hot_products(slot)
+            .into_iter()
+            .filter(|p| p.product.status == ProductStatus::OnShelf)
+            .filter(|p| d.is_valid_brand(p.product.brand_id))
+            .filter_map(|p| d.to_item(p))
+            .map(|p| ProductCard {
+                id: p.id,
+                name: p.name,
+                title: p.title,
+                subtitle: p.subtitle,
+                img_url: p.img_url,
+                sell_price: p.sell_price,
+                brand_id: p.brand_id as u64,
+                brand_name: p.brand_name,
+            })
+            .collect())
+    }
+
+    async fn count_hot_product_refs(&self, product_id: u64) -> Result<i64> {
+        let d = self.data();
+        Ok(d.hot_products
+            .iter()
+            .filter(|h| h.product_id == product_id)
+            .filter(|h| {
+                d.hot_lists
+                    .iter()
+                    .any(|l| l.valid && l.list.id == h.list_id)
+            })
+            .count() as i64)
+    }
+}
+
+#[async_trait]
+impl CategoryRepo for MemoryRepo {
+    async fn get_categories(&self) -> Result<Vec<Category>> {
+        let d = self.data();
+        let mut res: Vec<Category> = d
+            .categories
+            .iter()
+            .filter(|c| c.valid)
+            .map(|c| c.category.clone())
+            .collect();
+        res.sort_by_key(|c| (c.sequence, c.id));
+        Ok(res)
+    }
+
+    async fn get_category(&self, id: u64) -> Result<Option<Category>> {
+        Ok(self.data().category(id).cloned())
+    }
+
+    async fn count_child_categories(&self, id: u64) -> Result<i64> {
+        Ok(self
+            .data()
+            .categories
+            .iter()
+            .filter(|c| c.valid && c.category.parent_id == id)
+            .count() as i64)
+    }
+
+    async fn create_category(
+        &self,
+        category: &NewCategory,
+        parent_path: &str,
+        operator: &str,
+    ) -> Result<Category> {
+        let mut d = self.data();
+        let sequence = d
+            .categories
+            .iter()
+            .filter(|c| c.valid && c.category.parent_id == category.parent_id)
+            .map(|c| c.category.sequence)
+            .max()
+            .unwrap_or(0);
+        let id = d.next_id("category");
+        let created = Category {
+            id,
+            parent_id: category.parent_id,
+            path: format!("{}{}/", parent_path, id),
+            name: category.name.clone(),
+            sequence: sequence + 1,
+        };
+        d.categories.push(CategoryRow {
+            category: created.clone(),
+            valid: true,
+        });
+        d.push_audit_log(NewAuditLog::new(
+            operator,
+            AuditAction::Create,
+            EntityType::Category,
+            id,
+            None,
+            snapshot(&created),
+        ));
+        Ok(created)
+    }
+
+    async fn update_category(
+        &self,
+        category: &Category,
+        parent_path: &str,
+        operator: &str,
+    ) -> Result<Option<Category>> {
+        let mut d = self.data();
+        let before = d.category(category.id).cloned();
+        let new_path = format!("{}{}/", parent_path, category.id);
+        let old_path = category.path.clone();
+        let row = match d
+            .categories
+            .iter_mut()
+            .find(|c| c.valid && c.category.id == category.id)
+        {
+            Some(row) => row,
+            None => return Ok(None),
+        };
+        row.category.parent_id = category.parent_id;
+        row.category.name = category.name.clone();
+        row.category.path = new_path.clone();
+        if old_path != new_path {
+            for c in d.categories.iter_mut() {
+                if c.category.id != category.id && c.category.path.starts_with(&old_path) {
+                    c.category.path = format!("{}{}", new_path, &c.category.path[old_path.len()..]);
+                }
+            }
+        }
+        let after = d.category(category.id).cloned();
+        d.push_audit_log(NewAuditLog::new(
+            operator,
+            AuditAction::Update,
+            EntityType::Category,
+            category.id,
+            before.as_ref().and_then(snapshot),
+            after.as_ref().and_then(snapshot),
+        ));
+        Ok(after)
+    }
+
+    async fn update_categories_sequence(
+        &self,
+        css: &[CategorySequence],
+        operator: &str,
+    ) -> Result<Vec<(u64, Change<Category>)>> {
+        let mut d = self.data();
+        for cs in css.iter() {
+            d.category(cs.id).ok_or_else(|| category_not_found(cs.id))?;
+        }
+        let mut changes = vec![];
+        for cs in css.iter() {
+            let row = d
+                .categories
+                .iter_mut()
+                .find(|c| c.valid && c.category.id == cs.id)
+                .unwrap();
+            let before = row.category.clone();
+            row.category.sequence = cs.sequence;
+            let after = row.category.clone();
+            d.push_audit_log(NewAuditLog::new(
+                operator,
+                AuditAction::UpdateSequence,
+                EntityType::Category,
+                cs.id,
+                snapshot(&before),
+                snapshot(&after),
+            ));
+            changes.push((cs.id, (before, after)));
+        }
+        Ok(changes)
+    }
+
+    async fn delete_category(&self, id: u64, operator: &str) -> Result<bool> {
+        let mut d = self.data();
+        let before = d.category(id).cloned();
+        d.product_categories
+            .retain(|(_, category_id)| *category_id != id);
+        match d.categories.iter_mut().find(|c| c.category.id == id) {
+            Some(row) => row.valid = false,
+            None => return Ok(false),
+        }
+        d.push_audit_log(NewAuditLog::new(
+            operator,
+            AuditAction::Delete,
+            EntityType::Category,
+            id,
+            before.as_ref().and_then(snapshot),
+            None,
+        ));
+        Ok(true)
+    }
+
+    async fn get_category_products(
+        &self,
+        path: &str,
+        paging: &Paging,
+        cursor: Option<Cursor>,
+    ) -> Result<Vec<BrandItem>> {
+        let d = self.data();
+        let after_id = cursor.map_or(0, |c| c.id);
+        Ok(d.category_products(path)
+            .into_iter()
+            .filter(|p| p.id > after_id)
+            .skip(paging.offset() as usize)
+            .take(paging.limit_or(MIN_ROWS) as usize)
+            .map(brand_item)
+            .collect())
+    }
+
+    async fn count_category_products(&self, path: &str) -> Result<i64> {
+        Ok(self.data().category_products(path).len() as i64)
+    }
+
+    async fn get_product_category_ids(&self, product_id: u64) -> Result<Vec<u64>> {
+        let d = self.data();
+        let mut ids: Vec<u64> = d
+            .product_categories
+            .iter()
+            .filter(|(id, category_id)| *id == product_id && d.category(*category_id).is_some())
+            .map(|(_, category_id)| *category_id)
+            .collect();
+        ids.sort_unstable();
+        Ok(ids)
+    }
+
+    async fn set_product_categories(
+        &self,
+        product_id: u64,
+        category_ids: &[u64],
+        operator: &str,
+    ) -> Result<Vec<u64>> {
+        let distinct: HashSet<u64> = category_ids.iter().copied().collect();
+        if distinct.len() != category_ids.len() {
+            return Err(anyhow!("Duplicate entry for key 'udx_pid_cid'"));
+        }
+        let mut d = self.data();
+        let mut before: Vec<u64> = d
+            .product_categories
+            .iter()
+            .filter(|(id, category_id)| *id == product_id && d.category(*category_id).is_some())
+            .map(|(_, category_id)| *category_id)
+            .collect();
+        before.sort_unstable();
+        d.product_categories.retain(|(id, _)| *id != product_id);
+        for category_id in category_ids.iter() {
+            d.product_categories.push((product_id, *category_id));
+        }
+        d.push_audit_log(NewAuditLog::new(
+            operator,
+            AuditAction::UpdateCategories,
+            EntityType::Product,
+            product_id,
+            snapshot(&before),
+            snapshot(&category_ids),
+        ));
+        Ok(before)
+    }
+}
+
+#[async_trait]
+impl AdminUserRepo for MemoryRepo {
+    async fn get_user(&self, username: &str) -> Result<Option<AdminLoginUser>> {
+        Ok(self
+            .data()
+            .users
+            .iter()
+            .find(|u| u.user.username == username && u.user.status == UserStatus::Active)
+            .map(|u| AdminLoginUser {
+                id: u.user.id,
+                username: u.user.username.clone(),
+                password: u.password.clone(),
+                role: u.user.role,
+                must_change_password: u.user.must_change_password,
+            }))
+    }
+
+    async fn create_user(
+        &self,
+        username: &str,
+        password: &str,
+        role: Role,
+        operator: &str,
+    ) -> Result<u64> {
+        let mut d = self.data();
+        if d.users.iter().any(|u| u.user.username == username) {
+            return Err(anyhow!(
+                "Duplicate entry '{}' for key 'udx_uname'",
+                username
+            ));
+        }
+        let id = d.next_id("admin_user");
+        let now = Utc::now();
+        d.users.push(UserRow {
+            user: AdminUserItem {
+                id,
+                username: username.to_owned(),
+                role,
+                status: UserStatus::Active,
+                must_change_password: false,
+                creator: operator.to_owned(),
+                modifier: String::new(),
+                created_at: now,
+                updated_at: now,
+            },
+            password: password.to_owned(),
+        });
+        Ok(id)
+    }
+
+    async fn update_password(&self, username: &str, password: &str) -> Result<bool> {
+        let mut d = self.data();
+        match d.users.iter_mut().find(|u| u.user.username == username) {
+            Some(row) => {
+                row.password = password.to_owned();
+                row.user.must_change_password = false;
+                row.user.updated_at = Utc::now();
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn get_users(&self, paging: &Paging) -> Result<Vec<AdminUserItem>> {
+        let d = self.data();
+        let mut rows: Vec<&UserRow> = d
+            .users
+            .iter()
+            .filter(|u| u.user.status != UserStatus::Deleted)
+            .collect();
+        rows.sort_by_key(|u| u.user.id);
+        Ok(rows
+            .into_iter()
+            .skip(paging.offset.unwrap_or(0) as usize)
+            .take(paging.limit_or(MAX_ROWS) as usize)
+            .map(user_item)
+            .collect())
+    }
+
+    async fn count_users(&self) -> Result<i64> {
+        Ok(self
+            .data()
+            .users
+            .iter()
+            .filter(|u| u.user.status != UserStatus::Deleted)
+            .count() as i64)
+    }
+
+    async fn get_user_by_id(&self, id: u64) -> Result<Option<AdminUserItem>> {
+        Ok(self.data().user(id).map(user_item))
+    }
+
+    async fn update_user_role(&self, id: u64, role: Role, operator: &str) -> Result<bool> {
+        let mut d = self.data();
+        match d.user_mut(id) {
+            Some(row) => {
+                row.user.role = role;
+                row.user.modifier = operator.to_owned();
+                row.user.updated_at = Utc::now();
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn update_user_status(
+        &self,
+        id: u64,
+        status: UserStatus,
+        operator: &str,
+    ) -> Result<bool> {
+        let mut d = self.data();
+        match d.user_mut(id) {
+            Some(row) => {
+                if status == UserStatus::Deleted {
+                    row.user.username = format!("{}#{}", row.user.username, id);
+                }
+                row.user.status = status;
+                row.user.modifier = operator.to_owned();
+                row.user.updated_at = Utc::now();
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn reset_password(&self, id: u64, password: &str, operator: &str) -> Result<bool> {
+        let mut d = self.data();
+        match d.user_mut(id) {
+            Some(row) => {
+                row.password = password.to_owned();
+                row.user.must_change_password = true;
+                row.user.modifier = operator.to_owned();
+                row.user.updated_at = Utc::now();
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+#[async_trait]
+impl AuditLogRepo for MemoryRepo {
+    async fn create_audit_log(&self, log: &NewAuditLog) -> Result<u64> {
+        Ok(self.data().push_audit_log(log.clone()))
+    }
+
+    async fn get_audit_logs(&self, q: &AuditQuery) -> Result<Vec<AuditLog>> {
+        Ok(self
+            .data()
+            .matching_audit_logs(q)
+            .into_iter()
+            .skip(q.offset.unwrap_or(0) as usize)
+            .take(q.limit.unwrap_or(MIN_ROWS).min(MAX_ROWS) as usize)
+            .cloned()
+            .collect())
+    }
+
+    async fn count_audit_logs(&self, q: &AuditQuery) -> Result<i64> {
+        Ok(self.data().matching_audit_logs(q).len() as i64)
+    }
+}
+
+// `NOW()` of the database, a local time without zone
+fn now() -> NaiveDateTime {
+    Local::now().naive_local()
+}
+
+fn local_date(time: &DateTime<Utc>) -> NaiveDate {
+    time.with_timezone(&Local).naive_local().date()
+}
+
+// whether `date` is within the inclusive range, an open end is unbounded
+fn in_dates(date: NaiveDate, from: Option<NaiveDate>, to: Option<NaiveDate>) -> bool {
+    from.map_or(true, |from| date >= from) && to.map_or(true, |to| date <= to)
+}
+
+fn matches_filter(row: &ProductRow, filter: &ProductFilter) -> bool {
+    let p = &row.product;
+    let status = match filter.status {
+        Some(status) => p.status == status,
+        None => p.status != ProductStatus::Deleted,
+    };
+    status
+        && filter.brand_id.map_or(true, |id| p.brand_id == id)
+        && filter.kind.map_or(true, |kind| p.kind == kind)
+        && filter.min_price.map_or(true, |min| p.sell_price >= min)
+        && filter.max_price.map_or(true, |max| p.sell_price <= max)
+        && filter.creator.as_ref().map_or(true, |c| row.creator == *c)
+        && in_dates(
+            local_date(&row.created_at),
+            filter.created_from,
+            filter.created_to,
+        )
+        && in_dates(
+            local_date(&row.updated_at),
+            filter.updated_from,
+            filter.updated_to,
+        )
+}
+
+fn sort_products(rows: &mut [&ProductRow], sort: ProductSort) {
+    rows.sort_by_key(|p| p.id);
+    match sort {
+        ProductSort::IdAsc => {}
+        ProductSort::IdDesc => rows.reverse(),
+        ProductSort::PriceAsc => rows.sort_by_key(|p| p.product.sell_price),
+        ProductSort::PriceDesc => rows.sort_by_key(|p| Reverse(p.product.sell_price)),
+        ProductSort::SequenceAsc => rows.sort_by_key(|p| p.product.sequence),
+        ProductSort::SequenceDesc => rows.sort_by_key(|p| Reverse(p.product.sequence)),
+        ProductSort::UpdatedAtAsc => rows.sort_by_key(|p| p.updated_at),
+        ProductSort::UpdatedAtDesc => rows.sort_by_key(|p| Reverse(p.updated_at)),
+    }
+}
+
+// an on shelf product of a valid brand matching `q`, scored by the number of
+// matching fields
+fn search_item(d: &Data, row: &ProductRow, q: &str) -> Option<SearchItem> {
+    let p = &row.product;
+    if p.status != ProductStatus::OnShelf || !d.is_valid_brand(p.brand_id) {
+        return None;
+    }
+    let item = d.to_item(row)?;
+    let q = q.to_lowercase();
+    let score = [&p.name, &p.alias, &p.title, &p.subtitle, &item.brand_name]
+        .iter()
+        .filter(|text| text.to_lowercase().contains(&q))
+        .count();
+    if score == 0 {
+        return None;
+    }
+    Some(SearchItem {
+        id: item.id,
+        name: item.name,
+        title: item.title,
+        subtitle: item.subtitle,
+        img_url: item.img_url,
+        brand_id: p.brand_id,
+        brand_name: item.brand_name,
+        score: score as f64,
+    })
+}
+
+fn brand_item(row: &ProductRow) -> BrandItem {
+    BrandItem {
+        id: row.id,
+        name: row.product.name.clone(),
+        title: row.product.title.clone(),
+        subtitle: row.product.subtitle.clone(),
+        img_url: row.product.img_url.clone(),
+    }
+}
+
+fn brand_trash_item(row: &BrandRow) -> TrashItem {
+    trash_item(
+        row.brand.id,
+        &row.brand.name,
+        &row.modifier,
+        row.updated_at,
+        &row.deleted,
+    )
+}
+
+// rows deleted before the deleter was kept fall back to the last write, the
+// same guess the migration makes
+fn trash_item(
+    id: u64,
+    name: &str,
+    modifier: &str,
+    updated_at: DateTime<Utc>,
+    deleted: &Option<Deleted>,
+) -> TrashItem {
+    match deleted {
+        Some(d) => TrashItem {
+            id,
+            name: name.to_owned(),
+            deleted_by: d.by.clone(),
+            deleted_at: d.at,
+        },
+        None => TrashItem {
+            id,
+            name: name.to_owned(),
+            deleted_by: modifier.to_owned(),
+            deleted_at: updated_at,
+        },
+    }
+}
+
+fn user_item(row: &UserRow) -> AdminUserItem {
+    row.user.clone()
+}
@@ -0,0 +1,291 @@
+#[cfg(feature = "test-util")]
+mod memory;
+mod mysql;
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+
+use crate::models::admin::{AdminLoginUser, AdminUserItem, Role, UserStatus};
+use crate::models::audit::{AuditAction, AuditLog, AuditQuery, NewAuditLog};
+use crate::models::cosmetics::{
+    Brand, BrandItem, BrandSequence, Category, CategorySequence, HotListItem, HotProduct,
+    ImageSequence, NewBrand, NewCategory, NewHotList, NewProduct, NewSku, PriceHistoryQuery,
+    PriceRecord, ProductCard, ProductFilter, ProductImage, ProductItem, ProductPrice,
+    ProductStatus, SearchItem, Sku, TrashItem, UpdateBrand,
+};
+use crate::models::{Cursor, Paging};
+
+#[cfg(feature = "test-util")]
+pub use memory::MemoryRepo;
+pub use mysql::MySqlRepo;
+
+/// An entity before and after a write, what the audit log records.
+pub type Change<T> = (T, T);
+
+// Writes which touch several rows are one method each, the MySQL repo runs
+// them in a transaction and the memory repo under a single lock. Every catalog
+// write records its audit log in there too, so a change is never committed
+// without its log.
+
+#[async_trait]
+pub trait BrandRepo: std::fmt::Debug + Send + Sync {
+    async fn create_brand(&self, brand: Brand, operator: &str) -> Result<u64>;
+    /// Append the brands after the existing ones, fails when a name is taken.
+    async fn create_brands(&self, brands: &[NewBrand], operator: &str) -> Result<Vec<Brand>>;
+    async fn get_brands(&self, paging: &Paging, cursor: Option<Cursor>) -> Result<Vec<Brand>>;
+    async fn count_brands(&self) -> Result<i64>;
+    async fn get_all_brands(&self) -> Result<Vec<Brand>>;
+    async fn get_brand(&self, id: u64) -> Result<Option<Brand>>;
+    /// Id of a valid brand, fails when there is none by that name.
+    async fn get_brand_id(&self, name: &str) -> Result<u64>;
+    async fn is_brand_names_valid(&self, brands: &[NewBrand]) -> Result<bool>;
+    async fn update_brand(&self, id: u64, brand: &UpdateBrand, operator: &str) -> Result<bool>;
+    async fn update_brands_sequence(
+        &self,
+        bss: &[BrandSequence],
+        operator: &str,
+    ) -> Result<Vec<(u64, Change<Option<Brand>>)>>;
+    async fn delete_brand(&self, id: u32, operator: &str) -> Result<bool>;
+    async fn restore_brand(&self, id: u64, operator: &str) -> Result<bool>;
+    /// Remove a deleted brand which no product points at.
+    async fn purge_brand(&self, id: u64, operator: &str) -> Result<bool>;
+    async fn get_deleted_brands(&self, paging: &Paging) -> Result<Vec<TrashItem>>;
+    async fn count_deleted_brands(&self) -> Result<i64>;
+    async fn get_deleted_brand(&self, id: u64) -> Result<Option<TrashItem>>;
+    async fn get_hot_brands(&self) -> Result<Vec<Brand>>;
+    async fn get_hot_brand_ids(&self) -> Result<Vec<u64>>;
+    /// Replace the hot brands, returns the ids ranked before.
+    async fn set_hot_brands(&self, brand_ids: &[u64], operator: &str) -> Result<Vec<u64>>;
+}
+
+#[async_trait]
+pub trait ProductRepo: std::fmt::Debug + Send + Sync {
+    /// Create the product along with its default sku and first price.
+    async fn create_product(
+        &self,
+        product: &NewProduct,
+        brand_id: u64,
+        operator: &str,
+    ) -> Result<ProductItem>;
+    async fn get_product(&self, id: u64) -> Result<Option<ProductItem>>;
+    /// The product when the public api shows it.
+    async fn get_valid_product(&self, id: u64) -> Result<Option<ProductItem>>;
+    async fn get_products(
+        &self,
+        filter: &ProductFilter,
+        offset: u32,
+        limit: u32,
+    ) -> Result<Vec<ProductItem>>;
+    async fn count_products(&self, filter: &ProductFilter) -> Result<i64>;
+    async fn search_products(&self, q: &str, paging: &Paging) -> Result<Vec<SearchItem>>;
+    async fn count_search_products(&self, q: &str) -> Result<i64>;
+    async fn get_brand_products(
+        &self,
+        brand_id: u32,
+        paging: &Paging,
+        cursor: Option<Cursor>,
+    ) -> Result<Vec<BrandItem>>;
+    async fn count_brand_products(&self, brand_id: u32) -> Result<i64>;
+    /// Products of a brand in any status, deleted ones included.
+    async fn count_all_brand_products(&self, brand_id: u64) -> Result<i64>;
+    /// Update the product and its default sku, a price change is recorded in
+    /// the price history. Returns the product after the update.
+    async fn update_product(
+        &self,
+        id: u64,
+        product: NewProduct,
+        operator: &str,
+    ) -> Result<Option<ProductItem>>;
+    async fn update_product_price(
+        &self,
+        id: u64,
+        price: &ProductPrice,
+        operator: &str,
+    ) -> Result<Option<ProductItem>>;
+    /// Move a product from `from` to `to`, fails when someone else changed the
+    /// status in between. The move is logged as `action`.
+    async fn update_product_status(
+        &self,
+        id: u64,
+        from: ProductStatus,
+        to: ProductStatus,
+        action: AuditAction,
+        operator: &str,
+    ) -> Result<bool>;
+    /// Write checked import rows all or nothing, a row with an id updates that
+    /// product. Returns the product ids in row order.
+    async fn import_products(&self, products: Vec<NewProduct>, operator: &str) -> Result<Vec<u64>>;
+    /// Remove a deleted product for good, along with the rows only it owns.
+    async fn purge_product(&self, id: u64, operator: &str) -> Result<bool>;
+    /// Whether the product exists and is not deleted, drafts included.
+    async fn is_product_exist(&self, id: u64) -> Result<bool>;
+    /// Whether the product is visible to the public api, on shelf under a
+    /// valid brand.
+    async fn is_product_valid(&self, id: u64) -> Result<bool>;
+    async fn get_deleted_products(&self, paging: &Paging) -> Result<Vec<TrashItem>>;
+    async fn count_deleted_products(&self) -> Result<i64>;
+
+    async fn get_product_skus(&self, product_id: u64) -> Result<Vec<Sku>>;
+    async fn get_valid_skus(&self, product_id: u64) -> Result<Vec<Sku>>;
+    async fn get_sku(&self, product_id: u64, id: u64) -> Result<Option<Sku>>;
+    /// Whether another live sku already uses the barcode.
+    async fn is_barcode_taken(&self, barcode: &str, exclude_id: u64) -> Result<bool>;
+    /// A sku without a sequence goes after the existing ones.
+    async fn create_sku(&self, product_id: u64, sku: NewSku, operator: &str) -> Result<Sku>;
+    async fn update_sku(
+        &self,
+        product_id: u64,
+        id: u64,
+        sku: &NewSku,
+        operator: &str,
+    ) -> Result<Option<Sku>>;
+    /// Returns the deleted sku, the last sku of a product is never deleted.
+    async fn delete_sku(&self, product_id: u64, id: u64, operator: &str) -> Result<Sku>;
+    async fn get_price_history(
+        &self,
+        product_id: u64,
+        q: &PriceHistoryQuery,
+    ) -> Result<Vec<PriceRecord>>;
+
+    async fn get_product_images(&self, product_id: u64) -> Result<Vec<ProductImage>>;
+    /// Append `(url, thumb_url)` images after the existing ones.
+    async fn create_product_images(
+        &self,
+        product_id: u64,
+        images: &[(String, String)],
+        operator: &str,
+    ) -> Result<Change<Vec<ProductImage>>>;
+    async fn update_images_sequence(
+        &self,
+        product_id: u64,
+        iss: &[ImageSequence],
+        operator: &str,
+    ) -> Result<Change<Vec<ProductImage>>>;
+    async fn delete_product_image(
+        &self,
+        product_id: u64,
+        id: u64,
+        operator: &str,
+    ) -> Result<Change<Vec<ProductImage>>>;
+}
+
+#[async_trait]
+pub trait HotProductRepo: std::fmt::Debug + Send + Sync {
+    async fn create_hot_list(
+        &self,
+        list: &NewHotList,
+        product_ids: &[u64],
+        operator: &str,
+    ) -> Result<u64>;
+    /// End the live lists of the slot and start the new one in their place
+    /// right away, returns the new list.
+    async fn replace_hot_list(
+        &self,
+        list: &NewHotList,
+        product_ids: &[u64],
+        operator: &str,
+    ) -> Result<u64>;
+    /// Lists of a slot which have not ended yet, the latest started one is live.
+    async fn get_hot_lists(&self, slot: &str) -> Result<Vec<HotListItem>>;
+    async fn get_hot_list(&self, id: u64) -> Result<Option<HotListItem>>;
+    async fn delete_hot_list(&self, id: u64, operator: &str) -> Result<bool>;
+    async fn get_hot_products(&self, slot: &str) -> Result<Vec<HotProduct>>;
+    async fn get_hot_product_items(&self, slot: &str) -> Result<Vec<ProductItem>>;
+    /// Cards of the live list of a slot, skipping products which are not on
+    /// shelf and brands which are not valid any more.
+    async fn get_hot_product_cards(&self, slot: &str) -> Result<Vec<ProductCard>>;
+    /// Hot entries of valid lists pointing at a product, ended lists included.
+    async fn count_hot_product_refs(&self, product_id: u64) -> Result<i64>;
+}
+
+#[async_trait]
+pub trait CategoryRepo: std::fmt::Debug + Send + Sync {
+    async fn get_categories(&self) -> Result<Vec<Category>>;
+    async fn get_category(&self, id: u64) -> Result<Option<Category>>;
+    async fn count_child_categories(&self, id: u64) -> Result<i64>;
+    /// Append the category after its siblings.
+    async fn create_category(
+        &self,
+        category: &NewCategory,
+        parent_path: &str,
+        operator: &str,
+    ) -> Result<Category>;
+    /// Rename or move a category, the paths of its descendants follow the move.
+    async fn update_category(
+        &self,
+        category: &Category,
+        parent_path: &str,
+        operator: &str,
+    ) -> Result<Option<Category>>;
+    async fn update_categories_sequence(
+        &self,
+        css: &[CategorySequence],
+        operator: &str,
+    ) -> Result<Vec<(u64, Change<Category>)>>;
+    async fn delete_category(&self, id: u64, operator: &str) -> Result<bool>;
+    /// Products linked to the category at `path` or any of its descendants.
+    async fn get_category_products(
+        &self,
+        path: &str,
+        paging: &Paging,
+        cursor: Option<Cursor>,
+    ) -> Result<Vec<BrandItem>>;
+    async fn count_category_products(&self, path: &str) -> Result<i64>;
+    async fn get_product_category_ids(&self, product_id: u64) -> Result<Vec<u64>>;
+    /// Replace the categories of a product, returns the ids linked before.
+    async fn set_product_categories(
+        &self,
+        product_id: u64,
+        category_ids: &[u64],
+        operator: &str,
+    ) -> Result<Vec<u64>>;
+}
+
+#[async_trait]
+pub trait AdminUserRepo: std::fmt::Debug + Send + Sync {
+    /// An active user, for signing in.
+    async fn get_user(&self, username: &str) -> Result<Option<AdminLoginUser>>;
+    async fn create_user(
+        &self,
+        username: &str,
+        password: &str,
+        role: Role,
+        operator: &str,
+    ) -> Result<u64>;
+    async fn update_password(&self, username: &str, password: &str) -> Result<bool>;
+    async fn get_users(&self, paging: &Paging) -> Result<Vec<AdminUserItem>>;
+    async fn count_users(&self) -> Result<i64>;
+    async fn get_user_by_id(&self, id: u64) -> Result<Option<AdminUserItem>>;
+    async fn update_user_role(&self, id: u64, role: Role, operator: &str) -> Result<bool>;
+    async fn update_user_status(&self, id: u64, status: UserStatus, operator: &str)
+        -> Result<bool>;
+    /// Set a new password which has to be changed on the next sign in.
+    async fn reset_password(&self, id: u64, password: &str, operator: &str) -> Result<bool>;
+}
+
+#[async_trait]
+pub trait AuditLogRepo: std::fmt::Debug + Send + Sync {
+    async fn create_audit_log(&self, log: &NewAuditLog) -> Result<u64>;
+    async fn get_audit_logs(&self, q: &AuditQuery) -> Result<Vec<AuditLog>>;
+    async fn count_audit_logs(&self, q: &AuditQuery) -> Result<i64>;
+}
+
+// the sku `id` among the live skus of a product, when it may be deleted
+fn removable_sku(skus: &[Sku], id: u64) -> Result<Sku> {
+    let sku = skus
+        .iter()
+        .find(|s| s.id == id)
+        .ok_or_else(|| anyhow!("规格不存在, id: {}", id))?;
+    if skus.len() == 1 {
+        return Err(anyhow!("商品至少保留一个规格"));
+    }
+    Ok(sku.clone())
+}
+
+fn image_not_found(id: u64) -> anyhow::Error {
+    anyhow!("图片不存在, id: {}", id)
+}
+
+fn category_not_found(id: u64) -> anyhow::Error {
+    anyhow!("分类不存在, id: {}", id)
+}
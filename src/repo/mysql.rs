@@ -0,0 +1,1124 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use serde_json::Value;
+use sqlx::mysql::{MySqlConnection, MySqlPool};
+
+use super::{
+    category_not_found, image_not_found, removable_sku, AdminUserRepo, AuditLogRepo, BrandRepo,
+    CategoryRepo, Change, HotProductRepo, ProductRepo,
+};
+use crate::models::admin::{AdminLoginUser, AdminUserItem, Role, UserStatus};
+use crate::models::audit::{snapshot, AuditAction, AuditLog, AuditQuery, EntityType, NewAuditLog};
+use crate::models::cosmetics::{
+    Brand, BrandItem, BrandSequence, Category, CategorySequence, HotListItem, HotProduct,
+    ImageSequence, NewBrand, NewCategory, NewHotList, NewProduct, NewSku, PriceHistoryQuery,
+    PriceRecord, ProductCard, ProductFilter, ProductImage, ProductItem, ProductPrice,
+    ProductStatus, SearchItem, Sku, TrashItem, UpdateBrand,
+};
+use crate::models::{Cursor, Paging};
+use crate::sql;
+
+/// Everything in MySQL through the functions of `sql`.
+#[derive(Clone, Debug)]
+pub struct MySqlRepo {
+    pool: MySqlPool,
+}
+
+impl MySqlRepo {
+    pub fn new(pool: MySqlPool) -> Self {
+        Self { pool }
+    }
+
+    pub fn pool(&self) -> &MySqlPool {
+        &self.pool
+    }
+}
+
+#[async_trait]
+impl BrandRepo for MySqlRepo {
+    async fn create_brand(&self, brand: Brand, operator: &str) -> Result<u64> {
+        sql::cosmetics::create_brand(&self.pool, brand, operator).await
+    }
+
+    async fn create_brands(&self, brands: &[NewBrand], operator: &str) -> Result<Vec<Brand>> {
+        let mut tx = self.pool.begin().await?;
+        sql::cosmetics::is_brand_names_valid(&mut tx, &brands.to_vec()).await?;
+        let mut max_sequence = sql::cosmetics::get_max_brand_sequence(&mut tx).await?;
+        let new_brands: Vec<Brand> = brands
+            .iter()
+            .map(|b| {
+                max_sequence += 1;
+                Brand {
+                    id: 0,
+                    name: b.name.clone(),
+                    en_name: String::new(),
+                    logo_url: String::new(),
+                    country: String::new(),
+                    description: String::new(),
+                    website: String::new(),
+                    sequence: max_sequence,
+                    is_hot: false,
+                }
+            })
+            .collect();
+        let ok = sql::cosmetics::create_brands(&mut tx, new_brands.clone(), operator).await?;
+        if !ok {
+            return Err(anyhow!("Create brands failed."));
+        }
+        let mut created = vec![];
+        for b in new_brands.into_iter() {
+            let id = sql::cosmetics::get_brand_id(&mut tx, &b.name).await?;
+            let brand = Brand { id, ..b };
+            record(
+                &mut tx,
+                operator,
+                AuditAction::Create,
+                EntityType::Brand,
+                id,
+                None,
+                snapshot(&brand),
+            )
+            .await?;
+            created.push(brand);
+        }
+        tx.commit().await?;
+        Ok(created)
+    }
+
+    async fn get_brands(&self, paging: &Paging, cursor: Option<Cursor>) -> Result<Vec<Brand>> {
+        sql::cosmetics::get_brands(&self.pool, paging, cursor).await
+    }
+
+    async fn count_brands(&self) -> Result<i64> {
+        sql::cosmetics::count_brands(&self.pool).await
+    }
+
+    async fn get_all_brands(&self) -> Result<Vec<Brand>> {
+        sql::cosmetics::get_all_brands(&self.pool).await
+    }
+
+    async fn get_brand(&self, id: u64) -> Result<Option<Brand>> {
+        sql::cosmetics::get_brand(&self.pool, id).await
+    }
+
+    async fn get_brand_id(&self, name: &str) -> Result<u64> {
+        sql::cosmetics::get_brand_id(&self.pool, name).await
+    }
+
+    async fn is_brand_names_valid(&self, brands: &[NewBrand]) -> Result<bool> {
+        sql::cosmetics::is_brand_names_valid(&self.pool, &brands.to_vec()).await
+    }
+
+    async fn update_brand(&self, id: u64, brand: &UpdateBrand, operator: &str) -> Result<bool> {
+        let mut tx = self.pool.begin().await?;
+        let before = sql::cosmetics::get_brand(&mut tx, id).await?;
+        if !sql::cosmetics::update_brand(&mut tx, id, brand, operator).await? {
+            return Ok(false);
+        }
+        let after = sql::cosmetics::get_brand(&mut tx, id).await?;
+        record(
+            &mut tx,
+            operator,
+            AuditAction::Update,
+            EntityType::Brand,
+            id,
+            before.as_ref().and_then(snapshot),
+            after.as_ref().and_then(snapshot),
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn update_brands_sequence(
+        &self,
+        bss: &[BrandSequence],
+        operator: &str,
+    ) -> Result<Vec<(u64, Change<Option<Brand>>)>> {
+        let ids: Vec<u64> = bss.iter().map(|bs| bs.id).collect();
+        let mut tx = self.pool.begin().await?;
+        sql::cosmetics::is_brand_ids_valid(&mut tx, &ids).await?;
+        let mut changes = vec![];
+        for bs in bss.iter() {
+            let before = sql::cosmetics::get_brand(&mut tx, bs.id).await?;
+            sql::cosmetics::update_brand_sequence(&mut tx, bs, operator).await?;
+            let after = sql::cosmetics::get_brand(&mut tx, bs.id).await?;
+            record(
+                &mut tx,
+                operator,
+                AuditAction::UpdateSequence,
+                EntityType::Brand,
+                bs.id,
+                before.as_ref().and_then(snapshot),
+                after.as_ref().and_then(snapshot),
+            )
+            .await?;
+            changes.push((bs.id, (before, after)));
+        }
+        tx.commit().await?;
+        Ok(changes)
+    }
+
+    async fn delete_brand(&self, id: u32, operator: &str) -> Result<bool> {
+        let mut tx = self.pool.begin().await?;
+        let before = sql::cosmetics::get_brand(&mut tx, id as u64).await?;
+        if !sql::cosmetics::delete_brand(&mut tx, id, operator).await? {
+            return Ok(false);
+        }
+        record(
+            &mut tx,
+            operator,
+            AuditAction::Delete,
+            EntityType::Brand,
+            id as u64,
+            before.as_ref().and_then(snapshot),
+            None,
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn restore_brand(&self, id: u64, operator: &str) -> Result<bool> {
+        let mut tx = self.pool.begin().await?;
+        if !sql::cosmetics::restore_brand(&mut tx, id, operator).await? {
+            return Ok(false);
+        }
+        let after = sql::cosmetics::get_brand(&mut tx, id).await?;
+        record(
+            &mut tx,
+            operator,
+            AuditAction::Restore,
+            EntityType::Brand,
+            id,
+            None,
+            after.as_ref().and_then(snapshot),
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn purge_brand(&self, id: u64, operator: &str) -> Result<bool> {
+        let mut tx = self.pool.begin().await?;
+        let before = sql::cosmetics::get_deleted_brand(&mut tx, id).await?;
+        if !sql::cosmetics::purge_brand(&mut tx, id).await? {
+            return Ok(false);
+        }
+        record(
+            &mut tx,
+            operator,
+            AuditAction::Purge,
+            EntityType::Brand,
+            id,
+            before.as_ref().and_then(snapshot),
+            None,
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn get_deleted_brands(&self, paging: &Paging) -> Result<Vec<TrashItem>> {
+        sql::cosmetics::get_deleted_brands(&self.pool, paging).await
+    }
+
+    async fn count_deleted_brands(&self) -> Result<i64> {
+        sql::cosmetics::count_deleted_brands(&self.pool).await
+    }
+
+    async fn get_deleted_brand(&self, id: u64) -> Result<Option<TrashItem>> {
+        sql::cosmetics::get_deleted_brand(&self.pool, id).await
+    }
+
+    async fn get_hot_brands(&self) -> Result<Vec<Brand>> {
+        sql::cosmetics::get_hot_brands(&self.pool).await
+    }
+
+    async fn get_hot_brand_ids(&self) -> Result<Vec<u64>> {
+        sql::cosmetics::get_hot_brand_ids(&self.pool).await
+    }
+
+    async fn set_hot_brands(&self, brand_ids: &[u64], operator: &str) -> Result<Vec<u64>> {
+        let mut tx = self.pool.begin().await?;
+        if !brand_ids.is_empty() {
+            sql::cosmetics::is_brand_ids_valid(&mut tx, brand_ids).await?;
+        }
+        let before = sql::cosmetics::get_hot_brand_ids(&mut tx).await?;
+        sql::cosmetics::delete_hot_brands(&mut tx, operator).await?;
+        sql::cosmetics::create_hot_brands(&mut tx, brand_ids, operator).await?;
+        record(
+            &mut tx,
+            operator,
+            AuditAction::Replace,
+            EntityType::HotBrand,
+            0,
+            snapshot(&before),
+            snapshot(&brand_ids),
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(before)
+    }
+}
+
+#[async_trait]
+impl ProductRepo for MySqlRepo {
+    async fn create_product(
+        &self,
+        product: &NewProduct,
+        brand_id: u64,
+        operator: &str,
+    ) -> Result<ProductItem> {
+        let mut tx = self.pool.begin().await?;
+        let after = create_product(&mut tx, product, brand_id, operator).await?;
+        tx.commit().await?;
+        Ok(after)
+    }
+
+    async fn get_product(&self, id: u64) -> Result<Option<ProductItem>> {
+        sql::cosmetics::get_product(&self.pool, id).await
+    }
+
+    async fn get_valid_product(&self, id: u64) -> Result<Option<ProductItem>> {
+        sql::cosmetics::get_valid_product(&self.pool, id).await
+    }
+
+    async fn get_products(
+        &self,
+        filter: &ProductFilter,
+        offset: u32,
+        limit: u32,
+    ) -> Result<Vec<ProductItem>> {
+        sql::cosmetics::get_all_products(&self.pool, filter, offset, limit).await
+    }
+
+    async fn count_products(&self, filter: &ProductFilter) -> Result<i64> {
+        sql::cosmetics::count_products(&self.pool, filter).await
+    }
+
+    async fn search_products(&self, q: &str, paging: &Paging) -> Result<Vec<SearchItem>> {
+        sql::cosmetics::search_products(&self.pool, q, paging).await
+    }
+
+    async fn count_search_products(&self, q: &str) -> Result<i64> {
+        sql::cosmetics::count_search_products(&self.pool, q).await
+    }
+
+    async fn get_brand_products(
+        &self,
+        brand_id: u32,
+        paging: &Paging,
+        cursor: Option<Cursor>,
+    ) -> Result<Vec<BrandItem>> {
+        sql::cosmetics::get_brand_detail(&self.pool, brand_id, paging, cursor).await
+    }
+
+    async fn count_brand_products(&self, brand_id: u32) -> Result<i64> {
+        sql::cosmetics::count_brand_products(&self.pool, brand_id).await
+    }
+
+    async fn count_all_brand_products(&self, brand_id: u64) -> Result<i64> {
+        sql::cosmetics::count_all_brand_products(&self.pool, brand_id).await
+    }
+
+    async fn update_product(
+        &self,
+        id: u64,
+        product: NewProduct,
+        operator: &str,
+    ) -> Result<Option<ProductItem>> {
+        let mut tx = self.pool.begin().await?;
+        let after = update_product(&mut tx, id, product, operator).await?;
+        tx.commit().await?;
+        Ok(after)
+    }
+
+    async fn update_product_price(
+        &self,
+        id: u64,
+        price: &ProductPrice,
+        operator: &str,
+    ) -> Result<Option<ProductItem>> {
+        let mut tx = self.pool.begin().await?;
+        let before = sql::cosmetics::get_product(&mut tx, id).await?;
+        if !sql::cosmetics::update_product_price(&mut tx, id, price, operator).await? {
+            return Ok(None);
+        }
+        let after = sql::cosmetics::get_product(&mut tx, id).await?;
+        if let Some(after) = &after {
+            sync_default_sku(&mut tx, before.as_ref(), after, operator).await?;
+        }
+        record(
+            &mut tx,
+            operator,
+            AuditAction::UpdatePrice,
+            EntityType::Product,
+            id,
+            before.as_ref().and_then(snapshot),
+            after.as_ref().and_then(snapshot),
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(after)
+    }
+
+    async fn update_product_status(
+        &self,
+        id: u64,
+        from: ProductStatus,
+        to: ProductStatus,
+        action: AuditAction,
+        operator: &str,
+    ) -> Result<bool> {
+        let mut tx = self.pool.begin().await?;
+        let before = sql::cosmetics::get_product(&mut tx, id).await?;
+        if !sql::cosmetics::update_product_status(&mut tx, id, from, to, operator).await? {
+            return Ok(false);
+        }
+        let after = sql::cosmetics::get_product(&mut tx, id).await?;
+        record(
+            &mut tx,
+            operator,
+            action,
+            EntityType::Product,
+            id,
+            before.as_ref().and_then(snapshot),
+            after.as_ref().and_then(snapshot),
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn import_products(&self, products: Vec<NewProduct>, operator: &str) -> Result<Vec<u64>> {
+        let mut tx = self.pool.begin().await?;
+        let mut ids = vec![];
+        for product in products.into_iter() {
+            let id = match product.id {
+                Some(id) => {
+                    update_product(&mut tx, id, product, operator)
+                        .await?
+                        .ok_or_else(|| anyhow!("Product not exist, id: {}.", id))?;
+                    id
+                }
+                None => {
+                    create_product(&mut tx, &product, product.brand_id, operator)
+                        .await?
+                        .id
+                }
+            };
+            ids.push(id);
+        }
+        tx.commit().await?;
+        Ok(ids)
+    }
+
+    async fn purge_product(&self, id: u64, operator: &str) -> Result<bool> {
+        let mut tx = self.pool.begin().await?;
+        let before = sql::cosmetics::get_product(&mut tx, id).await?;
+        if !sql::cosmetics::purge_product(&mut tx, id).await? {
+            return Ok(false);
+        }
+        record(
+            &mut tx,
+            operator,
+            AuditAction::Purge,
+            EntityType::Product,
+            id,
+            before.as_ref().and_then(snapshot),
+            None,
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn is_product_exist(&self, id: u64) -> Result<bool> {
+        sql::cosmetics::is_product_exist(&self.pool, id).await
+    }
+
+    async fn is_product_valid(&self, id: u64) -> Result<bool> {
+        sql::cosmetics::is_product_valid(&self.pool, id).await
+    }
+
+    async fn get_deleted_products(&self, paging: &Paging) -> Result<Vec<TrashItem>> {
+        sql::cosmetics::get_deleted_products(&self.pool, paging).await
+    }
+
+    async fn count_deleted_products(&self) -> Result<i64> {
+        sql::cosmetics::count_deleted_products(&self.pool).await
+    }
+
+    async fn get_product_skus(&self, product_id: u64) -> Result<Vec<Sku>> {
+        sql::cosmetics::get_product_skus(&self.pool, product_id).await
+    }
+
+    async fn get_valid_skus(&self, product_id: u64) -> Result<Vec<Sku>> {
+        sql::cosmetics::get_valid_skus(&self.pool, product_id).await
+    }
+
+    async fn get_sku(&self, product_id: u64, id: u64) -> Result<Option<Sku>> {
+        sql::cosmetics::get_sku(&self.pool, product_id, id).await
+    }
+
+    async fn is_barcode_taken(&self, barcode: &str, exclude_id: u64) -> Result<bool> {
+        sql::cosmetics::is_barcode_taken(&self.pool, barcode, exclude_id).await
+    }
+
+    async fn create_sku(&self, product_id: u64, mut sku: NewSku, operator: &str) -> Result<Sku> {
+        let mut tx = self.pool.begin().await?;
+        if sku.sequence == 0 {
+            sku.sequence = sql::cosmetics::get_max_sku_sequence(&mut tx, product_id).await? + 1;
+        }
+        let id = sql::cosmetics::create_sku(&mut tx, product_id, &sku, operator).await?;
+        let price = ProductPrice {
+            sell_price: sku.sell_price,
+            import_price: sku.import_price,
+        };
+        sql::cosmetics::create_price_history(&mut tx, product_id, id, &price, operator).await?;
+        sql::cosmetics::sync_product_from_sku(&mut tx, product_id, operator).await?;
+        let after = sql::cosmetics::get_sku(&mut tx, product_id, id)
+            .await?
+            .ok_or_else(|| anyhow!("规格创建失败"))?;
+        record(
+            &mut tx,
+            operator,
+            AuditAction::Create,
+            EntityType::Sku,
+            id,
+            None,
+            snapshot(&after),
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(after)
+    }
+
+    async fn update_sku(
+        &self,
+        product_id: u64,
+        id: u64,
+        sku: &NewSku,
+        operator: &str,
+    ) -> Result<Option<Sku>> {
+        let mut tx = self.pool.begin().await?;
+        let before = match sql::cosmetics::get_sku(&mut tx, product_id, id).await? {
+            Some(s) => s,
+            None => return Ok(None),
+        };
+        if !sql::cosmetics::update_sku(&mut tx, product_id, id, sku, operator).await? {
+            return Ok(None);
+        }
+        if before.sell_price != sku.sell_price || before.import_price != sku.import_price {
+            let price = ProductPrice {
+                sell_price: sku.sell_price,
+                import_price: sku.import_price,
+            };
+            sql::cosmetics::create_price_history(&mut tx, product_id, id, &price, operator).await?;
+        }
+        sql::cosmetics::sync_product_from_sku(&mut tx, product_id, operator).await?;
+        let after = sql::cosmetics::get_sku(&mut tx, product_id, id).await?;
+        record(
+            &mut tx,
+            operator,
+            AuditAction::Update,
+            EntityType::Sku,
+            id,
+            snapshot(&before),
+            after.as_ref().and_then(snapshot),
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(after)
+    }
+
+    async fn delete_sku(&self, product_id: u64, id: u64, operator: &str) -> Result<Sku> {
+        let mut tx = self.pool.begin().await?;
+        // two deletes must not both see a second sku
+        if !sql::cosmetics::lock_product(&mut tx, product_id).await? {
+            return Err(anyhow!("Product not exist, id: {}.", product_id));
+        }
+        let skus = sql::cosmetics::get_product_skus(&mut tx, product_id).await?;
+        let before = removable_sku(&skus, id)?;
+        if !sql::cosmetics::delete_sku(&mut tx, product_id, id, operator).await? {
+            return Err(anyhow!("规格删除失败, id: {}", id));
+        }
+        sql::cosmetics::sync_product_from_sku(&mut tx, product_id, operator).await?;
+        record(
+            &mut tx,
+            operator,
+            AuditAction::Delete,
+            EntityType::Sku,
+            id,
+            snapshot(&before),
+            None,
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(before)
+    }
+
+    async fn get_price_history(
+        &self,
+        product_id: u64,
+        q: &PriceHistoryQuery,
+    ) -> Result<Vec<PriceRecord>> {
+        sql::cosmetics::get_price_history(&self.pool, product_id, q).await
+    }
+
+    async fn get_product_images(&self, product_id: u64) -> Result<Vec<ProductImage>> {
+        sql::cosmetics::get_product_images(&self.pool, product_id).await
+    }
+
+    async fn create_product_images(
+        &self,
+        product_id: u64,
+        images: &[(String, String)],
+        operator: &str,
+    ) -> Result<Change<Vec<ProductImage>>> {
+        let mut tx = self.pool.begin().await?;
+        let before = sql::cosmetics::get_product_images(&mut tx, product_id).await?;
+        let mut sequence = sql::cosmetics::get_max_image_sequence(&mut tx, product_id).await?;
+        for (url, thumb_url) in images.iter() {
+            sequence += 1;
+            sql::cosmetics::create_product_image(
+                &mut tx, product_id, url, thumb_url, sequence, operator,
+            )
+            .await?;
+        }
+        sql::cosmetics::sync_product_cover(&mut tx, product_id).await?;
+        let after = sql::cosmetics::get_product_images(&mut tx, product_id).await?;
+        record(
+            &mut tx,
+            operator,
+            AuditAction::UpdateImages,
+            EntityType::Product,
+            product_id,
+            snapshot(&before),
+            snapshot(&after),
+        )
+        .await?;
+        tx.commit().await?;
+        Ok((before, after))
+    }
+
+    async fn update_images_sequence(
+        &self,
+        product_id: u64,
+        iss: &[ImageSequence],
+        operator: &str,
+    ) -> Result<Change<Vec<ProductImage>>> {
+        let mut tx = self.pool.begin().await?;
+        let before = sql::cosmetics::get_product_images(&mut tx, product_id).await?;
+        for is in iss.iter() {
+            if !sql::cosmetics::update_image_sequence(&mut tx, product_id, is, operator).await? {
+                return Err(image_not_found(is.id));
+            }
+        }
+        sql::cosmetics::sync_product_cover(&mut tx, product_id).await?;
+        let after = sql::cosmetics::get_product_images(&mut tx, product_id).await?;
+        record(
+            &mut tx,
+            operator,
+            AuditAction::UpdateImages,
+            EntityType::Product,
+            product_id,
+            snapshot(&before),
+            snapshot(&after),
+        )
+        .await?;
+        tx.commit().await?;
+        Ok((before, after))
+    }
+
+    async fn delete_product_image(
+        &self,
+        product_id: u64,
+        id: u64,
+        operator: &str,
+    ) -> Result<Change<Vec<ProductImage>>> {
+        let mut tx = self.pool.begin().await?;
+        let before = sql::cosmetics::get_product_images(&mut tx, product_id).await?;
+        if !sql::cosmetics::delete_product_image(&mut tx, product_id, id, operator).await? {
+            return Err(image_not_found(id));
+        }
+        sql::cosmetics::sync_product_cover(&mut tx, product_id).await?;
+        let after = sql::cosmetics::get_product_images(&mut tx, product_id).await?;
+        record(
+            &mut tx,
+            operator,
+            AuditAction::UpdateImages,
+            EntityType::Product,
+            product_id,
+            snapshot(&before),
+            snapshot(&after),
+        )
+        .await?;
+        tx.commit().await?;
+        Ok((before, after))
+    }
+}
+
+#[async_trait]
+impl HotProductRepo for MySqlRepo {
+    async fn create_hot_list(
+        &self,
+        list: &NewHotList,
+        product_ids: &[u64],
+        operator: &str,
+    ) -> Result<u64> {
+        let mut tx = self.pool.begin().await?;
+        let id = sql::cosmetics::create_hot_list(&mut tx, list, operator).await?;
+        if !product_ids.is_empty() {
+            sql::cosmetics::create_hot_products(&mut tx, id, product_ids.to_vec(), operator)
+                .await?;
+        }
+        let after = hot_list_item(&mut tx, id).await?;
+        record(
+            &mut tx,
+            operator,
+            AuditAction::Create,
+            EntityType::HotProduct,
+            id,
+            None,
+            after.as_ref().and_then(snapshot),
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    async fn replace_hot_list(
+        &self,
+        list: &NewHotList,
+        product_ids: &[u64],
+        operator: &str,
+    ) -> Result<u64> {
+        let mut tx = self.pool.begin().await?;
+        let ended = sql::cosmetics::get_live_hot_list_ids(&mut tx, &list.slot).await?;
+        let mut before = vec![];
+        for ended_id in ended.iter() {
+            before.push(hot_list_item(&mut tx, *ended_id).await?);
+        }
+        if !ended.is_empty() {
+            sql::cosmetics::end_hot_lists(&mut tx, &ended, operator).await?;
+        }
+        for (ended_id, before) in ended.iter().zip(before.iter()) {
+            let after = hot_list_item(&mut tx, *ended_id).await?;
+            record(
+                &mut tx,
+                operator,
+                AuditAction::Update,
+                EntityType::HotProduct,
+                *ended_id,
+                before.as_ref().and_then(snapshot),
+                after.as_ref().and_then(snapshot),
+            )
+            .await?;
+        }
+        let id = sql::cosmetics::create_hot_list(&mut tx, list, operator).await?;
+        if !product_ids.is_empty() {
+            sql::cosmetics::create_hot_products(&mut tx, id, product_ids.to_vec(), operator)
+                .await?;
+        }
+        let after = hot_list_item(&mut tx, id).await?;
+        record(
+            &mut tx,
+            operator,
+            AuditAction::Create,
+            EntityType::HotProduct,
+            id,
+            None,
+            after.as_ref().and_then(snapshot),
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    async fn get_hot_lists(&self, slot: &str) -> Result<Vec<HotListItem>> {
+        let lists = sql::cosmetics::get_hot_lists(&self.pool, slot).await?;
+        let mut res = vec![];
+        for list in lists.into_iter() {
+            let product_ids = sql::cosmetics::get_hot_list_product_ids(&self.pool, list.id).await?;
+            res.push(HotListItem { list, product_ids });
+        }
+        Ok(res)
+    }
+
+    async fn get_hot_list(&self, id: u64) -> Result<Option<HotListItem>> {
+        let mut conn = self.pool.acquire().await?;
+        hot_list_item(&mut conn, id).await
+    }
+
+    async fn delete_hot_list(&self, id: u64, operator: &str) -> Result<bool> {
+        let mut tx = self.pool.begin().await?;
+        let before = hot_list_item(&mut tx, id).await?;
+        if !sql::cosmetics::delete_hot_list(&mut tx, id, operator).await? {
+            return Ok(false);
+        }
+        record(
+            &mut tx,
+            operator,
+            AuditAction::Delete,
+            EntityType::HotProduct,
+            id,
+            before.as_ref().and_then(snapshot),
+            None,
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn get_hot_products(&self, slot: &str) -> Result<Vec<HotProduct>> {
+        sql::cosmetics::get_hot_products(&self.pool, slot).await
+    }
+
+    async fn get_hot_product_items(&self, slot: &str) -> Result<Vec<ProductItem>> {
+        sql::cosmetics::get_hot_product_items(&self.pool, slot).await
+    }
+
+    async fn get_hot_product_cards(&self, slot: &str) -> Result<Vec<ProductCard>> {
+        sql::cosmetics::get_hot_product_cards(&self.pool, slot).await
+    }
+
+    async fn count_hot_product_refs(&self, product_id: u64) -> Result<i64> {
+        sql::cosmetics::count_hot_product_refs(&self.pool, product_id).await
+    }
+}
+
+#[async_trait]
+impl CategoryRepo for MySqlRepo {
+    async fn get_categories(&self) -> Result<Vec<Category>> {
+        sql::cosmetics::get_categories(&self.pool).await
+    }
+
+    async fn get_category(&self, id: u64) -> Result<Option<Category>> {
+        sql::cosmetics::get_category(&self.pool, id).await
+    }
+
+    async fn count_child_categories(&self, id: u64) -> Result<i64> {
+        sql::cosmetics::count_child_categories(&self.pool, id).await
+    }
+
+    async fn create_category(
+        &self,
+        category: &NewCategory,
+        parent_path: &str,
+        operator: &str,
+    ) -> Result<Category> {
+        let mut tx = self.pool.begin().await?;
+        let sequence =
+            sql::cosmetics::get_max_category_sequence(&mut tx, category.parent_id).await?;
+        let id =
+            sql::cosmetics::create_category(&mut tx, category, parent_path, sequence + 1, operator)
+                .await?;
+        let after = sql::cosmetics::get_category(&mut tx, id)
+            .await?
+            .ok_or_else(|| anyhow!("Create category failed."))?;
+        record(
+            &mut tx,
+            operator,
+            AuditAction::Create,
+            EntityType::Category,
+            id,
+            None,
+            snapshot(&after),
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(after)
+    }
+
+    async fn update_category(
+        &self,
+        category: &Category,
+        parent_path: &str,
+        operator: &str,
+    ) -> Result<Option<Category>> {
+        let mut tx = self.pool.begin().await?;
+        let before = sql::cosmetics::get_category(&mut tx, category.id).await?;
+        if !sql::cosmetics::update_category(&mut tx, category, parent_path, operator).await? {
+            return Ok(None);
+        }
+        let after = sql::cosmetics::get_category(&mut tx, category.id).await?;
+        record(
+            &mut tx,
+            operator,
+            AuditAction::Update,
+            EntityType::Category,
+            category.id,
+            before.as_ref().and_then(snapshot),
+            after.as_ref().and_then(snapshot),
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(after)
+    }
+
+    async fn update_categories_sequence(
+        &self,
+        css: &[CategorySequence],
+        operator: &str,
+    ) -> Result<Vec<(u64, Change<Category>)>> {
+        let mut tx = self.pool.begin().await?;
+        let mut changes = vec![];
+        for cs in css.iter() {
+            let before = sql::cosmetics::get_category(&mut tx, cs.id)
+                .await?
+                .ok_or_else(|| category_not_found(cs.id))?;
+            sql::cosmetics::update_category_sequence(&mut tx, cs, operator).await?;
+            let after = sql::cosmetics::get_category(&mut tx, cs.id)
+                .await?
+                .ok_or_else(|| category_not_found(cs.id))?;
+            record(
+                &mut tx,
+                operator,
+                AuditAction::UpdateSequence,
+                EntityType::Category,
+                cs.id,
+                snapshot(&before),
+                snapshot(&after),
+            )
+            .await?;
+            changes.push((cs.id, (before, after)));
+        }
+        tx.commit().await?;
+        Ok(changes)
+    }
+
+    async fn delete_category(&self, id: u64, operator: &str) -> Result<bool> {
+        let mut tx = self.pool.begin().await?;
+        let before = sql::cosmetics::get_category(&mut tx, id).await?;
+        if !sql::cosmetics::delete_category(&mut tx, id, operator).await? {
+            return Ok(false);
+        }
+        record(
+            &mut tx,
+            operator,
+            AuditAction::Delete,
+            EntityType::Category,
+            id,
+            before.as_ref().and_then(snapshot),
+            None,
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn get_category_products(
+        &self,
+        path: &str,
+        paging: &Paging,
+        cursor: Option<Cursor>,
+    ) -> Result<Vec<BrandItem>> {
+        sql::cosmetics::get_category_products(&self.pool, path, paging, cursor).await
+    }
+
+    async fn count_category_products(&self, path: &str) -> Result<i64> {
+        sql::cosmetics::count_category_products(&self.pool, path).await
+    }
+
+    async fn get_product_category_ids(&self, product_id: u64) -> Result<Vec<u64>> {
+        sql::cosmetics::get_product_category_ids(&self.pool, product_id).await
+    }
+
+    async fn set_product_categories(
+        &self,
+        product_id: u64,
+        category_ids: &[u64],
+        operator: &str,
+    ) -> Result<Vec<u64>> {
+        let mut tx = self.pool.begin().await?;
+        let before = sql::cosmetics::get_product_category_ids(&mut tx, product_id).await?;
+        sql::cosmetics::set_product_categories(&mut tx, product_id, category_ids, operator).await?;
+        record(
+            &mut tx,
+            operator,
+            AuditAction::UpdateCategories,
+            EntityType::Product,
+            product_id,
+            snapshot(&before),
+            snapshot(&category_ids),
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(before)
+    }
+}
+
+#[async_trait]
+impl AdminUserRepo for MySqlRepo {
+    async fn get_user(&self, username: &str) -> Result<Option<AdminLoginUser>> {
+        sql::admin::get_user(&self.pool, username).await
+    }
+
+    async fn create_user(
+        &self,
+        username: &str,
+        password: &str,
+        role: Role,
+        operator: &str,
+    ) -> Result<u64> {
+        sql::admin::create_user(&self.pool, username, password, role, operator).await
+    }
+
+    async fn update_password(&self, username: &str, password: &str) -> Result<bool> {
+        sql::admin::update_password(&self.pool, username, password).await
+    }
+
+    async fn get_users(&self, paging: &Paging) -> Result<Vec<AdminUserItem>> {
+        sql::admin::get_users(&self.pool, paging).await
+    }
+
+    async fn count_users(&self) -> Result<i64> {
+        sql::admin::count_users(&self.pool).await
+    }
+
+    async fn get_user_by_id(&self, id: u64) -> Result<Option<AdminUserItem>> {
+        sql::admin::get_user_by_id(&self.pool, id).await
+    }
+
+    async fn update_user_role(&self, id: u64, role: Role, operator: &str) -> Result<bool> {
+        sql::admin::update_user_role(&self.pool, id, role, operator).await
+    }
+
+    async fn update_user_status(
+        &self,
+        id: u64,
+        status: UserStatus,
+        operator: &str,
+    ) -> Result<bool> {
+        sql::admin::update_user_status(&self.pool, id, status, operator).await
+    }
+
+    async fn reset_password(&self, id: u64, password: &str, operator: &str) -> Result<bool> {
+        sql::admin::reset_password(&self.pool, id, password, operator).await
+    }
+}
+
+#[async_trait]
+impl AuditLogRepo for MySqlRepo {
+    async fn create_audit_log(&self, log: &NewAuditLog) -> Result<u64> {
+        sql::audit::create_audit_log(&self.pool, log).await
+    }
+
+    async fn get_audit_logs(&self, q: &AuditQuery) -> Result<Vec<AuditLog>> {
+        sql::audit::get_audit_logs(&self.pool, q).await
+    }
+
+    async fn count_audit_logs(&self, q: &AuditQuery) -> Result<i64> {
+        sql::audit::count_audit_logs(&self.pool, q).await
+    }
+}
+
+// create a product in a transaction along with its default sku
+async fn create_product(
+    conn: &mut MySqlConnection,
+    product: &NewProduct,
+    brand_id: u64,
+    operator: &str,
+) -> Result<ProductItem> {
+    let id = sql::cosmetics::create_product(&mut *conn, product, brand_id, operator).await?;
+    let after = sql::cosmetics::get_product(&mut *conn, id)
+        .await?
+        .ok_or_else(|| anyhow!("Create product failed."))?;
+    sync_default_sku(&mut *conn, None, &after, operator).await?;
+    record(
+        conn,
+        operator,
+        AuditAction::Create,
+        EntityType::Product,
+        id,
+        None,
+        snapshot(&after),
+    )
+    .await?;
+    Ok(after)
+}
+
+// update a product in a transaction, comparing the prices with the row read in
+// the same transaction
+async fn update_product(
+    conn: &mut MySqlConnection,
+    id: u64,
+    product: NewProduct,
+    operator: &str,
+) -> Result<Option<ProductItem>> {
+    let before = sql::cosmetics::get_product(&mut *conn, id).await?;
+    if !sql::cosmetics::update_product(&mut *conn, id, product, operator).await? {
+        return Ok(None);
+    }
+    let after = sql::cosmetics::get_product(&mut *conn, id).await?;
+    if let Some(after) = &after {
+        sync_default_sku(&mut *conn, before.as_ref(), after, operator).await?;
+    }
+    record(
+        conn,
+        operator,
+        AuditAction::Update,
+        EntityType::Product,
+        id,
+        before.as_ref().and_then(snapshot),
+        after.as_ref().and_then(snapshot),
+    )
+    .await?;
+    Ok(after)
+}
+
+// keep the default sku in step with the product, and record a price history
+// row for it whenever the prices are set
+async fn sync_default_sku(
+    conn: &mut MySqlConnection,
+    before: Option<&ProductItem>,
+    after: &ProductItem,
+    operator: &str,
+) -> Result<()> {
+    let price = ProductPrice {
+        sell_price: after.sell_price,
+        import_price: after.import_price,
+    };
+    let sku_id = sql::cosmetics::sync_default_sku(
+        &mut *conn,
+        after.id,
+        &after.spec,
+        after.kind,
+        &price,
+        operator,
+    )
+    .await?;
+    if let Some(b) = before {
+        if b.sell_price == after.sell_price && b.import_price == after.import_price {
+            return Ok(());
+        }
+    }
+    sql::cosmetics::create_price_history(&mut *conn, after.id, sku_id, &price, operator).await?;
+    Ok(())
+}
+
+async fn hot_list_item(conn: &mut MySqlConnection, id: u64) -> Result<Option<HotListItem>> {
+    let list = match sql::cosmetics::get_hot_list(&mut *conn, id).await? {
+        Some(l) => l,
+        None => return Ok(None),
+    };
+    let product_ids = sql::cosmetics::get_hot_list_product_ids(&mut *conn, id).await?;
+    Ok(Some(HotListItem { list, product_ids }))
+}
+
+// record a change in the audit log within the transaction writing it
+async fn record(
+    conn: &mut MySqlConnection,
+    operator: &str,
+    action: AuditAction,
+    entity_type: EntityType,
+    entity_id: u64,
+    before: Option<Value>,
+    after: Option<Value>,
+) -> Result<()> {
+    let log = NewAuditLog::new(operator, action, entity_type, entity_id, before, after);
+    sql::audit::create_audit_log(conn, &log).await?;
+    Ok(())
+}
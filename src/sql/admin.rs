@@ -2,17 +2,19 @@ use anyhow::Result;
 use sqlx::mysql::MySqlPool;
 use sqlx::{query_as_unchecked, query_unchecked, Done};
 
-use crate::models::admin::AdminLoginUser;
+use crate::models::admin::{AdminLoginUser, AdminUserItem, Role, UserStatus};
+use crate::models::{Paging, MAX_ROWS};
 
 pub async fn get_user(db: &MySqlPool, username: &str) -> Result<Option<AdminLoginUser>> {
     query_as_unchecked!(
         AdminLoginUser,
         r#"
-SELECT `id`, `username`, `password`
+SELECT `id`, `username`, `password`, `role`, `must_change_password`
 FROM admin_user
-WHERE username = ?
+WHERE username = ? AND status = ?
 "#,
-        username
+        username,
+        UserStatus::Active as i8,
     )
     .fetch_optional(db)
     .await
@@ -23,15 +25,17 @@ pub async fn create_user(
     db: &MySqlPool,
     username: &str,
     password: &str,
+    role: Role,
     operator: &str,
 ) -> Result<u64> {
     let id = query_unchecked!(
         r#"
-INSERT INTO admin_user (`username`, `password`, `creator`)
-VALUES (?, ?, ?)
+INSERT INTO admin_user (`username`, `password`, `role`, `creator`)
+VALUES (?, ?, ?, ?)
 "#,
         username,
         password,
+        role,
         operator
     )
     .execute(db)
@@ -43,8 +47,8 @@ VALUES (?, ?, ?)
 pub async fn update_password(db: &MySqlPool, username: &str, password: &str) -> Result<bool> {
     let row = query_unchecked!(
         r#"
-UPDATE admin_user SET `password` = ?
-WHERE `username` = ?  
+UPDATE admin_user SET `password` = ?, `must_change_password` = 0
+WHERE `username` = ?
 "#,
         password,
         username
@@ -55,3 +59,144 @@ WHERE `username` = ?
 
     Ok(row > 0)
 }
+
+pub async fn get_users(db: &MySqlPool, paging: &Paging) -> Result<Vec<AdminUserItem>> {
+    query_as_unchecked!(
+        AdminUserItem,
+        r#"
+SELECT `id`, `username`, `role`, `status`, `must_change_password`, `creator`,
+`modifier`, `created_at`, `updated_at`
+FROM admin_user
+WHERE status != ?
+ORDER BY id
+LIMIT ?, ?
+"#,
+        UserStatus::Deleted as i8,
+        paging.offset.unwrap_or(0),
+        paging.limit_or(MAX_ROWS),
+    )
+    .fetch_all(db)
+    .await
+    .map_err(|e| e.into())
+}
+
+pub async fn count_users(db: &MySqlPool) -> Result<i64> {
+    let record = query_unchecked!(
+        r#"SELECT COUNT(*) AS `count` FROM admin_user WHERE status != ?"#,
+        UserStatus::Deleted as i8,
+    )
+    .fetch_one(db)
+    .await?;
+
+    Ok(record.count)
+}
+
+pub async fn get_user_by_id(db: &MySqlPool, id: u64) -> Result<Option<AdminUserItem>> {
+    query_as_unchecked!(
+        AdminUserItem,
+        r#"
+SELECT `id`, `username`, `role`, `status`, `must_change_password`, `creator`,
+`modifier`, `created_at`, `updated_at`
+FROM admin_user
+WHERE id = ? AND status != ?
+"#,
+        id,
+        UserStatus::Deleted as i8,
+    )
+    .fetch_optional(db)
+    .await
+    .map_err(|e| e.into())
+}
+
+pub async fn get_user_by_name(db: &MySqlPool, username: &str) -> Result<Option<AdminUserItem>> {
+    query_as_unchecked!(
+        AdminUserItem,
+        r#"
+SELECT `id`, `username`, `role`, `status`, `must_change_password`, `creator`,
+`modifier`, `created_at`, `updated_at`
+FROM admin_user
+WHERE username = ? AND status != ?
+"#,
+        username,
+        UserStatus::Deleted as i8,
+    )
+    .fetch_optional(db)
+    .await
+    .map_err(|e| e.into())
+}
+
+pub async fn update_user_role(db: &MySqlPool, id: u64, role: Role, operator: &str) -> Result<bool> {
+    let row = query_unchecked!(
+        r#"UPDATE admin_user SET `role` = ?, modifier = ? WHERE id = ? AND status != ?"#,
+        role,
+        operator,
+        id,
+        UserStatus::Deleted as i8,
+    )
+    .execute(db)
+    .await?
+    .rows_affected();
+
+    Ok(row > 0)
+}
+
+pub async fn update_user_status(
+    db: &MySqlPool,
+    id: u64,
+    status: UserStatus,
+    operator: &str,
+) -> Result<bool> {
+    // the name of a deleted user is freed up for reuse, `#` never shows up in
+    // a valid one
+    let row = if status == UserStatus::Deleted {
+        query_unchecked!(
+            r#"
+UPDATE admin_user SET status = ?, modifier = ?, username = CONCAT(username, '#', id)
+WHERE id = ? AND status != ?
+"#,
+            status,
+            operator,
+            id,
+            UserStatus::Deleted as i8,
+        )
+        .execute(db)
+        .await?
+        .rows_affected()
+    } else {
+        query_unchecked!(
+            r#"UPDATE admin_user SET status = ?, modifier = ? WHERE id = ? AND status != ?"#,
+            status,
+            operator,
+            id,
+            UserStatus::Deleted as i8,
+        )
+        .execute(db)
+        .await?
+        .rows_affected()
+    };
+
+    Ok(row > 0)
+}
+
+pub async fn reset_password(
+    db: &MySqlPool,
+    id: u64,
+    password: &str,
+    operator: &str,
+) -> Result<bool> {
+    let row = query_unchecked!(
+        r#"
+UPDATE admin_user SET `password` = ?, `must_change_password` = 1, modifier = ?
+WHERE id = ? AND status != ?
+"#,
+        password,
+        operator,
+        id,
+        UserStatus::Deleted as i8,
+    )
+    .execute(db)
+    .await?
+    .rows_affected();
+
+    Ok(row > 0)
+}
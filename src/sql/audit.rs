@@ -0,0 +1,80 @@
+use anyhow::Result;
+use sqlx::mysql::{MySql, MySqlPool};
+use sqlx::{query_as_unchecked, query_unchecked, Done, Executor};
+
+use crate::models::audit::{AuditLog, AuditQuery, NewAuditLog};
+use crate::models::{MAX_ROWS, MIN_ROWS};
+
+pub async fn create_audit_log<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    log: &NewAuditLog,
+) -> Result<u64> {
+    let id = query_unchecked!(
+        r#"
+INSERT INTO audit_log (`operator`, `action`, `entity_type`, `entity_id`, `before_data`,
+`after_data`, `diff`)
+VALUES (?, ?, ?, ?, ?, ?, ?)
+"#,
+        log.operator,
+        log.action.as_str(),
+        log.entity_type.as_str(),
+        log.entity_id,
+        log.before_data,
+        log.after_data,
+        log.diff,
+    )
+    .execute(db)
+    .await?
+    .last_insert_id();
+
+    Ok(id)
+}
+
+pub async fn get_audit_logs(db: &MySqlPool, q: &AuditQuery) -> Result<Vec<AuditLog>> {
+    query_as_unchecked!(
+        AuditLog,
+        r#"
+SELECT `id`, `operator`, `action`, `entity_type`, `entity_id`, `before_data`,
+`after_data`, `diff`, `created_at`
+FROM audit_log
+WHERE (? IS NULL OR entity_type = ?)
+AND (? IS NULL OR entity_id = ?)
+AND (? IS NULL OR operator = ?)
+ORDER BY id DESC
+LIMIT ?, ?
+"#,
+        q.entity_type,
+        q.entity_type,
+        q.entity_id,
+        q.entity_id,
+        q.operator,
+        q.operator,
+        q.offset.unwrap_or(0),
+        q.limit.unwrap_or(MIN_ROWS).min(MAX_ROWS),
+    )
+    .fetch_all(db)
+    .await
+    .map_err(|e| e.into())
+}
+
+pub async fn count_audit_logs(db: &MySqlPool, q: &AuditQuery) -> Result<i64> {
+    let record = query_unchecked!(
+        r#"
+SELECT COUNT(*) AS `count`
+FROM audit_log
+WHERE (? IS NULL OR entity_type = ?)
+AND (? IS NULL OR entity_id = ?)
+AND (? IS NULL OR operator = ?)
+"#,
+        q.entity_type,
+        q.entity_type,
+        q.entity_id,
+        q.entity_id,
+        q.operator,
+        q.operator,
+    )
+    .fetch_one(db)
+    .await?;
+
+    Ok(record.count)
+}
@@ -0,0 +1,120 @@
+use sqlx::mysql::{MySql, MySqlArguments};
+use sqlx::query::Query;
+use sqlx::{query_with, Arguments, Encode, Type};
+
+/// Builds a statement whose size is only known at runtime, e.g. an `IN` list
+/// or a batch insert. Values only ever go in as bound parameters, the sql
+/// text is made of the fragments pushed by the caller and `?` placeholders.
+#[derive(Default)]
+pub struct QueryBuilder {
+    sql: String,
+    args: MySqlArguments,
+    binds: usize,
+}
+
+impl QueryBuilder {
+    pub fn new(sql: &str) -> Self {
+        Self {
+            sql: sql.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    /// Append raw sql, never pass anything that came from a request.
+    pub fn push(&mut self, sql: &str) -> &mut Self {
+        self.sql.push_str(sql);
+        self
+    }
+
+    /// Append a `?` bound to `value`.
+    pub fn push_bind<'q, T>(&mut self, value: T) -> &mut Self
+    where
+        T: 'q + Send + Encode<'q, MySql> + Type<MySql>,
+    {
+        self.sql.push('?');
+        self.args.add(value);
+        self.binds += 1;
+        self
+    }
+
+    /// Append `(?, ?, ?)` bound to `values`. An empty list becomes `(NULL)`,
+    /// so that `x IN (NULL)` matches nothing instead of being a syntax error.
+    pub fn push_in<'q, T, I>(&mut self, values: I) -> &mut Self
+    where
+        I: IntoIterator<Item = T>,
+        T: 'q + Send + Encode<'q, MySql> + Type<MySql>,
+    {
+        self.sql.push('(');
+        let mut empty = true;
+        for value in values {
+            if !empty {
+                self.sql.push_str(", ");
+            }
+            self.push_bind(value);
+            empty = false;
+        }
+        if empty {
+            self.sql.push_str("NULL");
+        }
+        self.sql.push(')');
+        self
+    }
+
+    /// Append the rows of a `VALUES` clause, `(?, ?), (?, ?)`, `f` binds the
+    /// columns of one row.
+    pub fn push_values<I, F>(&mut self, rows: I, mut f: F) -> &mut Self
+    where
+        I: IntoIterator,
+        F: FnMut(&mut ValuesRow<'_>, I::Item),
+    {
+        for (i, item) in rows.into_iter().enumerate() {
+            if i > 0 {
+                self.sql.push_str(", ");
+            }
+            self.sql.push('(');
+            f(
+                &mut ValuesRow {
+                    builder: self,
+                    first: true,
+                },
+                item,
+            );
+            self.sql.push(')');
+        }
+        self
+    }
+
+    pub fn sql(&self) -> &str {
+        &self.sql
+    }
+
+    /// Number of bound parameters so far.
+    pub fn binds(&self) -> usize {
+        self.binds
+    }
+
+    /// The statement with its arguments, the builder is left without them.
+    pub fn build(&mut self) -> Query<'_, MySql, MySqlArguments> {
+        query_with(&self.sql, std::mem::take(&mut self.args))
+    }
+}
+
+/// One row of `QueryBuilder::push_values`.
+pub struct ValuesRow<'a> {
+    builder: &'a mut QueryBuilder,
+    first: bool,
+}
+
+impl ValuesRow<'_> {
+    pub fn bind<'q, T>(&mut self, value: T) -> &mut Self
+    where
+        T: 'q + Send + Encode<'q, MySql> + Type<MySql>,
+    {
+        if !self.first {
+            self.builder.sql.push_str(", ");
+        }
+        self.builder.push_bind(value);
+        self.first = false;
+        self
+    }
+}
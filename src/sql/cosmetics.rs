@@ -1,14 +1,27 @@
+//! Queries of the catalog. The `get_max_*_sequence` functions read with
+//! `FOR UPDATE`, run in a transaction they hold a concurrent writer until the
+//! sequence taken is committed.
+
 use crate::models::cosmetics::{
-    Brand, BrandItem, BrandSequence, HotProduct, NewBrand, NewProduct, ProductItem,
+    Brand, BrandItem, BrandSequence, Category, CategorySequence, HotList, HotProduct,
+    ImageSequence, NewBrand, NewCategory, NewHotList, NewProduct, NewSku, PriceHistoryQuery,
+    PriceRecord, ProductCard, ProductFilter, ProductImage, ProductItem, ProductPrice,
+    ProductStatus, SearchItem, Sku, SkuStatus, TrashItem, UpdateBrand,
 };
-use crate::models::{CommonStatus, Paging, MAX_ROWS, MIN_ROWS};
+use crate::models::{CommonStatus, Cursor, Paging, MAX_ROWS, MIN_ROWS};
+use crate::sql::builder::QueryBuilder;
 use anyhow::{anyhow, Result};
-use sqlx::mysql::MySqlPool;
-use sqlx::{query, query_as, query_as_unchecked, query_unchecked, Done, Row};
+use sqlx::mysql::{MySql, MySqlConnection};
+use sqlx::{query, query_as, query_as_unchecked, query_unchecked, Done, Executor, Row};
+use std::collections::HashSet;
 
 // brands
 
-pub async fn create_brand(db: &MySqlPool, brand: Brand, operator: &str) -> Result<u64> {
+pub async fn create_brand<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    brand: Brand,
+    operator: &str,
+) -> Result<u64> {
     let id = query_unchecked!(
         r#"
 INSERT INTO brand (`name`, `sequence`, `creator`)
@@ -25,17 +38,15 @@ VALUES ( ?, ?, ?)
     Ok(id)
 }
 
-pub async fn is_brand_names_valid(db: &MySqlPool, brands: &Vec<NewBrand>) -> Result<bool> {
-    let names: Vec<String> = brands
-        .iter()
-        .map(|b| r#"""#.to_string() + &b.name.clone() + r#"""#)
-        .collect();
-    let s = format!(
-        "SELECT DISTINCT name FROM brand WHERE name IN ({}) AND status = {}",
-        names.join(", "),
-        CommonStatus::Valid
-    );
-    let rows = query(s.as_str()).fetch_all(db).await?;
+pub async fn is_brand_names_valid<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    brands: &Vec<NewBrand>,
+) -> Result<bool> {
+    let mut qb = QueryBuilder::new("SELECT DISTINCT name FROM brand WHERE name IN ");
+    qb.push_in(brands.iter().map(|b| b.name.as_str()))
+        .push(" AND status = ")
+        .push_bind(CommonStatus::Valid as i8);
+    let rows = qb.build().fetch_all(db).await?;
     if rows.len() > 0 {
         let get_names: Vec<String> = rows.iter().map(|row| row.get(0)).collect();
         return Err(anyhow!(
@@ -46,9 +57,9 @@ pub async fn is_brand_names_valid(db: &MySqlPool, brands: &Vec<NewBrand>) -> Res
     Ok(true)
 }
 
-pub async fn get_max_brand_sequence(db: &MySqlPool) -> Result<i32> {
+pub async fn get_max_brand_sequence<'c, E: Executor<'c, Database = MySql>>(db: E) -> Result<i32> {
     let record = query_unchecked!(
-        r#"SELECT COALESCE(MAX(`sequence`), 0) AS `max_id` FROM brand WHERE status = ?"#,
+        r#"SELECT COALESCE(MAX(`sequence`), 0) AS `max_id` FROM brand WHERE status = ? FOR UPDATE"#,
         CommonStatus::Valid as i8
     )
     .fetch_one(db)
@@ -57,43 +68,74 @@ pub async fn get_max_brand_sequence(db: &MySqlPool) -> Result<i32> {
     Ok(record.max_id as i32)
 }
 
-pub async fn create_brands(db: &MySqlPool, brands: Vec<Brand>, operator: &str) -> Result<bool> {
+pub async fn create_brands<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    brands: Vec<Brand>,
+    operator: &str,
+) -> Result<bool> {
     let mut brands = brands.clone();
     brands.sort_by(|a, b| a.sequence.cmp(&b.sequence));
-    let mut s = brands.iter().fold(
-        "INSERT INTO brand (`name`, `sequence`, `creator`) VALUES ".to_string(),
-        |s, brand| {
-            format!(
-                "{} ('{}', {}, '{}'),",
-                s, brand.name, brand.sequence, operator
-            )
-        },
-    );
-    s.pop();
-    let id = query(s.as_str()).execute(db).await?.last_insert_id();
+    let mut qb = QueryBuilder::new("INSERT INTO brand (`name`, `sequence`, `creator`) VALUES ");
+    qb.push_values(brands.iter(), |row, brand| {
+        row.bind(brand.name.as_str())
+            .bind(brand.sequence)
+            .bind(operator);
+    });
+    let id = qb.build().execute(db).await?.last_insert_id();
 
     Ok(id > 0)
 }
 
-pub async fn get_brands(db: &MySqlPool, paging: Paging) -> Result<Vec<Brand>> {
+pub async fn get_brands<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    paging: &Paging,
+    cursor: Option<Cursor>,
+) -> Result<Vec<Brand>> {
+    let (sequence, id) = match cursor {
+        Some(c) => (Some(c.sequence), Some(c.id)),
+        None => (None, None),
+    };
     query_as_unchecked!(
         Brand,
         r#"
-SELECT id, `name`, `sequence`, false AS is_hot
+SELECT id, `name`, `en_name`, `logo_url`, `country`, `description`, `website`,
+`sequence`, EXISTS (
+  SELECT 1 FROM hot_brand h WHERE h.brand_id = brand.id AND h.status = ?
+) AS is_hot
 FROM brand
 WHERE status = ?
+AND (? IS NULL OR `sequence` > ? OR (`sequence` = ? AND id > ?))
 ORDER BY `sequence`, id
 LIMIT ?, ?"#,
         CommonStatus::Valid as i8,
-        paging.offset.unwrap_or(0),
-        paging.limit.unwrap_or(MAX_ROWS).min(MAX_ROWS),
+        CommonStatus::Valid as i8,
+        sequence,
+        sequence,
+        sequence,
+        id,
+        paging.offset(),
+        paging.limit_or(MAX_ROWS),
     )
     .fetch_all(db)
     .await
     .map_err(|e| e.into())
 }
 
-pub async fn get_brand_id(db: &MySqlPool, brand_name: &str) -> Result<u64> {
+pub async fn count_brands<'c, E: Executor<'c, Database = MySql>>(db: E) -> Result<i64> {
+    let record = query_unchecked!(
+        r#"SELECT COUNT(*) AS `count` FROM brand WHERE status = ?"#,
+        CommonStatus::Valid as i8,
+    )
+    .fetch_one(db)
+    .await?;
+
+    Ok(record.count)
+}
+
+pub async fn get_brand_id<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    brand_name: &str,
+) -> Result<u64> {
     let record = query_unchecked!(
         r#"SELECT id FROM brand WHERE name = ? AND status = ?"#,
         brand_name,
@@ -108,28 +150,67 @@ pub async fn get_brand_id(db: &MySqlPool, brand_name: &str) -> Result<u64> {
     }
 }
 
-pub async fn get_all_brands(db: &MySqlPool) -> Result<Vec<Brand>> {
+pub async fn get_all_brands<'c, E: Executor<'c, Database = MySql>>(db: E) -> Result<Vec<Brand>> {
     query_as_unchecked!(
         Brand,
         r#"
-SELECT id, `name`, `sequence`, false AS is_hot
+SELECT id, `name`, `en_name`, `logo_url`, `country`, `description`, `website`,
+`sequence`, EXISTS (
+  SELECT 1 FROM hot_brand h WHERE h.brand_id = brand.id AND h.status = ?
+) AS is_hot
 FROM brand
 WHERE status = ?
 ORDER BY `sequence`, id"#,
         CommonStatus::Valid as i8,
+        CommonStatus::Valid as i8,
     )
     .fetch_all(db)
     .await
     .map_err(|e| e.into())
 }
 
-pub async fn update_brand(db: &MySqlPool, brand: Brand, operator: &str) -> Result<bool> {
+pub async fn get_brand<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    id: u64,
+) -> Result<Option<Brand>> {
+    query_as_unchecked!(
+        Brand,
+        r#"
+SELECT id, `name`, `en_name`, `logo_url`, `country`, `description`, `website`,
+`sequence`, EXISTS (
+  SELECT 1 FROM hot_brand h WHERE h.brand_id = brand.id AND h.status = ?
+) AS is_hot
+FROM brand
+WHERE id = ?"#,
+        CommonStatus::Valid as i8,
+        id,
+    )
+    .fetch_optional(db)
+    .await
+    .map_err(|e| e.into())
+}
+
+pub async fn update_brand<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    id: u64,
+    brand: &UpdateBrand,
+    operator: &str,
+) -> Result<bool> {
     let row = query_unchecked!(
-        r#"UPDATE brand SET `name`= ?, `sequence` = ?, modifier = ? WHERE id = ?"#,
+        r#"
+UPDATE brand SET `name`= ?, `en_name` = ?, `logo_url` = ?, `country` = ?,
+`description` = ?, `website` = ?, modifier = ?
+WHERE id = ? AND status = ?
+"#,
         brand.name,
-        brand.sequence,
+        brand.en_name,
+        brand.logo_url,
+        brand.country,
+        brand.description,
+        brand.website,
         operator,
-        brand.id,
+        id,
+        CommonStatus::Valid as i8,
     )
     .execute(db)
     .await?
@@ -138,11 +219,19 @@ pub async fn update_brand(db: &MySqlPool, brand: Brand, operator: &str) -> Resul
     Ok(row > 0)
 }
 
-pub async fn delete_brand(db: &MySqlPool, id: u32, operator: &str) -> Result<bool> {
+pub async fn delete_brand<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    id: u32,
+    operator: &str,
+) -> Result<bool> {
     let row = query_unchecked!(
-        r#"UPDATE brand SET status = ?, modifier = ? WHERE id = ?"#,
+        r#"
+UPDATE brand SET status = ?, modifier = ?, deleted_by = ?, deleted_at = CURRENT_TIMESTAMP
+WHERE id = ?
+"#,
         CommonStatus::Invalid as i8,
         operator,
+        operator,
         id,
     )
     .execute(db)
@@ -152,21 +241,87 @@ pub async fn delete_brand(db: &MySqlPool, id: u32, operator: &str) -> Result<boo
     Ok(row > 0)
 }
 
-pub async fn is_brand_ids_valid(db: &MySqlPool, ids: Vec<String>) -> Result<bool> {
-    let s = format!(
-        "SELECT id FROM brand WHERE id IN ({}) AND status = {}",
-        ids.join(", "),
-        CommonStatus::Valid,
-    );
-    let get_ids = query(s.as_str()).fetch_all(db).await?;
-    if get_ids.len() != ids.len() {
+pub async fn restore_brand<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    id: u64,
+    operator: &str,
+) -> Result<bool> {
+    let row = query_unchecked!(
+        r#"
+UPDATE brand SET status = ?, modifier = ?, deleted_by = '', deleted_at = NULL
+WHERE id = ? AND status = ?
+"#,
+        CommonStatus::Valid as i8,
+        operator,
+        id,
+        CommonStatus::Invalid as i8,
+    )
+    .execute(db)
+    .await?
+    .rows_affected();
+
+    Ok(row > 0)
+}
+
+/// Products of a brand in any status, deleted ones still point at it.
+pub async fn count_all_brand_products<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    id: u64,
+) -> Result<i64> {
+    let record = query_unchecked!(
+        r#"SELECT COUNT(*) AS `count` FROM product WHERE brand_id = ?"#,
+        id,
+    )
+    .fetch_one(db)
+    .await?;
+
+    Ok(record.count)
+}
+
+/// Remove a deleted brand for good along with its old hot brand rows. Nothing
+/// is removed when a product has been added to the brand in the meantime.
+pub async fn purge_brand(conn: &mut MySqlConnection, id: u64) -> Result<bool> {
+    let row = query_unchecked!(
+        r#"
+DELETE FROM brand
+WHERE id = ? AND status = ?
+AND NOT EXISTS (SELECT 1 FROM product WHERE brand_id = ?)
+"#,
+        id,
+        CommonStatus::Invalid as i8,
+        id,
+    )
+    .execute(&mut *conn)
+    .await?
+    .rows_affected();
+    if row == 0 {
+        return Ok(false);
+    }
+    query_unchecked!(r#"DELETE FROM hot_brand WHERE brand_id = ?"#, id)
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(true)
+}
+
+pub async fn is_brand_ids_valid<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    ids: &[u64],
+) -> Result<bool> {
+    let distinct: HashSet<u64> = ids.iter().copied().collect();
+    let mut qb = QueryBuilder::new("SELECT id FROM brand WHERE id IN ");
+    qb.push_in(distinct.iter().copied())
+        .push(" AND status = ")
+        .push_bind(CommonStatus::Valid as i8);
+    let get_ids = qb.build().fetch_all(db).await?;
+    if get_ids.len() != distinct.len() {
         return Err(anyhow!("Brand ids not match: {:?}", ids));
     }
     Ok(true)
 }
 
-pub async fn update_brand_sequence(
-    db: &MySqlPool,
+pub async fn update_brand_sequence<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
     brand_sequence: &BrandSequence,
     operator: &str,
 ) -> Result<bool> {
@@ -183,30 +338,53 @@ pub async fn update_brand_sequence(
     Ok(row > 0)
 }
 
-pub async fn get_brand_detail(db: &MySqlPool, id: u32, paging: Paging) -> Result<Vec<BrandItem>> {
+pub async fn get_brand_detail<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    id: u32,
+    paging: &Paging,
+    cursor: Option<Cursor>,
+) -> Result<Vec<BrandItem>> {
+    let after_id = cursor.map(|c| c.id);
     query_as!(
         BrandItem,
         r#"
 SELECT `id`, `name`, `title`, `subtitle`, `img_url`
 FROM product
-WHERE brand_id = ? AND status = ?
+WHERE brand_id = ? AND status = ? AND (? IS NULL OR id > ?)
 ORDER BY id
 LIMIT ?, ?
 "#,
         id,
-        CommonStatus::Valid as i8,
-        paging.offset.unwrap_or(0),
-        paging.limit.unwrap_or(MIN_ROWS).min(MAX_ROWS),
+        ProductStatus::OnShelf as i8,
+        after_id,
+        after_id,
+        paging.offset(),
+        paging.limit_or(MIN_ROWS),
     )
     .fetch_all(db)
     .await
     .map_err(|e| e.into())
 }
 
+pub async fn count_brand_products<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    id: u32,
+) -> Result<i64> {
+    let record = query_unchecked!(
+        r#"SELECT COUNT(*) AS `count` FROM product WHERE brand_id = ? AND status = ?"#,
+        id,
+        ProductStatus::OnShelf,
+    )
+    .fetch_one(db)
+    .await?;
+
+    Ok(record.count)
+}
+
 // product
 
-pub async fn create_product(
-    db: &MySqlPool,
+pub async fn create_product<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
     product: &NewProduct,
     brand_id: u64,
     operator: &str,
@@ -242,7 +420,10 @@ VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
     Ok(id)
 }
 
-pub async fn get_valid_product(db: &MySqlPool, id: u64) -> Result<Option<ProductItem>> {
+pub async fn get_valid_product<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    id: u64,
+) -> Result<Option<ProductItem>> {
     query_as_unchecked!(
         ProductItem,
         r#"
@@ -252,9 +433,10 @@ p.img_url, p.status, p.comment
 FROM product p
 JOIN brand b
 ON p.brand_id = b.id
-WHERE p.id = ? AND p.status = ?
+WHERE p.id = ? AND p.status = ? AND b.status = ?
 "#,
         id,
+        ProductStatus::OnShelf,
         CommonStatus::Valid as i8,
     )
     .fetch_optional(db)
@@ -262,7 +444,10 @@ WHERE p.id = ? AND p.status = ?
     .map_err(|e| e.into())
 }
 
-pub async fn get_product(db: &MySqlPool, id: u64) -> Result<Option<ProductItem>> {
+pub async fn get_product<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    id: u64,
+) -> Result<Option<ProductItem>> {
     query_as_unchecked!(
         ProductItem,
         r#"
@@ -281,7 +466,13 @@ WHERE p.id = ?
     .map_err(|e| e.into())
 }
 
-pub async fn get_all_products(db: &MySqlPool, paging: Paging) -> Result<Vec<ProductItem>> {
+pub async fn get_all_products<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    filter: &ProductFilter,
+    offset: u32,
+    limit: u32,
+) -> Result<Vec<ProductItem>> {
+    let sort = filter.sort.as_str();
     query_as_unchecked!(
         ProductItem,
         r#"
@@ -291,19 +482,175 @@ p.img_url, p.status, p.comment
 FROM product p
 JOIN brand b
 ON p.brand_id = b.id
-ORDER BY p.id
+WHERE (? IS NULL OR p.brand_id = ?)
+AND ((? IS NULL AND p.status != ?) OR p.status = ?)
+AND (? IS NULL OR p.kind = ?)
+AND (? IS NULL OR p.sell_price >= ?)
+AND (? IS NULL OR p.sell_price <= ?)
+AND (? IS NULL OR p.creator = ?)
+AND (? IS NULL OR p.created_at >= ?)
+AND (? IS NULL OR p.created_at < DATE_ADD(?, INTERVAL 1 DAY))
+AND (? IS NULL OR p.updated_at >= ?)
+AND (? IS NULL OR p.updated_at < DATE_ADD(?, INTERVAL 1 DAY))
+ORDER BY
+CASE WHEN ? = 'sell_price' THEN p.sell_price END ASC,
+CASE WHEN ? = '-sell_price' THEN p.sell_price END DESC,
+CASE WHEN ? = 'sequence' THEN p.sequence END ASC,
+CASE WHEN ? = '-sequence' THEN p.sequence END DESC,
+CASE WHEN ? = 'updated_at' THEN p.updated_at END ASC,
+CASE WHEN ? = '-updated_at' THEN p.updated_at END DESC,
+CASE WHEN ? = '-id' THEN p.id END DESC,
+p.id
+LIMIT ?, ?
+"#,
+        filter.brand_id,
+        filter.brand_id,
+        filter.status,
+        ProductStatus::Deleted,
+        filter.status,
+        filter.kind,
+        filter.kind,
+        filter.min_price,
+        filter.min_price,
+        filter.max_price,
+        filter.max_price,
+        filter.creator,
+        filter.creator,
+        filter.created_from,
+        filter.created_from,
+        filter.created_to,
+        filter.created_to,
+        filter.updated_from,
+        filter.updated_from,
+        filter.updated_to,
+        filter.updated_to,
+        sort,
+        sort,
+        sort,
+        sort,
+        sort,
+        sort,
+        sort,
+        offset,
+        limit,
+    )
+    .fetch_all(db)
+    .await
+    .map_err(|e| e.into())
+}
+
+pub async fn count_products<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    filter: &ProductFilter,
+) -> Result<i64> {
+    let record = query_unchecked!(
+        r#"
+SELECT COUNT(*) AS `count`
+FROM product p
+JOIN brand b
+ON p.brand_id = b.id
+WHERE (? IS NULL OR p.brand_id = ?)
+AND ((? IS NULL AND p.status != ?) OR p.status = ?)
+AND (? IS NULL OR p.kind = ?)
+AND (? IS NULL OR p.sell_price >= ?)
+AND (? IS NULL OR p.sell_price <= ?)
+AND (? IS NULL OR p.creator = ?)
+AND (? IS NULL OR p.created_at >= ?)
+AND (? IS NULL OR p.created_at < DATE_ADD(?, INTERVAL 1 DAY))
+AND (? IS NULL OR p.updated_at >= ?)
+AND (? IS NULL OR p.updated_at < DATE_ADD(?, INTERVAL 1 DAY))
+"#,
+        filter.brand_id,
+        filter.brand_id,
+        filter.status,
+        ProductStatus::Deleted,
+        filter.status,
+        filter.kind,
+        filter.kind,
+        filter.min_price,
+        filter.min_price,
+        filter.max_price,
+        filter.max_price,
+        filter.creator,
+        filter.creator,
+        filter.created_from,
+        filter.created_from,
+        filter.created_to,
+        filter.created_to,
+        filter.updated_from,
+        filter.updated_from,
+        filter.updated_to,
+        filter.updated_to,
+    )
+    .fetch_one(db)
+    .await?;
+
+    Ok(record.count)
+}
+
+/// Full-text search over product names and titles plus the brand name, the
+/// ngram parser of the indexes makes this work for Chinese text.
+pub async fn search_products<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    q: &str,
+    paging: &Paging,
+) -> Result<Vec<SearchItem>> {
+    query_as_unchecked!(
+        SearchItem,
+        r#"
+SELECT p.id, p.name, p.title, p.subtitle, p.img_url, p.brand_id, b.name AS brand_name,
+MATCH (p.name, p.alias, p.title, p.subtitle) AGAINST (? IN NATURAL LANGUAGE MODE)
++ MATCH (b.name) AGAINST (? IN NATURAL LANGUAGE MODE) AS score
+FROM product p
+JOIN brand b
+ON p.brand_id = b.id
+WHERE p.status = ? AND b.status = ?
+AND (MATCH (p.name, p.alias, p.title, p.subtitle) AGAINST (? IN NATURAL LANGUAGE MODE)
+OR MATCH (b.name) AGAINST (? IN NATURAL LANGUAGE MODE))
+ORDER BY score DESC, p.sequence, p.id
 LIMIT ?, ?
 "#,
+        q,
+        q,
+        ProductStatus::OnShelf,
+        CommonStatus::Valid as i8,
+        q,
+        q,
         paging.offset.unwrap_or(0),
-        paging.limit.unwrap_or(MAX_ROWS).min(MAX_ROWS),
+        paging.limit_or(MIN_ROWS),
     )
     .fetch_all(db)
     .await
     .map_err(|e| e.into())
 }
 
-pub async fn update_product(
-    db: &MySqlPool,
+pub async fn count_search_products<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    q: &str,
+) -> Result<i64> {
+    let record = query_unchecked!(
+        r#"
+SELECT COUNT(*) AS `count`
+FROM product p
+JOIN brand b
+ON p.brand_id = b.id
+WHERE p.status = ? AND b.status = ?
+AND (MATCH (p.name, p.alias, p.title, p.subtitle) AGAINST (? IN NATURAL LANGUAGE MODE)
+OR MATCH (b.name) AGAINST (? IN NATURAL LANGUAGE MODE))
+"#,
+        ProductStatus::OnShelf,
+        CommonStatus::Valid as i8,
+        q,
+        q,
+    )
+    .fetch_one(db)
+    .await?;
+
+    Ok(record.count)
+}
+
+pub async fn update_product<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
     id: u64,
     product: NewProduct,
     operator: &str,
@@ -312,8 +659,7 @@ pub async fn update_product(
         r#"
 UPDATE product SET `name` = ?, `alias` = ?, `title` = ?, `subtitle` = ?,
 `brand_id` = ?, `spec` = ?, `kind` = ?, `sell_price` = ?, `import_price` = ?,
-`sequence` = ?, `jd_id` = ?, `jd_url` = ?, `img_url` = ?, `status` = ?,
-`comment` = ?, modifier = ?
+`sequence` = ?, `jd_id` = ?, `jd_url` = ?, `status` = ?, `comment` = ?, modifier = ?
 WHERE id = ?
 "#,
         product.name,
@@ -328,7 +674,6 @@ WHERE id = ?
         product.sequence,
         product.jd_id,
         product.jd_url,
-        product.img_url,
         product.status,
         product.comment,
         operator,
@@ -341,12 +686,53 @@ WHERE id = ?
     Ok(row > 0)
 }
 
-pub async fn delete_product(db: &MySqlPool, id: u64, operator: &str) -> Result<bool> {
+/// Deleted products keep the prices they had.
+pub async fn update_product_price<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    id: u64,
+    price: &ProductPrice,
+    operator: &str,
+) -> Result<bool> {
     let row = query_unchecked!(
-        r#"UPDATE product SET status = ?, modifier = ? WHERE id = ?"#,
-        CommonStatus::Invalid as i8,
+        r#"
+UPDATE product SET `sell_price` = ?, `import_price` = ?, modifier = ?
+WHERE id = ? AND status != ?
+"#,
+        price.sell_price,
+        price.import_price,
+        operator,
+        id,
+        ProductStatus::Deleted,
+    )
+    .execute(db)
+    .await?
+    .rows_affected();
+
+    Ok(row > 0)
+}
+
+/// Move a product from `from` to `to`, fails when someone else changed the
+/// status in between. Who deleted it is kept apart from the last modifier.
+pub async fn update_product_status<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    id: u64,
+    from: ProductStatus,
+    to: ProductStatus,
+    operator: &str,
+) -> Result<bool> {
+    let deleted = to == ProductStatus::Deleted;
+    let row = query_unchecked!(
+        r#"
+UPDATE product SET status = ?, modifier = ?, deleted_by = ?,
+deleted_at = IF(?, CURRENT_TIMESTAMP, NULL)
+WHERE id = ? AND status = ?
+"#,
+        to,
         operator,
+        if deleted { operator } else { "" },
+        deleted,
         id,
+        from,
     )
     .execute(db)
     .await?
@@ -355,10 +741,62 @@ pub async fn delete_product(db: &MySqlPool, id: u64, operator: &str) -> Result<b
     Ok(row > 0)
 }
 
-pub async fn is_product_valid(db: &MySqlPool, id: u64) -> Result<bool> {
+/// Remove a deleted product for good, along with the rows only it owns. The
+/// price history is kept for the reports.
+pub async fn purge_product(conn: &mut MySqlConnection, id: u64) -> Result<bool> {
+    let row = query_unchecked!(
+        r#"DELETE FROM product WHERE id = ? AND status = ?"#,
+        id,
+        ProductStatus::Deleted,
+    )
+    .execute(&mut *conn)
+    .await?
+    .rows_affected();
+    if row == 0 {
+        return Ok(false);
+    }
+    for statement in &[
+        "DELETE FROM product_sku WHERE product_id = ?",
+        "DELETE FROM product_image WHERE product_id = ?",
+        "DELETE FROM product_category WHERE product_id = ?",
+        "DELETE FROM hot_product WHERE product_id = ?",
+    ] {
+        query(statement).bind(id).execute(&mut *conn).await?;
+    }
+
+    Ok(true)
+}
+
+/// Whether the product exists and is not deleted, drafts included.
+pub async fn is_product_exist<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    id: u64,
+) -> Result<bool> {
+    let record = query_unchecked!(
+        r#"SELECT COUNT(*) AS `count` FROM product WHERE id = ? AND status != ?"#,
+        id,
+        ProductStatus::Deleted,
+    )
+    .fetch_one(db)
+    .await?;
+
+    Ok(record.count > 0)
+}
+
+/// Whether the product is visible to the public api.
+pub async fn is_product_valid<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    id: u64,
+) -> Result<bool> {
     let id = query_unchecked!(
-        r#"SELECT id FROM product WHERE id = ? AND status = ? LIMIT 1"#,
+        r#"
+SELECT p.id FROM product p
+JOIN brand b ON p.brand_id = b.id
+WHERE p.id = ? AND p.status = ? AND b.status = ?
+LIMIT 1
+"#,
         id,
+        ProductStatus::OnShelf,
         CommonStatus::Valid as i8,
     )
     .fetch_optional(db)
@@ -370,48 +808,1199 @@ pub async fn is_product_valid(db: &MySqlPool, id: u64) -> Result<bool> {
     }
 }
 
-// hot product
+// trash
 
-pub async fn get_hot_products(db: &MySqlPool) -> Result<Vec<HotProduct>> {
+pub async fn get_deleted_brands<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    paging: &Paging,
+) -> Result<Vec<TrashItem>> {
     query_as_unchecked!(
-        HotProduct,
+        TrashItem,
         r#"
-SELECT `product_id`
-FROM hot_product
+SELECT id, `name`, deleted_by, COALESCE(deleted_at, updated_at) AS deleted_at
+FROM brand
 WHERE status = ?
-ORDER BY id"#,
-        CommonStatus::Valid as i8,
+ORDER BY deleted_at DESC, id DESC
+LIMIT ?, ?"#,
+        CommonStatus::Invalid as i8,
+        paging.offset.unwrap_or(0),
+        paging.limit_or(MIN_ROWS),
     )
     .fetch_all(db)
     .await
     .map_err(|e| e.into())
 }
 
-pub async fn delete_hot_products(db: &MySqlPool, operator: &str) -> Result<bool> {
-    let _ = query_unchecked!(
-        r#"UPDATE hot_product SET status = ?, modifier = ? WHERE status != ?"#,
+pub async fn get_deleted_brand<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    id: u64,
+) -> Result<Option<TrashItem>> {
+    query_as_unchecked!(
+        TrashItem,
+        r#"
+SELECT id, `name`, deleted_by, COALESCE(deleted_at, updated_at) AS deleted_at
+FROM brand
+WHERE id = ? AND status = ?"#,
+        id,
         CommonStatus::Invalid as i8,
-        operator,
+    )
+    .fetch_optional(db)
+    .await
+    .map_err(|e| e.into())
+}
+
+pub async fn count_deleted_brands<'c, E: Executor<'c, Database = MySql>>(db: E) -> Result<i64> {
+    let record = query_unchecked!(
+        r#"SELECT COUNT(*) AS `count` FROM brand WHERE status = ?"#,
         CommonStatus::Invalid as i8,
     )
-    .execute(db)
-    .await?
-    .rows_affected();
+    .fetch_one(db)
+    .await?;
 
-    Ok(true)
+    Ok(record.count)
 }
 
-pub async fn create_hot_products(
-    db: &MySqlPool,
-    hot_products: Vec<u64>,
-    operator: &str,
-) -> Result<bool> {
-    let mut s = hot_products.iter().fold(
-        "INSERT INTO hot_product (`product_id`, `creator`) VALUES ".to_string(),
-        |s, hp| format!("{} ({}, '{}'),", s, hp, operator),
-    );
-    s.pop();
-    let id = query(s.as_str()).execute(db).await?.last_insert_id();
+pub async fn get_deleted_products<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    paging: &Paging,
+) -> Result<Vec<TrashItem>> {
+    query_as_unchecked!(
+        TrashItem,
+        r#"
+SELECT id, `name`, deleted_by, COALESCE(deleted_at, updated_at) AS deleted_at
+FROM product
+WHERE status = ?
+ORDER BY deleted_at DESC, id DESC
+LIMIT ?, ?"#,
+        ProductStatus::Deleted,
+        paging.offset.unwrap_or(0),
+        paging.limit_or(MIN_ROWS),
+    )
+    .fetch_all(db)
+    .await
+    .map_err(|e| e.into())
+}
 
-    Ok(id > 0)
+pub async fn count_deleted_products<'c, E: Executor<'c, Database = MySql>>(db: E) -> Result<i64> {
+    let record = query_unchecked!(
+        r#"SELECT COUNT(*) AS `count` FROM product WHERE status = ?"#,
+        ProductStatus::Deleted,
+    )
+    .fetch_one(db)
+    .await?;
+
+    Ok(record.count)
+}
+
+// product image
+
+pub async fn get_product_images<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    product_id: u64,
+) -> Result<Vec<ProductImage>> {
+    query_as_unchecked!(
+        ProductImage,
+        r#"
+SELECT `id`, `product_id`, `url`, `thumb_url`, `sequence`
+FROM product_image
+WHERE product_id = ? AND status = ?
+ORDER BY `sequence`, id
+"#,
+        product_id,
+        CommonStatus::Valid as i8,
+    )
+    .fetch_all(db)
+    .await
+    .map_err(|e| e.into())
+}
+
+pub async fn get_max_image_sequence<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    product_id: u64,
+) -> Result<i32> {
+    let record = query_unchecked!(
+        r#"SELECT MAX(`sequence`) AS `sequence` FROM product_image WHERE product_id = ? AND status = ? FOR UPDATE"#,
+        product_id,
+        CommonStatus::Valid as i8,
+    )
+    .fetch_one(db)
+    .await?;
+
+    Ok(record.sequence.unwrap_or(0))
+}
+
+pub async fn create_product_image<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    product_id: u64,
+    url: &str,
+    thumb_url: &str,
+    sequence: i32,
+    operator: &str,
+) -> Result<u64> {
+    let id = query_unchecked!(
+        r#"
+INSERT INTO product_image (`product_id`, `url`, `thumb_url`, `sequence`, `creator`)
+VALUES (?, ?, ?, ?, ?)
+"#,
+        product_id,
+        url,
+        thumb_url,
+        sequence,
+        operator,
+    )
+    .execute(db)
+    .await?
+    .last_insert_id();
+
+    Ok(id)
+}
+
+pub async fn update_image_sequence<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    product_id: u64,
+    image_sequence: &ImageSequence,
+    operator: &str,
+) -> Result<bool> {
+    let row = query_unchecked!(
+        r#"
+UPDATE product_image SET `sequence` = ?, modifier = ?
+WHERE id = ? AND product_id = ? AND status = ?
+"#,
+        image_sequence.sequence,
+        operator,
+        image_sequence.id,
+        product_id,
+        CommonStatus::Valid as i8,
+    )
+    .execute(db)
+    .await?
+    .rows_affected();
+
+    Ok(row > 0)
+}
+
+pub async fn delete_product_image<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    product_id: u64,
+    id: u64,
+    operator: &str,
+) -> Result<bool> {
+    let row = query_unchecked!(
+        r#"UPDATE product_image SET status = ?, modifier = ? WHERE id = ? AND product_id = ? AND status = ?"#,
+        CommonStatus::Invalid as i8,
+        operator,
+        id,
+        product_id,
+        CommonStatus::Valid as i8,
+    )
+    .execute(db)
+    .await?
+    .rows_affected();
+
+    Ok(row > 0)
+}
+
+/// Use the first image as `product.img_url`, or clear it when there is none.
+pub async fn sync_product_cover<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    product_id: u64,
+) -> Result<()> {
+    query_unchecked!(
+        r#"
+UPDATE product SET img_url = COALESCE((
+  SELECT url FROM product_image
+  WHERE product_id = ? AND status = ?
+  ORDER BY `sequence`, id
+  LIMIT 1
+), '')
+WHERE id = ?
+"#,
+        product_id,
+        CommonStatus::Valid as i8,
+        product_id,
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+// product sku
+
+/// All skus of a product except the deleted ones, the default one first.
+pub async fn get_product_skus<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    product_id: u64,
+) -> Result<Vec<Sku>> {
+    query_as_unchecked!(
+        Sku,
+        r#"
+SELECT `id`, `product_id`, `spec`, `kind`, `barcode`, `sell_price`, `import_price`,
+`sequence`, `status`
+FROM product_sku
+WHERE product_id = ? AND status != ?
+ORDER BY `sequence`, id
+"#,
+        product_id,
+        SkuStatus::Deleted,
+    )
+    .fetch_all(db)
+    .await
+    .map_err(|e| e.into())
+}
+
+/// Skus on sale, for the public product detail.
+pub async fn get_valid_skus<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    product_id: u64,
+) -> Result<Vec<Sku>> {
+    query_as_unchecked!(
+        Sku,
+        r#"
+SELECT `id`, `product_id`, `spec`, `kind`, `barcode`, `sell_price`, `import_price`,
+`sequence`, `status`
+FROM product_sku
+WHERE product_id = ? AND status = ?
+ORDER BY `sequence`, id
+"#,
+        product_id,
+        SkuStatus::OnSale,
+    )
+    .fetch_all(db)
+    .await
+    .map_err(|e| e.into())
+}
+
+pub async fn get_sku<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    product_id: u64,
+    id: u64,
+) -> Result<Option<Sku>> {
+    query_as_unchecked!(
+        Sku,
+        r#"
+SELECT `id`, `product_id`, `spec`, `kind`, `barcode`, `sell_price`, `import_price`,
+`sequence`, `status`
+FROM product_sku
+WHERE id = ? AND product_id = ? AND status != ?
+"#,
+        id,
+        product_id,
+        SkuStatus::Deleted,
+    )
+    .fetch_optional(db)
+    .await
+    .map_err(|e| e.into())
+}
+
+pub async fn get_max_sku_sequence<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    product_id: u64,
+) -> Result<i32> {
+    let record = query_unchecked!(
+        r#"SELECT MAX(`sequence`) AS `sequence` FROM product_sku WHERE product_id = ? AND status != ? FOR UPDATE"#,
+        product_id,
+        SkuStatus::Deleted,
+    )
+    .fetch_one(db)
+    .await?;
+
+    Ok(record.sequence.unwrap_or(0))
+}
+
+/// Lock the product row until the end of the transaction, writers of its
+/// skus take it first so they run one after another. Returns whether the
+/// product exists.
+pub async fn lock_product<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    product_id: u64,
+) -> Result<bool> {
+    let row = query_unchecked!(
+        r#"SELECT id FROM product WHERE id = ? FOR UPDATE"#,
+        product_id
+    )
+    .fetch_optional(db)
+    .await?;
+
+    Ok(row.is_some())
+}
+
+/// Whether another live sku already uses the barcode.
+pub async fn is_barcode_taken<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    barcode: &str,
+    exclude_id: u64,
+) -> Result<bool> {
+    let record = query_unchecked!(
+        r#"SELECT COUNT(*) AS count FROM product_sku WHERE barcode = ? AND id != ? AND status != ?"#,
+        barcode,
+        exclude_id,
+        SkuStatus::Deleted,
+    )
+    .fetch_one(db)
+    .await?;
+
+    Ok(record.count > 0)
+}
+
+pub async fn create_sku<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    product_id: u64,
+    sku: &NewSku,
+    operator: &str,
+) -> Result<u64> {
+    let id = query_unchecked!(
+        r#"
+INSERT INTO product_sku (`product_id`, `spec`, `kind`, `barcode`, `sell_price`,
+`import_price`, `sequence`, `status`, `creator`)
+VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+"#,
+        product_id,
+        sku.spec,
+        sku.kind,
+        sku.barcode,
+        sku.sell_price,
+        sku.import_price,
+        sku.sequence,
+        sku.status,
+        operator,
+    )
+    .execute(db)
+    .await?
+    .last_insert_id();
+
+    Ok(id)
+}
+
+pub async fn update_sku<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    product_id: u64,
+    id: u64,
+    sku: &NewSku,
+    operator: &str,
+) -> Result<bool> {
+    let row = query_unchecked!(
+        r#"
+UPDATE product_sku SET `spec` = ?, `kind` = ?, `barcode` = ?, `sell_price` = ?,
+`import_price` = ?, `sequence` = ?, `status` = ?, modifier = ?
+WHERE id = ? AND product_id = ? AND status != ?
+"#,
+        sku.spec,
+        sku.kind,
+        sku.barcode,
+        sku.sell_price,
+        sku.import_price,
+        sku.sequence,
+        sku.status,
+        operator,
+        id,
+        product_id,
+        SkuStatus::Deleted,
+    )
+    .execute(db)
+    .await?
+    .rows_affected();
+
+    Ok(row > 0)
+}
+
+pub async fn delete_sku<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    product_id: u64,
+    id: u64,
+    operator: &str,
+) -> Result<bool> {
+    let row = query_unchecked!(
+        r#"UPDATE product_sku SET status = ?, modifier = ? WHERE id = ? AND product_id = ? AND status != ?"#,
+        SkuStatus::Deleted,
+        operator,
+        id,
+        product_id,
+        SkuStatus::Deleted,
+    )
+    .execute(db)
+    .await?
+    .rows_affected();
+
+    Ok(row > 0)
+}
+
+/// Write the spec and prices of a product to its default sku, creating the
+/// sku when the product has none yet. Returns the id of the default sku, the
+/// first one on sale or else the first live one.
+pub async fn sync_default_sku(
+    conn: &mut MySqlConnection,
+    product_id: u64,
+    spec: &str,
+    kind: u8,
+    price: &ProductPrice,
+    operator: &str,
+) -> Result<u64> {
+    let default = query_unchecked!(
+        r#"
+SELECT id FROM product_sku
+WHERE product_id = ? AND status != ?
+ORDER BY status != ?, `sequence`, id
+LIMIT 1
+FOR UPDATE
+"#,
+        product_id,
+        SkuStatus::Deleted,
+        SkuStatus::OnSale,
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    if let Some(record) = default {
+        query_unchecked!(
+            r#"
+UPDATE product_sku SET `spec` = ?, `kind` = ?, `sell_price` = ?, `import_price` = ?, modifier = ?
+WHERE id = ?
+"#,
+            spec,
+            kind,
+            price.sell_price,
+            price.import_price,
+            operator,
+            record.id,
+        )
+        .execute(&mut *conn)
+        .await?;
+        return Ok(record.id);
+    }
+
+    let id = query_unchecked!(
+        r#"
+INSERT INTO product_sku (`product_id`, `spec`, `kind`, `sell_price`, `import_price`,
+`sequence`, `creator`)
+VALUES (?, ?, ?, ?, ?, 1, ?)
+"#,
+        product_id,
+        spec,
+        kind,
+        price.sell_price,
+        price.import_price,
+        operator,
+    )
+    .execute(&mut *conn)
+    .await?
+    .last_insert_id();
+
+    Ok(id)
+}
+
+/// Mirror the default sku back onto the product, so that lists, search and
+/// filters keep working on the product table alone.
+pub async fn sync_product_from_sku<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    product_id: u64,
+    operator: &str,
+) -> Result<()> {
+    query_unchecked!(
+        r#"
+UPDATE product p
+JOIN (
+  SELECT `spec`, `kind`, `sell_price`, `import_price`
+  FROM product_sku
+  WHERE product_id = ? AND status != ?
+  ORDER BY status != ?, `sequence`, id
+  LIMIT 1
+) s
+SET p.spec = s.spec, p.kind = s.kind, p.sell_price = s.sell_price,
+p.import_price = s.import_price, p.modifier = ?
+WHERE p.id = ?
+"#,
+        product_id,
+        SkuStatus::Deleted,
+        SkuStatus::OnSale,
+        operator,
+        product_id,
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+// price history
+
+pub async fn create_price_history<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    product_id: u64,
+    sku_id: u64,
+    price: &ProductPrice,
+    operator: &str,
+) -> Result<u64> {
+    let id = query_unchecked!(
+        r#"
+INSERT INTO price_history (`product_id`, `sku_id`, `sell_price`, `import_price`,
+`price_time`, `creator`)
+VALUES (?, ?, ?, ?, NOW(), ?)
+"#,
+        product_id,
+        sku_id,
+        price.sell_price,
+        price.import_price,
+        operator,
+    )
+    .execute(db)
+    .await?
+    .last_insert_id();
+
+    Ok(id)
+}
+
+pub async fn get_price_history<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    product_id: u64,
+    q: &PriceHistoryQuery,
+) -> Result<Vec<PriceRecord>> {
+    query_as_unchecked!(
+        PriceRecord,
+        r#"
+SELECT `sell_price`, `import_price`, `price_time`
+FROM price_history
+WHERE product_id = ? AND status = ?
+AND (? IS NULL OR sku_id = ?)
+AND (? IS NULL OR price_time >= ?)
+AND (? IS NULL OR price_time < DATE_ADD(?, INTERVAL 1 DAY))
+ORDER BY price_time, id
+"#,
+        product_id,
+        CommonStatus::Valid as i8,
+        q.sku_id,
+        q.sku_id,
+        q.start,
+        q.start,
+        q.end,
+        q.end,
+    )
+    .fetch_all(db)
+    .await
+    .map_err(|e| e.into())
+}
+
+// hot brand
+
+/// Valid hot brands in ranked order.
+pub async fn get_hot_brands<'c, E: Executor<'c, Database = MySql>>(db: E) -> Result<Vec<Brand>> {
+    query_as_unchecked!(
+        Brand,
+        r#"
+SELECT b.id, b.name, b.en_name, b.logo_url, b.country, b.description, b.website,
+b.sequence, true AS is_hot
+FROM hot_brand h
+JOIN brand b
+ON h.brand_id = b.id
+WHERE h.status = ? AND b.status = ?
+ORDER BY h.sequence, h.id"#,
+        CommonStatus::Valid as i8,
+        CommonStatus::Valid as i8,
+    )
+    .fetch_all(db)
+    .await
+    .map_err(|e| e.into())
+}
+
+pub async fn get_hot_brand_ids<'c, E: Executor<'c, Database = MySql>>(db: E) -> Result<Vec<u64>> {
+    let records = query_unchecked!(
+        r#"SELECT brand_id FROM hot_brand WHERE status = ? ORDER BY `sequence`, id"#,
+        CommonStatus::Valid as i8,
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(records.into_iter().map(|r| r.brand_id).collect())
+}
+
+pub async fn delete_hot_brands<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    operator: &str,
+) -> Result<bool> {
+    let _ = query_unchecked!(
+        r#"UPDATE hot_brand SET status = ?, modifier = ? WHERE status != ?"#,
+        CommonStatus::Invalid as i8,
+        operator,
+        CommonStatus::Invalid as i8,
+    )
+    .execute(db)
+    .await?
+    .rows_affected();
+
+    Ok(true)
+}
+
+/// Rank the brands in the given order, starting from 1.
+pub async fn create_hot_brands<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    brand_ids: &[u64],
+    operator: &str,
+) -> Result<()> {
+    if brand_ids.is_empty() {
+        return Ok(());
+    }
+    let mut qb =
+        QueryBuilder::new("INSERT INTO hot_brand (`brand_id`, `sequence`, `creator`) VALUES ");
+    qb.push_values(brand_ids.iter().enumerate(), |row, (i, brand_id)| {
+        row.bind(*brand_id).bind(i as i32 + 1).bind(operator);
+    });
+    qb.build().execute(db).await?;
+
+    Ok(())
+}
+
+/// Hot entries of valid lists pointing at a product, ended lists included.
+pub async fn count_hot_product_refs<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    product_id: u64,
+) -> Result<i64> {
+    let record = query_unchecked!(
+        r#"
+SELECT COUNT(*) AS `count`
+FROM hot_product h
+JOIN hot_list l
+ON h.list_id = l.id
+WHERE h.product_id = ? AND h.status = ? AND l.status = ?
+"#,
+        product_id,
+        CommonStatus::Valid as i8,
+        CommonStatus::Valid as i8,
+    )
+    .fetch_one(db)
+    .await?;
+
+    Ok(record.count)
+}
+
+// hot product
+
+pub async fn get_hot_products<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    slot: &str,
+) -> Result<Vec<HotProduct>> {
+    query_as_unchecked!(
+        HotProduct,
+        r#"
+SELECT `product_id`
+FROM hot_product
+WHERE status = ? AND list_id = (
+  SELECT id FROM hot_list
+  WHERE slot = ? AND status = ? AND start_at <= NOW() AND (end_at IS NULL OR end_at > NOW())
+  ORDER BY start_at DESC, id DESC
+  LIMIT 1
+)
+ORDER BY `sequence`, id"#,
+        CommonStatus::Valid as i8,
+        slot,
+        CommonStatus::Valid as i8,
+    )
+    .fetch_all(db)
+    .await
+    .map_err(|e| e.into())
+}
+
+pub async fn get_hot_product_items<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    slot: &str,
+) -> Result<Vec<ProductItem>> {
+    query_as_unchecked!(
+        ProductItem,
+        r#"
+SELECT p.id, p.name, p.alias, p.title, p.subtitle, p.brand_id, b.name as brand_name,
+p.spec, p.kind, p.sell_price, p.import_price, p.sequence, p.jd_id, p.jd_url,
+p.img_url, p.status, p.comment
+FROM hot_product h
+JOIN product p
+ON h.product_id = p.id
+JOIN brand b
+ON p.brand_id = b.id
+WHERE h.status = ? AND h.list_id = (
+  SELECT id FROM hot_list
+  WHERE slot = ? AND status = ? AND start_at <= NOW() AND (end_at IS NULL OR end_at > NOW())
+  ORDER BY start_at DESC, id DESC
+  LIMIT 1
+)
+ORDER BY h.sequence, h.id"#,
+        CommonStatus::Valid as i8,
+        slot,
+        CommonStatus::Valid as i8,
+    )
+    .fetch_all(db)
+    .await
+    .map_err(|e| e.into())
+}
+
+/// Cards of the live list of a slot, skipping products which are not on shelf
+/// and brands which are not valid any more.
+pub async fn get_hot_product_cards<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    slot: &str,
+) -> Result<Vec<ProductCard>> {
+    query_as_unchecked!(
+        ProductCard,
+        r#"
+SELECT p.id, p.name, p.title, p.subtitle, p.img_url, p.sell_price, p.brand_id,
+b.name as brand_name
+FROM hot_product h
+JOIN product p
+ON h.product_id = p.id
+JOIN brand b
+ON p.brand_id = b.id
+WHERE h.status = ? AND p.status = ? AND b.status = ? AND h.list_id = (
+  SELECT id FROM hot_list
+  WHERE slot = ? AND status = ? AND start_at <= NOW() AND (end_at IS NULL OR end_at > NOW())
+  ORDER BY start_at DESC, id DESC
+  LIMIT 1
+)
+ORDER BY h.sequence, h.id"#,
+        CommonStatus::Valid as i8,
+        ProductStatus::OnShelf,
+        CommonStatus::Valid as i8,
+        slot,
+        CommonStatus::Valid as i8,
+    )
+    .fetch_all(db)
+    .await
+    .map_err(|e| e.into())
+}
+
+/// Lists of a slot which have not ended yet, the latest started one is live.
+pub async fn get_hot_lists<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    slot: &str,
+) -> Result<Vec<HotList>> {
+    query_as_unchecked!(
+        HotList,
+        r#"
+SELECT `id`, `slot`, `start_at`, `end_at`
+FROM hot_list
+WHERE slot = ? AND status = ? AND (end_at IS NULL OR end_at > NOW())
+ORDER BY start_at DESC, id DESC"#,
+        slot,
+        CommonStatus::Valid as i8,
+    )
+    .fetch_all(db)
+    .await
+    .map_err(|e| e.into())
+}
+
+pub async fn get_hot_list<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    id: u64,
+) -> Result<Option<HotList>> {
+    query_as_unchecked!(
+        HotList,
+        r#"
+SELECT `id`, `slot`, `start_at`, `end_at`
+FROM hot_list
+WHERE id = ? AND status = ?"#,
+        id,
+        CommonStatus::Valid as i8,
+    )
+    .fetch_optional(db)
+    .await
+    .map_err(|e| e.into())
+}
+
+pub async fn get_hot_list_product_ids<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    list_id: u64,
+) -> Result<Vec<u64>> {
+    let records = query_unchecked!(
+        r#"
+SELECT product_id FROM hot_product
+WHERE list_id = ? AND status = ?
+ORDER BY `sequence`, id"#,
+        list_id,
+        CommonStatus::Valid as i8,
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(records.into_iter().map(|r| r.product_id).collect())
+}
+
+/// A list starts right away when `start_at` is not given.
+pub async fn create_hot_list<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    list: &NewHotList,
+    operator: &str,
+) -> Result<u64> {
+    let id = query_unchecked!(
+        r#"
+INSERT INTO hot_list (`slot`, `start_at`, `end_at`, `creator`)
+VALUES (?, COALESCE(?, NOW()), ?, ?)
+"#,
+        list.slot,
+        list.start_at,
+        list.end_at,
+        operator,
+    )
+    .execute(db)
+    .await?
+    .last_insert_id();
+
+    Ok(id)
+}
+
+/// Lists of a slot which have started and not ended yet, locked until the end
+/// of the transaction.
+pub async fn get_live_hot_list_ids<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    slot: &str,
+) -> Result<Vec<u64>> {
+    let rows = query_unchecked!(
+        r#"
+SELECT id FROM hot_list
+WHERE slot = ? AND status = ? AND start_at <= NOW() AND (end_at IS NULL OR end_at > NOW())
+FOR UPDATE
+"#,
+        slot,
+        CommonStatus::Valid as i8,
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows.into_iter().map(|r| r.id).collect())
+}
+
+pub async fn end_hot_lists<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    ids: &[u64],
+    operator: &str,
+) -> Result<bool> {
+    let mut qb = QueryBuilder::new("UPDATE hot_list SET end_at = NOW(), modifier = ");
+    qb.push_bind(operator)
+        .push(" WHERE id IN ")
+        .push_in(ids.iter().copied());
+    let row = qb.build().execute(db).await?.rows_affected();
+
+    Ok(row > 0)
+}
+
+pub async fn delete_hot_list<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    id: u64,
+    operator: &str,
+) -> Result<bool> {
+    let row = query_unchecked!(
+        r#"UPDATE hot_list SET status = ?, modifier = ? WHERE id = ? AND status = ?"#,
+        CommonStatus::Invalid as i8,
+        operator,
+        id,
+        CommonStatus::Valid as i8,
+    )
+    .execute(db)
+    .await?
+    .rows_affected();
+
+    Ok(row > 0)
+}
+
+pub async fn create_hot_products<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    list_id: u64,
+    hot_products: Vec<u64>,
+    operator: &str,
+) -> Result<bool> {
+    let mut qb = QueryBuilder::new(
+        "INSERT INTO hot_product (`list_id`, `product_id`, `sequence`, `creator`) VALUES ",
+    );
+    qb.push_values(hot_products.iter().enumerate(), |row, (i, product_id)| {
+        row.bind(list_id)
+            .bind(*product_id)
+            .bind(i as i32 + 1)
+            .bind(operator);
+    });
+    let id = qb.build().execute(db).await?.last_insert_id();
+
+    Ok(id > 0)
+}
+
+// category
+
+pub async fn get_categories<'c, E: Executor<'c, Database = MySql>>(db: E) -> Result<Vec<Category>> {
+    query_as_unchecked!(
+        Category,
+        r#"
+SELECT `id`, `parent_id`, `path`, `name`, `sequence`
+FROM category
+WHERE status = ?
+ORDER BY `sequence`, id"#,
+        CommonStatus::Valid as i8,
+    )
+    .fetch_all(db)
+    .await
+    .map_err(|e| e.into())
+}
+
+pub async fn get_category<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    id: u64,
+) -> Result<Option<Category>> {
+    query_as_unchecked!(
+        Category,
+        r#"
+SELECT `id`, `parent_id`, `path`, `name`, `sequence`
+FROM category
+WHERE id = ? AND status = ?"#,
+        id,
+        CommonStatus::Valid as i8,
+    )
+    .fetch_optional(db)
+    .await
+    .map_err(|e| e.into())
+}
+
+pub async fn get_max_category_sequence<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    parent_id: u64,
+) -> Result<i32> {
+    let record = query_unchecked!(
+        r#"
+SELECT COALESCE(MAX(`sequence`), 0) AS `max_id`
+FROM category
+WHERE parent_id = ? AND status = ?
+FOR UPDATE"#,
+        parent_id,
+        CommonStatus::Valid as i8
+    )
+    .fetch_one(db)
+    .await?;
+
+    Ok(record.max_id as i32)
+}
+
+pub async fn create_category(
+    conn: &mut MySqlConnection,
+    category: &NewCategory,
+    parent_path: &str,
+    sequence: i32,
+    operator: &str,
+) -> Result<u64> {
+    let id = query_unchecked!(
+        r#"
+INSERT INTO category (`parent_id`, `name`, `sequence`, `creator`)
+VALUES (?, ?, ?, ?)
+"#,
+        category.parent_id,
+        category.name,
+        sequence,
+        operator,
+    )
+    .execute(&mut *conn)
+    .await?
+    .last_insert_id();
+
+    query_unchecked!(
+        r#"UPDATE category SET `path` = CONCAT(?, id, '/') WHERE id = ?"#,
+        parent_path,
+        id,
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(id)
+}
+
+/// Rename or move a category, `category.path` is the current path and the paths
+/// of its descendants follow the move.
+pub async fn update_category(
+    conn: &mut MySqlConnection,
+    category: &Category,
+    new_parent_path: &str,
+    operator: &str,
+) -> Result<bool> {
+    let new_path = format!("{}{}/", new_parent_path, category.id);
+    let old_path = &category.path;
+
+    let row = query_unchecked!(
+        r#"
+UPDATE category SET `parent_id` = ?, `name` = ?, `path` = ?, modifier = ?
+WHERE id = ? AND status = ?"#,
+        category.parent_id,
+        category.name,
+        new_path,
+        operator,
+        category.id,
+        CommonStatus::Valid as i8,
+    )
+    .execute(&mut *conn)
+    .await?
+    .rows_affected();
+
+    if row > 0 && *old_path != new_path {
+        query_unchecked!(
+            r#"
+UPDATE category SET `path` = CONCAT(?, SUBSTRING(`path`, CHAR_LENGTH(?) + 1)), modifier = ?
+WHERE `path` LIKE CONCAT(?, '%') AND id != ?"#,
+            new_path,
+            old_path,
+            operator,
+            old_path,
+            category.id,
+        )
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    Ok(row > 0)
+}
+
+pub async fn update_category_sequence<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    category_sequence: &CategorySequence,
+    operator: &str,
+) -> Result<bool> {
+    let row = query_unchecked!(
+        r#"UPDATE category SET `sequence` = ?, modifier = ? WHERE id = ? AND status = ?"#,
+        category_sequence.sequence,
+        operator,
+        category_sequence.id,
+        CommonStatus::Valid as i8,
+    )
+    .execute(db)
+    .await?
+    .rows_affected();
+
+    Ok(row > 0)
+}
+
+pub async fn count_child_categories<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    id: u64,
+) -> Result<i64> {
+    let record = query_unchecked!(
+        r#"SELECT COUNT(*) AS `count` FROM category WHERE parent_id = ? AND status = ?"#,
+        id,
+        CommonStatus::Valid as i8,
+    )
+    .fetch_one(db)
+    .await?;
+
+    Ok(record.count)
+}
+
+pub async fn delete_category(conn: &mut MySqlConnection, id: u64, operator: &str) -> Result<bool> {
+    let row = query_unchecked!(
+        r#"UPDATE category SET status = ?, modifier = ? WHERE id = ?"#,
+        CommonStatus::Invalid as i8,
+        operator,
+        id,
+    )
+    .execute(&mut *conn)
+    .await?
+    .rows_affected();
+
+    query_unchecked!(r#"DELETE FROM product_category WHERE category_id = ?"#, id)
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(row > 0)
+}
+
+pub async fn get_product_category_ids<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    product_id: u64,
+) -> Result<Vec<u64>> {
+    let rows = query_unchecked!(
+        r#"
+SELECT pc.category_id
+FROM product_category pc
+JOIN category c
+ON pc.category_id = c.id
+WHERE pc.product_id = ? AND c.status = ?
+ORDER BY pc.category_id"#,
+        product_id,
+        CommonStatus::Valid as i8,
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows.into_iter().map(|r| r.category_id).collect())
+}
+
+pub async fn set_product_categories(
+    conn: &mut MySqlConnection,
+    product_id: u64,
+    category_ids: &[u64],
+    operator: &str,
+) -> Result<bool> {
+    query_unchecked!(
+        r#"DELETE FROM product_category WHERE product_id = ?"#,
+        product_id
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    if !category_ids.is_empty() {
+        let mut qb = QueryBuilder::new(
+            "INSERT INTO product_category (`product_id`, `category_id`, `creator`) VALUES ",
+        );
+        qb.push_values(category_ids.iter(), |row, category_id| {
+            row.bind(product_id).bind(*category_id).bind(operator);
+        });
+        qb.build().execute(&mut *conn).await?;
+    }
+
+    Ok(true)
+}
+
+/// Products linked to the category at `path` or any of its descendants.
+pub async fn get_category_products<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    path: &str,
+    paging: &Paging,
+    cursor: Option<Cursor>,
+) -> Result<Vec<BrandItem>> {
+    let after_id = cursor.map(|c| c.id);
+    query_as_unchecked!(
+        BrandItem,
+        r#"
+SELECT DISTINCT p.id, p.name, p.title, p.subtitle, p.img_url
+FROM product p
+JOIN product_category pc
+ON pc.product_id = p.id
+JOIN category c
+ON pc.category_id = c.id
+WHERE c.path LIKE CONCAT(?, '%') AND c.status = ? AND p.status = ?
+AND (? IS NULL OR p.id > ?)
+ORDER BY p.id
+LIMIT ?, ?
+"#,
+        path,
+        CommonStatus::Valid as i8,
+        ProductStatus::OnShelf,
+        after_id,
+        after_id,
+        paging.offset(),
+        paging.limit_or(MIN_ROWS),
+    )
+    .fetch_all(db)
+    .await
+    .map_err(|e| e.into())
+}
+
+pub async fn count_category_products<'c, E: Executor<'c, Database = MySql>>(
+    db: E,
+    path: &str,
+) -> Result<i64> {
+    let record = query_unchecked!(
+        r#"
+SELECT COUNT(DISTINCT p.id) AS `count`
+FROM product p
+JOIN product_category pc
+ON pc.product_id = p.id
+JOIN category c
+ON pc.category_id = c.id
+WHERE c.path LIKE CONCAT(?, '%') AND c.status = ? AND p.status = ?
+"#,
+        path,
+        CommonStatus::Valid as i8,
+        ProductStatus::OnShelf,
+    )
+    .fetch_one(db)
+    .await?;
+
+    Ok(record.count)
 }
@@ -0,0 +1,197 @@
+use anyhow::{anyhow, Result};
+use sqlx::migrate::Migrator;
+use sqlx::mysql::MySqlPool;
+use sqlx::{query, query_as, Executor};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MigrationState {
+    Applied,
+    Pending,
+    /// Failed half way, has to be fixed by hand.
+    Dirty,
+    /// The file changed after it was applied.
+    Modified,
+    /// Applied by a newer build, this one doesn't know it.
+    Unknown,
+}
+
+impl MigrationState {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            MigrationState::Applied => "applied",
+            MigrationState::Pending => "pending",
+            MigrationState::Dirty => "dirty",
+            MigrationState::Modified => "modified",
+            MigrationState::Unknown => "unknown",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub state: MigrationState,
+}
+
+/// The migrations under `migrations/`, built into the binary.
+pub fn migrator() -> Migrator {
+    sqlx::migrate!("./migrations")
+}
+
+/// The scripts reverting them, under `migrations/down/` with the same names.
+pub fn down_migrator() -> Migrator {
+    sqlx::migrate!("./migrations/down")
+}
+
+/// Every embedded migration along with the ones only the database knows,
+/// ordered by version.
+pub async fn status(db: &MySqlPool) -> Result<Vec<MigrationStatus>> {
+    let applied = applied_migrations(db).await?;
+    let migrator = migrator();
+    let mut res: Vec<MigrationStatus> = migrator
+        .iter()
+        .map(|m| {
+            let state = match applied.iter().find(|a| a.version == m.version) {
+                None => MigrationState::Pending,
+                Some(a) if !a.success => MigrationState::Dirty,
+                Some(a) if a.checksum != *m.checksum => MigrationState::Modified,
+                Some(_) => MigrationState::Applied,
+            };
+            MigrationStatus {
+                version: m.version,
+                description: m.description.to_string(),
+                state,
+            }
+        })
+        .collect();
+    for a in applied.into_iter() {
+        if migrator.iter().all(|m| m.version != a.version) {
+            res.push(MigrationStatus {
+                version: a.version,
+                description: a.description,
+                state: MigrationState::Unknown,
+            });
+        }
+    }
+    res.sort_by_key(|m| m.version);
+    Ok(res)
+}
+
+/// Apply the pending migrations in order, returns the applied ones.
+pub async fn up(db: &MySqlPool) -> Result<Vec<MigrationStatus>> {
+    let pending: Vec<MigrationStatus> = status(db)
+        .await?
+        .into_iter()
+        .filter(|m| m.state == MigrationState::Pending)
+        .collect();
+    migrator().run(db).await?;
+    Ok(pending)
+}
+
+/// Revert the latest applied migration, returns it or `None` when nothing
+/// was applied. MySQL commits DDL right away so this can't be a transaction,
+/// the migration is marked failed before its script runs and forgotten once
+/// the script went through. A script failing half way leaves it dirty, to be
+/// fixed by hand.
+pub async fn down(db: &MySqlPool) -> Result<Option<MigrationStatus>> {
+    let latest = match status(db)
+        .await?
+        .into_iter()
+        .rev()
+        .find(|m| m.state != MigrationState::Pending)
+    {
+        Some(m) => m,
+        None => return Ok(None),
+    };
+    if latest.state != MigrationState::Applied {
+        return Err(anyhow!(
+            "migration {} is {}, it can't be reverted.",
+            latest.version,
+            latest.state.as_str()
+        ));
+    }
+    let down_migrator = down_migrator();
+    let script = down_migrator
+        .iter()
+        .find(|m| m.version == latest.version)
+        .ok_or_else(|| anyhow!("migration {} has no down script.", latest.version))?;
+
+    query("UPDATE _sqlx_migrations SET success = 0 WHERE version = ?")
+        .bind(latest.version)
+        .execute(db)
+        .await?;
+    db.execute(&*script.sql).await?;
+    query("DELETE FROM _sqlx_migrations WHERE version = ?")
+        .bind(latest.version)
+        .execute(db)
+        .await?;
+    Ok(Some(latest))
+}
+
+/// Fail unless every migration this build knows about is applied.
+pub async fn check(db: &MySqlPool) -> Result<()> {
+    let behind: Vec<String> = status(db)
+        .await?
+        .into_iter()
+        .filter(|m| m.state != MigrationState::Applied && m.state != MigrationState::Unknown)
+        .map(|m| format!("{} {} ({})", m.version, m.description, m.state.as_str()))
+        .collect();
+    if !behind.is_empty() {
+        return Err(anyhow!(
+            "database schema is behind, run `kerria migrate up` first: {}",
+            behind.join(", ")
+        ));
+    }
+    Ok(())
+}
+
+/// Versions of the embedded migrations without a down script, `migrate down`
+/// refuses to revert them.
+pub fn irreversible() -> Vec<i64> {
+    let down_migrator = down_migrator();
+    migrator()
+        .iter()
+        .map(|m| m.version)
+        .filter(|v| down_migrator.iter().all(|d| d.version != *v))
+        .collect()
+}
+
+struct AppliedMigration {
+    version: i64,
+    description: String,
+    success: bool,
+    checksum: Vec<u8>,
+}
+
+async fn applied_migrations(db: &MySqlPool) -> Result<Vec<AppliedMigration>> {
+    // the table is shared with `sqlx migrate`, it doesn't exist on a fresh database
+    let (tables,): (i64,) = query_as(
+        r#"
+SELECT COUNT(*) FROM information_schema.tables
+WHERE table_schema = DATABASE() AND table_name = '_sqlx_migrations'
+"#,
+    )
+    .fetch_one(db)
+    .await?;
+    if tables == 0 {
+        return Ok(vec![]);
+    }
+
+    let rows: Vec<(i64, String, bool, Vec<u8>)> = query_as(
+        "SELECT version, description, success, checksum FROM _sqlx_migrations ORDER BY version",
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(
+            |(version, description, success, checksum)| AppliedMigration {
+                version,
+                description,
+                success,
+                checksum,
+            },
+        )
+        .collect())
+}
@@ -1,2 +1,5 @@
 pub mod admin;
+pub mod audit;
+pub mod builder;
 pub mod cosmetics;
+pub mod migrate;
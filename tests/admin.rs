@@ -0,0 +1,98 @@
+use kerria::environment::{LoginThrottle, SessionStore};
+use kerria::models::admin::{Permission, Role};
+
+#[test]
+fn test_role_permissions() {
+    assert!(Role::Viewer.has_permission(Permission::ReadCatalog));
+    assert!(!Role::Viewer.has_permission(Permission::DeleteCatalog));
+    assert!(!Role::CatalogEditor.has_permission(Permission::UpdatePrice));
+    assert!(Role::PricingManager.has_permission(Permission::UpdatePrice));
+    assert!(!Role::PricingManager.has_permission(Permission::WriteCatalog));
+    assert!(Role::Superuser.require(Permission::ManageUsers).is_ok());
+    assert!(Role::Superuser.require(Permission::PurgeCatalog).is_ok());
+    assert!(!Role::CatalogEditor.has_permission(Permission::PurgeCatalog));
+    assert!(Role::CatalogEditor
+        .require(Permission::ManageUsers)
+        .is_err());
+}
+
+#[test]
+fn test_role_names() {
+    // the cli takes the same names as the api
+    for role in [
+        Role::Viewer,
+        Role::CatalogEditor,
+        Role::PricingManager,
+        Role::Superuser,
+    ]
+    .iter()
+    {
+        let name = serde_json::to_value(role).unwrap();
+        assert_eq!(name, role.as_str());
+        assert_eq!(role.as_str().parse::<Role>().unwrap(), *role);
+    }
+    assert!("admin".parse::<Role>().is_err());
+}
+
+#[tokio::test]
+async fn test_login_throttle_locks_account() {
+    let throttle = LoginThrottle::memory();
+    for _ in 0..4 {
+        throttle.record_failure("alice", "10.0.0.1").await.unwrap();
+        assert!(throttle.check("alice", "10.0.0.1").await.is_ok());
+    }
+    throttle.record_failure("alice", "10.0.0.1").await.unwrap();
+    assert!(throttle.check("alice", "10.0.0.2").await.is_err());
+    assert!(throttle.check("bob", "10.0.0.1").await.is_ok());
+
+    throttle.record_success("alice").await.unwrap();
+    assert!(throttle.check("alice", "10.0.0.1").await.is_ok());
+}
+
+#[tokio::test]
+async fn test_session_rotate() {
+    let sessions = SessionStore::memory();
+    let session = sessions.create(1, "alice").await.unwrap();
+    assert!(sessions.is_active(&session.id).await.unwrap());
+
+    let rotated = sessions.rotate(&session.refresh_token).await.unwrap();
+    assert_eq!(rotated.id, session.id);
+    assert_eq!(rotated.username, "alice");
+    assert_ne!(rotated.refresh_token, session.refresh_token);
+    assert!(sessions.rotate("no-dot").await.is_err());
+
+    // replaying a rotated token ends the session for everyone holding it
+    assert!(sessions.rotate(&session.refresh_token).await.is_err());
+    assert!(!sessions.is_active(&session.id).await.unwrap());
+    assert!(sessions.rotate(&rotated.refresh_token).await.is_err());
+}
+
+#[tokio::test]
+async fn test_session_rotate_race() {
+    let sessions = SessionStore::memory();
+    let session = sessions.create(1, "alice").await.unwrap();
+    let (a, b) = futures::join!(
+        sessions.rotate(&session.refresh_token),
+        sessions.rotate(&session.refresh_token)
+    );
+    assert!(a.is_ok() != b.is_ok());
+}
+
+#[tokio::test]
+async fn test_session_revoke_user() {
+    let sessions = SessionStore::memory();
+    let first = sessions.create(1, "alice").await.unwrap();
+    let second = sessions.create(1, "alice").await.unwrap();
+    let other = sessions.create(2, "bob").await.unwrap();
+    // a rotated session still belongs to its user
+    let second = sessions.rotate(&second.refresh_token).await.unwrap();
+
+    sessions.revoke(&first.id).await.unwrap();
+    assert!(!sessions.is_active(&first.id).await.unwrap());
+    assert!(sessions.is_active(&second.id).await.unwrap());
+
+    sessions.revoke_user(1).await.unwrap();
+    assert!(!sessions.is_active(&second.id).await.unwrap());
+    assert!(sessions.rotate(&second.refresh_token).await.is_err());
+    assert!(sessions.is_active(&other.id).await.unwrap());
+}
@@ -0,0 +1,900 @@
+mod common;
+
+use kerria::api;
+use kerria::environment::{Environment, MemoryStorage, Storage};
+use kerria::handlers::admin::hash_password;
+use kerria::helpers::problem;
+use kerria::models::admin::Role;
+use serde_json::{json, Value};
+use sqlx::types::Decimal;
+use std::sync::Arc;
+use warp::hyper::StatusCode;
+use warp::Filter;
+
+use common::HOSTILE_NAMES;
+
+const ADMIN_PASSWORD: &str = "admin-password";
+
+/// The api on the memory repo signed in as a superuser. These tests cover the
+/// handlers only, the SQL the hostile names go through is run against MySQL
+/// in tests/sql.rs.
+async fn setup() -> (Environment, String) {
+    let env = Environment::memory("test-secret");
+    let password = hash_password(ADMIN_PASSWORD.as_bytes()).unwrap();
+    env.admin_users()
+        .create_user("admin", &password, Role::Superuser, "test")
+        .await
+        .unwrap();
+    let token = login(&env, "admin", ADMIN_PASSWORD).await.unwrap();
+    (env, token)
+}
+
+async fn send(
+    env: &Environment,
+    method: &str,
+    path: &str,
+    token: &str,
+    body: Option<Value>,
+) -> (StatusCode, Value) {
+    let filter = api::admin_filters(env.clone())
+        .or(api::cosmetics(env.clone()))
+        .recover(problem::unpack);
+    let mut req = warp::test::request().method(method).path(path);
+    if !token.is_empty() {
+        req = req.header("authorization", format!("Bearer {}", token));
+    }
+    if let Some(body) = body {
+        req = req.json(&body);
+    }
+    let resp = req.reply(&filter).await;
+    let body = serde_json::from_slice(resp.body()).unwrap_or(Value::Null);
+    (resp.status(), body)
+}
+
+async fn import_csv(env: &Environment, token: &str, csv: &str) -> (StatusCode, Value) {
+    let filter = api::admin_filters(env.clone()).recover(problem::unpack);
+    let resp = warp::test::request()
+        .method("POST")
+        .path("/admin/api/v1/cosmetics/products/import?format=csv&commit=true")
+        .header("authorization", format!("Bearer {}", token))
+        .body(csv.to_owned())
+        .reply(&filter)
+        .await;
+    let body = serde_json::from_slice(resp.body()).unwrap_or(Value::Null);
+    (resp.status(), body)
+}
+
+async fn login(env: &Environment, username: &str, password: &str) -> Option<String> {
+    let body = json!({ "username": username, "password": password });
+    let (status, resp) = send(env, "POST", "/admin/api/v1/login", "", Some(body)).await;
+    if status != StatusCode::OK {
+        return None;
+    }
+    resp["token"].as_str().map(str::to_owned)
+}
+
+async fn create_brands(env: &Environment, token: &str, names: &[&str]) -> Vec<u64> {
+    let brands: Vec<Value> = names.iter().map(|name| json!({ "name": name })).collect();
+    let path = "/admin/api/v1/cosmetics/brands";
+    let (status, _) = send(env, "POST", path, token, Some(json!(brands))).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let (_, resp) = send(env, "GET", path, token, None).await;
+    names
+        .iter()
+        .map(|name| {
+            resp["data"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|b| b["name"] == *name)
+                .and_then(|b| b["id"].as_u64())
+                .unwrap()
+        })
+        .collect()
+}
+
+fn new_product(name: &str, brand_name: &str, status: &str) -> Value {
+    json!({
+        "name": name,
+        "alias": "",
+        "title": format!("{} 50ml", name),
+        "subtitle": "",
+        "brand_name": brand_name,
+        "spec": "50ml",
+        "kind": 0,
+        "sell_price": "100.00",
+        "import_price": "60.00",
+        "sequence": 0,
+        "jd_id": "",
+        "jd_url": "",
+        "status": status,
+        "comment": "",
+    })
+}
+
+async fn create_product(env: &Environment, token: &str, product: Value) -> u64 {
+    let path = "/admin/api/v1/cosmetics/product";
+    let (status, resp) = send(env, "POST", path, token, Some(product)).await;
+    assert_eq!(status, StatusCode::CREATED);
+    resp["id"].as_u64().unwrap()
+}
+
+fn decimal(v: &Value) -> Decimal {
+    v.as_str().unwrap().parse().unwrap()
+}
+
+#[tokio::test]
+async fn test_auth_and_permissions() {
+    let (env, token) = setup().await;
+    let brands = "/admin/api/v1/cosmetics/brands";
+
+    let (status, _) = send(&env, "GET", brands, "", None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert!(login(&env, "admin", "wrong-password").await.is_none());
+    assert!(login(&env, "nobody", ADMIN_PASSWORD).await.is_none());
+
+    let viewer = json!({
+        "username": "viewer",
+        "password": "viewer-password",
+        "role": "viewer",
+    });
+    let (status, resp) = send(&env, "POST", "/admin/gen", &token, Some(viewer)).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(resp["username"], "viewer");
+
+    let viewer = login(&env, "viewer", "viewer-password").await.unwrap();
+    let (status, _) = send(&env, "GET", brands, &viewer, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let body = json!([{ "name": "Lancome" }]);
+    let (status, _) = send(&env, "POST", brands, &viewer, Some(body)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = send(&env, "GET", "/admin/api/v1/users", &viewer, None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, resp) = send(&env, "GET", "/admin/api/v1/user", &viewer, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(resp["role"], "viewer");
+}
+
+async fn login_from(env: &Environment, username: &str, peer: &str, forwarded: &str) -> StatusCode {
+    let filter = api::admin_filters(env.clone()).recover(problem::unpack);
+    warp::test::request()
+        .method("POST")
+        .path("/admin/api/v1/login")
+        .remote_addr(peer.parse().unwrap())
+        .header("x-forwarded-for", forwarded)
+        .json(&json!({ "username": username, "password": "wrong-password" }))
+        .reply(&filter)
+        .await
+        .status()
+}
+
+#[tokio::test]
+async fn test_forwarded_addr_needs_trusted_proxy() {
+    // a client can't dodge the address lock by making up forwarding headers
+    let (env, _) = setup().await;
+    for i in 0..20 {
+        let forwarded = format!("10.0.0.{}", i);
+        let status = login_from(&env, &format!("user{}", i), "192.0.2.1:4000", &forwarded).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+    let status = login_from(&env, "someone", "192.0.2.1:4000", "10.0.1.1").await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+
+    // behind the proxy every client has its own counter, a spoofed left-most
+    // entry doesn't count
+    let proxy = "192.0.2.9".parse().unwrap();
+    let env = setup().await.0.with_trusted_proxies(vec![proxy]);
+    for i in 0..20 {
+        let forwarded = format!("10.0.1.1, 10.0.0.{}", i);
+        let status = login_from(&env, &format!("user{}", i), "192.0.2.9:4000", &forwarded).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+    let status = login_from(&env, "someone", "192.0.2.9:4000", "10.0.0.1").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let status = login_from(&env, "someone", "192.0.2.9:4000", "10.0.0.1, 10.0.1.1").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_brand_lifecycle() {
+    let (env, token) = setup().await;
+    let ids = create_brands(&env, &token, HOSTILE_NAMES).await;
+
+    // names are stored as is, in the order they were created
+    let path = "/admin/api/v1/cosmetics/brands";
+    let (status, resp) = send(&env, "GET", path, &token, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(resp["total"], HOSTILE_NAMES.len());
+    let names: Vec<&str> = resp["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|b| b["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, HOSTILE_NAMES);
+
+    let body = json!([{ "name": HOSTILE_NAMES[0] }]);
+    let (status, _) = send(&env, "POST", path, &token, Some(body)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let path = format!("/admin/api/v1/cosmetics/brand/{}", ids[0]);
+    let body = json!({ "name": "O'Reilly Paris", "country": "France" });
+    let (status, _) = send(&env, "PUT", &path, &token, Some(body)).await;
+    assert_eq!(status, StatusCode::OK);
+    let body = json!({ "name": HOSTILE_NAMES[1] });
+    let (status, _) = send(&env, "PUT", &path, &token, Some(body)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let path = "/admin/api/v1/cosmetics/brands/sequence";
+    let body = json!([{ "id": ids[0], "sequence": 100 }]);
+    let (status, _) = send(&env, "PUT", path, &token, Some(body)).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, resp) = send(&env, "GET", "/api/v1/cosmetics/brands", "", None).await;
+    let last = resp["data"].as_array().unwrap().last().unwrap().clone();
+    assert_eq!(last["id"], ids[0]);
+    assert_eq!(last["name"], "O'Reilly Paris");
+    assert_eq!(last["country"], "France");
+
+    let path = "/admin/api/v1/cosmetics/brand/hot";
+    let body = json!([ids[2], ids[1], ids[2]]);
+    let (status, _) = send(&env, "POST", path, &token, Some(body)).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, resp) = send(&env, "GET", "/api/v1/cosmetics/brands/hot", "", None).await;
+    assert_eq!(resp["total"], 2);
+    assert_eq!(resp["data"][0]["id"], ids[2]);
+    assert_eq!(resp["data"][1]["id"], ids[1]);
+    let is_hot = |resp: &Value, id: u64| {
+        resp["data"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|b| b["id"] == id)
+            .map(|b| b["is_hot"].clone())
+    };
+    let (_, resp) = send(&env, "GET", "/api/v1/cosmetics/brands", "", None).await;
+    assert_eq!(is_hot(&resp, ids[2]), Some(json!(true)));
+    assert_eq!(is_hot(&resp, ids[0]), Some(json!(false)));
+
+    // replaced hot brands are only kept as history
+    let (status, _) = send(&env, "POST", path, &token, Some(json!([ids[1]]))).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, resp) = send(&env, "GET", "/api/v1/cosmetics/brands", "", None).await;
+    assert_eq!(is_hot(&resp, ids[1]), Some(json!(true)));
+    assert_eq!(is_hot(&resp, ids[2]), Some(json!(false)));
+
+    // soft delete, restore, then purge for good
+    let path = format!("/admin/api/v1/cosmetics/brand/{}", ids[3]);
+    let (status, _) = send(&env, "DELETE", &path, &token, None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let trash = "/admin/api/v1/cosmetics/trash/brands";
+    let (_, resp) = send(&env, "GET", trash, &token, None).await;
+    assert_eq!(resp["total"], 1);
+    assert_eq!(resp["data"][0]["name"], HOSTILE_NAMES[3]);
+    assert_eq!(resp["data"][0]["deleted_by"], "admin");
+
+    let restore = format!("{}/restore", path);
+    let (status, _) = send(&env, "PUT", &restore, &token, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let purge = format!("{}/purge", path);
+    let (status, _) = send(&env, "DELETE", &purge, &token, None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = send(&env, "DELETE", &path, &token, None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = send(&env, "DELETE", &purge, &token, None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (_, resp) = send(&env, "GET", trash, &token, None).await;
+    assert_eq!(resp["total"], 0);
+    let (_, resp) = send(&env, "GET", "/admin/api/v1/cosmetics/brands", &token, None).await;
+    assert_eq!(resp["total"], HOSTILE_NAMES.len() - 1);
+}
+
+#[tokio::test]
+async fn test_product_lifecycle() {
+    let (env, token) = setup().await;
+    create_brands(&env, &token, &["Lancome"]).await;
+    let id = create_product(&env, &token, new_product("Rose Cream", "Lancome", "draft")).await;
+    let admin = format!("/admin/api/v1/cosmetics/product/{}", id);
+    let public = format!("/api/v1/cosmetics/product/{}", id);
+
+    // drafts are hidden from the public api
+    let (status, resp) = send(&env, "GET", &public, "", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(resp, Value::Null);
+
+    let (status, _) = send(&env, "PUT", &format!("{}/publish", admin), &token, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, resp) = send(&env, "GET", &public, "", None).await;
+    assert_eq!(resp["name"], "Rose Cream");
+    assert_eq!(resp["brand_name"], "Lancome");
+    assert_eq!(resp["skus"].as_array().unwrap().len(), 1);
+
+    let price = json!({ "sell_price": "120.00", "import_price": "70.00" });
+    let path = format!("{}/price", admin);
+    let (status, _) = send(&env, "PUT", &path, &token, Some(price)).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, resp) = send(&env, "GET", &admin, &token, None).await;
+    assert_eq!(decimal(&resp["sell_price"]), Decimal::new(12000, 2));
+    assert_eq!(
+        decimal(&resp["skus"][0]["sell_price"]),
+        Decimal::new(12000, 2)
+    );
+
+    let sku = json!({
+        "spec": "100ml",
+        "barcode": "6901234567892",
+        "sell_price": "200.00",
+        "import_price": "120.00",
+    });
+    let path = format!("{}/skus", admin);
+    let (status, _) = send(&env, "POST", &path, &token, Some(sku.clone())).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let (status, _) = send(&env, "POST", &path, &token, Some(sku)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (_, resp) = send(&env, "GET", &path, &token, None).await;
+    assert_eq!(resp["total"], 2);
+    let audit = "/admin/api/v1/audit?entity_type=sku";
+    let (_, resp) = send(&env, "GET", audit, &token, None).await;
+    assert_eq!(resp["total"], 1);
+    assert_eq!(resp["data"][0]["action"], "create");
+
+    let (status, _) = send(&env, "PUT", &format!("{}/unpublish", admin), &token, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, resp) = send(&env, "GET", &public, "", None).await;
+    assert_eq!(resp, Value::Null);
+
+    // deleted products go to the trash and come back off shelf
+    let (status, _) = send(&env, "DELETE", &admin, &token, None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = send(&env, "PUT", &format!("{}/publish", admin), &token, None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let trash = "/admin/api/v1/cosmetics/trash/products";
+    let (_, resp) = send(&env, "GET", trash, &token, None).await;
+    assert_eq!(resp["total"], 1);
+    assert_eq!(resp["data"][0]["id"], id);
+
+    let (status, _) = send(&env, "PUT", &format!("{}/restore", admin), &token, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, resp) = send(&env, "GET", &admin, &token, None).await;
+    assert_eq!(resp["status"], "off_shelf");
+
+    let purge = format!("{}/purge", admin);
+    let (status, _) = send(&env, "DELETE", &purge, &token, None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    send(&env, "DELETE", &admin, &token, None).await;
+    let (status, _) = send(&env, "DELETE", &purge, &token, None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (_, resp) = send(&env, "GET", &admin, &token, None).await;
+    assert_eq!(resp, Value::Null);
+}
+
+#[tokio::test]
+async fn test_admin_update_resolves_brand() {
+    let (env, token) = setup().await;
+    let brand_ids = create_brands(&env, &token, &["Lancome", "Estee Lauder"]).await;
+    let id = create_product(&env, &token, new_product("Rose Cream", "Lancome", "draft")).await;
+    let admin = format!("/admin/api/v1/cosmetics/product/{}", id);
+    let (_, resp) = send(&env, "GET", &admin, &token, None).await;
+    assert_eq!(resp["brand_id"], brand_ids[0]);
+
+    // the brand id comes from the brand name, the body carries none
+    let product = new_product("Rose Cream", "Estee Lauder", "draft");
+    let (status, _) = send(&env, "PUT", &admin, &token, Some(product)).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, resp) = send(&env, "GET", &admin, &token, None).await;
+    assert_eq!(resp["brand_id"], brand_ids[1]);
+    assert_eq!(resp["brand_name"], "Estee Lauder");
+
+    let product = new_product("Rose Cream", "Nobody", "draft");
+    let (status, _) = send(&env, "PUT", &admin, &token, Some(product)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (_, resp) = send(&env, "GET", &admin, &token, None).await;
+    assert_eq!(resp["brand_id"], brand_ids[1]);
+}
+
+#[tokio::test]
+async fn test_product_trash() {
+    let (env, token) = setup().await;
+    let storage = Arc::new(MemoryStorage::new("/uploads"));
+    let env = env.with_storage(storage.clone());
+    let password = hash_password(b"pricer-password").unwrap();
+    env.admin_users()
+        .create_user("pricer", &password, Role::PricingManager, "test")
+        .await
+        .unwrap();
+    let pricer = login(&env, "pricer", "pricer-password").await.unwrap();
+    create_brands(&env, &token, &["Lancome"]).await;
+    let id = create_product(
+        &env,
+        &token,
+        new_product("Rose Cream", "Lancome", "off_shelf"),
+    )
+    .await;
+    let admin = format!("/admin/api/v1/cosmetics/product/{}", id);
+    let url = storage
+        .put("products/a.jpg", b"jpg".to_vec())
+        .await
+        .unwrap();
+    let thumb_url = storage
+        .put("products/a_thumb.jpg", b"jpg".to_vec())
+        .await
+        .unwrap();
+    env.products()
+        .create_product_images(id, &[(url, thumb_url)], "admin")
+        .await
+        .unwrap();
+
+    let price = json!({ "sell_price": "120.00", "import_price": "70.00" });
+    let path = format!("{}/price", admin);
+    let (status, _) = send(&env, "PUT", &path, &pricer, Some(price.clone())).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // a deleted product can't be edited, repriced or imported over
+    let (status, _) = send(&env, "DELETE", &admin, &token, None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = send(&env, "PUT", &path, &pricer, Some(price)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let product = new_product("Rose Cream", "Lancome", "off_shelf");
+    let (status, _) = send(&env, "PUT", &admin, &token, Some(product)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    for row_status in &["off_shelf", "2", ""] {
+        let csv = format!(
+            "id,name,title,brand_name,status,sell_price\n{},Rose Cream,Rose,Lancome,{},99.00\n",
+            id, row_status
+        );
+        let (status, resp) = import_csv(&env, &token, &csv).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(resp["committed"], false);
+        let message = resp["errors"][0]["message"].as_str().unwrap();
+        assert!(message.contains("回收站"), "{}", message);
+    }
+    let (_, resp) = send(&env, "GET", &admin, &token, None).await;
+    assert_eq!(resp["status"], "deleted");
+    assert_eq!(decimal(&resp["sell_price"]), Decimal::new(12000, 2));
+    let trash = "/admin/api/v1/cosmetics/trash/products";
+    let (_, resp) = send(&env, "GET", trash, &token, None).await;
+    assert_eq!(resp["data"][0]["id"], id);
+    assert_eq!(resp["data"][0]["deleted_by"], "admin");
+
+    // restoring takes it out of the trash
+    let (status, _) = send(&env, "PUT", &format!("{}/restore", admin), &token, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, resp) = send(&env, "GET", trash, &token, None).await;
+    assert_eq!(resp["total"], 0);
+    let (_, resp) = send(&env, "GET", &admin, &token, None).await;
+    assert_eq!(resp["status"], "off_shelf");
+    assert_eq!(decimal(&resp["sell_price"]), Decimal::new(12000, 2));
+    send(&env, "DELETE", &admin, &token, None).await;
+
+    // purging removes the image files but keeps the price history
+    let (status, _) = send(&env, "DELETE", &format!("{}/purge", admin), &token, None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert!(storage.get("products/a.jpg").is_none());
+    assert!(storage.get("products/a_thumb.jpg").is_none());
+    let (_, resp) = send(&env, "GET", trash, &token, None).await;
+    assert_eq!(resp["total"], 0);
+    let (_, resp) = send(&env, "GET", &format!("{}/prices", admin), &token, None).await;
+    assert!(resp["total"].as_u64().unwrap() > 0);
+}
+
+#[tokio::test]
+async fn test_product_status_input() {
+    let (env, token) = setup().await;
+    create_brands(&env, &token, &["Lancome"]).await;
+    let mut product = new_product("Rose Cream", "Lancome", "");
+    product.as_object_mut().unwrap().remove("status");
+    let id = create_product(&env, &token, product.clone()).await;
+    let admin = format!("/admin/api/v1/cosmetics/product/{}", id);
+    let (_, resp) = send(&env, "GET", &admin, &token, None).await;
+    assert_eq!(resp["status"], "draft");
+
+    // editing leaves the status alone, whatever the body says
+    product["status"] = json!("on_shelf");
+    let (status, _) = send(&env, "PUT", &admin, &token, Some(product.clone())).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, resp) = send(&env, "GET", &admin, &token, None).await;
+    assert_eq!(resp["status"], "draft");
+
+    // the numbers older clients send are still understood
+    product["status"] = json!(2);
+    let id = create_product(&env, &token, product.clone()).await;
+    let admin = format!("/admin/api/v1/cosmetics/product/{}", id);
+    let (_, resp) = send(&env, "GET", &admin, &token, None).await;
+    assert_eq!(resp["status"], "off_shelf");
+    product["status"] = json!(7);
+    let path = "/admin/api/v1/cosmetics/product";
+    let (status, _) = send(&env, "POST", path, &token, Some(product)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_default_sku_needs_price_permission() {
+    let (env, token) = setup().await;
+    create_brands(&env, &token, &["Lancome"]).await;
+    let id = create_product(
+        &env,
+        &token,
+        new_product("Rose Cream", "Lancome", "on_shelf"),
+    )
+    .await;
+    let editor = json!({
+        "username": "editor",
+        "password": "editor-password",
+        "role": "catalog_editor",
+    });
+    send(&env, "POST", "/admin/api/v1/users", &token, Some(editor)).await;
+    let editor = login(&env, "editor", "editor-password").await.unwrap();
+
+    let admin = format!("/admin/api/v1/cosmetics/product/{}", id);
+    let skus = format!("{}/skus", admin);
+    let (_, resp) = send(&env, "GET", &skus, &token, None).await;
+    let default_id = resp["data"][0]["id"].as_u64().unwrap();
+
+    // an editor may add skus after the default one, but not in front of it
+    let sku = json!({ "spec": "100ml", "sell_price": "180.00", "import_price": "90.00" });
+    let (status, resp) = send(&env, "POST", &skus, &editor, Some(sku)).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let sku_id = resp["id"].as_u64().unwrap();
+    let sku = json!({
+        "spec": "30ml",
+        "sell_price": "60.00",
+        "import_price": "30.00",
+        "sequence": -1,
+    });
+    let (status, _) = send(&env, "POST", &skus, &editor, Some(sku.clone())).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let moved = json!({
+        "spec": "100ml",
+        "sell_price": "180.00",
+        "import_price": "90.00",
+        "sequence": -1,
+    });
+    let path = format!("{}/sku/{}", admin, sku_id);
+    let (status, _) = send(&env, "PUT", &path, &editor, Some(moved)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    // deleting the default sku hands its place and prices to the next one
+    let default = format!("{}/sku/{}", admin, default_id);
+    let (status, _) = send(&env, "DELETE", &default, &editor, None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = send(&env, "DELETE", &default, &token, None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (_, resp) = send(&env, "GET", &admin, &token, None).await;
+    assert_eq!(decimal(&resp["sell_price"]), Decimal::new(18000, 2));
+
+    let (status, resp) = send(&env, "POST", &skus, &token, Some(sku)).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let first = format!("{}/sku/{}", admin, resp["id"].as_u64().unwrap());
+    let (status, _) = send(&env, "DELETE", &path, &editor, None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    // a disabled sku gives the default up to the first one on sale
+    let sku = json!({ "spec": "50ml", "sell_price": "120.00", "import_price": "60.00" });
+    let (status, _) = send(&env, "POST", &skus, &editor, Some(sku)).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let disabled = json!({
+        "spec": "30ml",
+        "sell_price": "60.00",
+        "import_price": "30.00",
+        "sequence": -1,
+        "status": "disabled",
+    });
+    let (status, _) = send(&env, "PUT", &first, &editor, Some(disabled.clone())).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = send(&env, "PUT", &first, &token, Some(disabled)).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, resp) = send(&env, "GET", &admin, &token, None).await;
+    assert_eq!(resp["spec"], "50ml");
+    assert_eq!(decimal(&resp["sell_price"]), Decimal::new(12000, 2));
+}
+
+#[tokio::test]
+async fn test_hot_lists_and_search() {
+    let (env, token) = setup().await;
+    create_brands(&env, &token, &["Lancome"]).await;
+    let rose = create_product(
+        &env,
+        &token,
+        new_product("Rose Cream", "Lancome", "on_shelf"),
+    )
+    .await;
+    let lily = create_product(
+        &env,
+        &token,
+        new_product("Lily Toner", "Lancome", "on_shelf"),
+    )
+    .await;
+
+    let body = json!({ "slot": "home", "product_ids": [lily, rose, lily] });
+    let path = "/admin/api/v1/cosmetics/hot/lists";
+    let (status, resp) = send(&env, "POST", path, &token, Some(body)).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let list_id = resp["id"].as_u64().unwrap();
+    let (_, resp) = send(&env, "GET", "/api/v1/cosmetics/hot?slot=home", "", None).await;
+    assert_eq!(resp["total"], 2);
+    assert_eq!(resp["data"][0]["id"], lily);
+    assert_eq!(resp["data"][1]["id"], rose);
+    assert_eq!(resp["data"][1]["brand_name"], "Lancome");
+    let (_, resp) = send(&env, "GET", "/api/v1/cosmetics/hot?slot=sale", "", None).await;
+    assert_eq!(resp["total"], 0);
+
+    let path = format!("/admin/api/v1/cosmetics/product/{}/unpublish", lily);
+    send(&env, "PUT", &path, &token, None).await;
+    let (_, resp) = send(&env, "GET", "/api/v1/cosmetics/hot", "", None).await;
+    assert_eq!(resp["total"], 1);
+    assert_eq!(resp["data"][0]["id"], rose);
+
+    let (_, resp) = send(&env, "GET", "/api/v1/cosmetics/search?q=rose", "", None).await;
+    assert_eq!(resp["total"], 1);
+    assert_eq!(resp["data"][0]["id"], rose);
+    let (_, resp) = send(&env, "GET", "/api/v1/cosmetics/search?q=lily", "", None).await;
+    assert_eq!(resp["total"], 0);
+    let (_, resp) = send(&env, "GET", "/api/v1/cosmetics/search?q=lancome", "", None).await;
+    assert_eq!(resp["total"], 1);
+
+    // a product shown by a hot list can't be purged
+    let admin = format!("/admin/api/v1/cosmetics/product/{}", rose);
+    send(&env, "DELETE", &admin, &token, None).await;
+    let purge = format!("{}/purge", admin);
+    let (status, _) = send(&env, "DELETE", &purge, &token, None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let path = format!("/admin/api/v1/cosmetics/hot/list/{}", list_id);
+    let (status, _) = send(&env, "DELETE", &path, &token, None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = send(&env, "DELETE", &purge, &token, None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+}
+
+#[tokio::test]
+async fn test_export_brands_streams_every_page() {
+    let (env, token) = setup().await;
+    let names: Vec<String> = (1..=130).map(|i| format!("Brand {:03}", i)).collect();
+    let names: Vec<&str> = names.iter().map(String::as_str).collect();
+    create_brands(&env, &token, &names).await;
+
+    let filter = api::admin_filters(env.clone()).recover(problem::unpack);
+    let resp = warp::test::request()
+        .method("GET")
+        .path("/admin/api/v1/cosmetics/brands/export?format=csv")
+        .header("authorization", format!("Bearer {}", token))
+        .reply(&filter)
+        .await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let csv = String::from_utf8(resp.body().to_vec()).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    // the header, then every brand once in order across the pages
+    assert_eq!(lines.len(), names.len() + 1);
+    for (line, name) in lines[1..].iter().zip(names.iter()) {
+        assert!(line.contains(&format!(",{},", name)));
+    }
+}
+
+#[tokio::test]
+async fn test_product_hidden_with_its_brand() {
+    let (env, token) = setup().await;
+    let brand_ids = create_brands(&env, &token, &["Lancome"]).await;
+    let id = create_product(
+        &env,
+        &token,
+        new_product("Rose Cream", "Lancome", "on_shelf"),
+    )
+    .await;
+    let path = format!("/admin/api/v1/cosmetics/brand/{}", brand_ids[0]);
+    let (status, _) = send(&env, "DELETE", &path, &token, None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    // still on shelf, but the public api no longer shows it
+    let public = format!("/api/v1/cosmetics/product/{}", id);
+    let (_, resp) = send(&env, "GET", &public, "", None).await;
+    assert_eq!(resp, Value::Null);
+    let (_, resp) = send(&env, "GET", &format!("{}/prices", public), "", None).await;
+    assert_eq!(resp["total"], 0);
+    let body = json!({ "slot": "home", "product_ids": [id] });
+    let path = "/admin/api/v1/cosmetics/hot/lists";
+    let (status, _) = send(&env, "POST", path, &token, Some(body)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_replace_home_list() {
+    let (env, token) = setup().await;
+    create_brands(&env, &token, &["Lancome"]).await;
+    let rose = create_product(
+        &env,
+        &token,
+        new_product("Rose Cream", "Lancome", "on_shelf"),
+    )
+    .await;
+    let lily = create_product(
+        &env,
+        &token,
+        new_product("Lily Toner", "Lancome", "on_shelf"),
+    )
+    .await;
+    let lists = "/admin/api/v1/cosmetics/hot/lists?slot=home";
+    let body = json!({ "product_ids": [rose], "start_at": "2099-01-01T00:00:00" });
+    let (status, _) = send(&env, "POST", lists, &token, Some(body)).await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    // the legacy page replaces the live list instead of stacking up new ones
+    let path = "/admin/api/v1/cosmetics/product/hot";
+    for ids in [vec![rose], vec![lily, rose]].iter() {
+        let (status, _) = send(&env, "POST", path, &token, Some(json!(ids))).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+    let (_, resp) = send(&env, "GET", lists, &token, None).await;
+    assert_eq!(resp["total"], 2);
+    assert_eq!(resp["data"][0]["start_at"], "2099-01-01T00:00:00");
+    assert_eq!(resp["data"][1]["product_ids"], json!([lily, rose]));
+    assert!(resp["data"][1]["end_at"].is_null());
+    let (_, resp) = send(&env, "GET", "/api/v1/cosmetics/hot", "", None).await;
+    assert_eq!(resp["total"], 2);
+    assert_eq!(resp["data"][0]["id"], lily);
+
+    let (_, resp) = send(
+        &env,
+        "GET",
+        "/admin/api/v1/audit?entity_type=hot_product",
+        &token,
+        None,
+    )
+    .await;
+    assert_eq!(resp["total"], 4);
+}
+
+#[tokio::test]
+async fn test_categories() {
+    let (env, token) = setup().await;
+    create_brands(&env, &token, &["Lancome"]).await;
+    let rose = create_product(
+        &env,
+        &token,
+        new_product("Rose Cream", "Lancome", "on_shelf"),
+    )
+    .await;
+
+    let path = "/admin/api/v1/cosmetics/categories";
+    let (status, resp) = send(&env, "POST", path, &token, Some(json!({ "name": "护肤" }))).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let skin = resp["id"].as_u64().unwrap();
+    let body = json!({ "name": "面霜", "parent_id": skin });
+    let (_, resp) = send(&env, "POST", path, &token, Some(body)).await;
+    let cream = resp["id"].as_u64().unwrap();
+
+    let (_, resp) = send(&env, "GET", "/api/v1/cosmetics/categories", "", None).await;
+    assert_eq!(resp["total"], 2);
+    assert_eq!(resp["data"][0]["name"], "护肤");
+    assert_eq!(resp["data"][0]["children"][0]["id"], cream);
+
+    // a category can't move under itself
+    let path = format!("/admin/api/v1/cosmetics/category/{}", skin);
+    let body = json!({ "name": "护肤", "parent_id": cream });
+    let (status, _) = send(&env, "PUT", &path, &token, Some(body)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let categories = format!("/admin/api/v1/cosmetics/product/{}/categories", rose);
+    let (status, _) = send(&env, "PUT", &categories, &token, Some(json!([cream]))).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, resp) = send(&env, "GET", &categories, &token, None).await;
+    assert_eq!(resp["data"], json!([cream]));
+    let (status, _) = send(&env, "PUT", &categories, &token, Some(json!([999]))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // the products of the sub categories are included
+    let public = format!("/api/v1/cosmetics/category/{}/products", skin);
+    let (_, resp) = send(&env, "GET", &public, "", None).await;
+    assert_eq!(resp["total"], 1);
+    assert_eq!(resp["data"][0]["id"], rose);
+
+    let (status, _) = send(&env, "DELETE", &path, &token, None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let child = format!("/admin/api/v1/cosmetics/category/{}", cream);
+    let (status, _) = send(&env, "DELETE", &child, &token, None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = send(&env, "DELETE", &path, &token, None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+}
+
+#[tokio::test]
+async fn test_users_and_audit_logs() {
+    let (env, token) = setup().await;
+    let editor = json!({
+        "username": "editor",
+        "password": "editor-password",
+        "role": "catalog_editor",
+    });
+    let path = "/admin/api/v1/users";
+    let (status, resp) = send(&env, "POST", path, &token, Some(editor.clone())).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let id = resp["id"].as_u64().unwrap();
+    let (status, _) = send(&env, "POST", path, &token, Some(editor)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (_, resp) = send(&env, "GET", path, &token, None).await;
+    assert_eq!(resp["total"], 2);
+
+    let editor = login(&env, "editor", "editor-password").await.unwrap();
+    create_brands(&env, &editor, &["Lancome"]).await;
+    let (_, resp) = send(
+        &env,
+        "GET",
+        "/admin/api/v1/audit?entity_type=brand",
+        &token,
+        None,
+    )
+    .await;
+    assert_eq!(resp["total"], 1);
+    assert_eq!(resp["data"][0]["operator"], "editor");
+    assert_eq!(resp["data"][0]["action"], "create");
+    assert_eq!(resp["data"][0]["after_data"]["name"], "Lancome");
+
+    // disabling signs the user out and keeps them from signing in again
+    let user = format!("{}/{}", path, id);
+    let (status, _) = send(&env, "PUT", &format!("{}/disable", user), &token, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = send(&env, "GET", "/admin/api/v1/cosmetics/brands", &editor, None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert!(login(&env, "editor", "editor-password").await.is_none());
+    let (_, resp) = send(&env, "GET", &user, &token, None).await;
+    assert_eq!(resp["status"], "disabled");
+
+    let (status, _) = send(&env, "PUT", &format!("{}/enable", user), &token, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(login(&env, "editor", "editor-password").await.is_some());
+}
+
+#[tokio::test]
+async fn test_user_management() {
+    let (env, token) = setup().await;
+    let path = "/admin/api/v1/users";
+    for username in &["", "name#1", "a-name-longer-than-thirty-two-chars"] {
+        let body = json!({ "username": username, "password": "viewer-password" });
+        let (status, _) = send(&env, "POST", path, &token, Some(body)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+    let viewer = json!({ "username": "viewer", "password": "viewer-password" });
+    let (_, resp) = send(&env, "POST", path, &token, Some(viewer.clone())).await;
+    let id = resp["id"].as_u64().unwrap();
+    let user = format!("{}/{}", path, id);
+
+    let body = json!({ "role": "pricing_manager" });
+    let (status, _) = send(&env, "PUT", &user, &token, Some(body)).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, resp) = send(&env, "GET", &user, &token, None).await;
+    assert_eq!(resp["role"], "pricing_manager");
+
+    // a reset password has to be changed at the next sign in
+    let body = json!({ "password": "reset-password" });
+    let (status, resp) = send(
+        &env,
+        "PUT",
+        &format!("{}/password", user),
+        &token,
+        Some(body),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(resp["password"], "reset-password");
+    assert!(login(&env, "viewer", "viewer-password").await.is_none());
+    let (_, resp) = send(&env, "GET", &user, &token, None).await;
+    assert_eq!(resp["must_change_password"], true);
+
+    let (_, resp) = send(&env, "GET", path, &token, None).await;
+    let own = format!("{}/{}", path, resp["data"][0]["id"]);
+    let (status, _) = send(&env, "DELETE", &own, &token, None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = send(&env, "DELETE", &user, &token, None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = send(&env, "GET", &user, &token, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert!(login(&env, "viewer", "reset-password").await.is_none());
+
+    // the name of a deleted user can be taken again
+    let (status, resp) = send(&env, "POST", path, &token, Some(viewer)).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_ne!(resp["id"].as_u64().unwrap(), id);
+    assert!(login(&env, "viewer", "viewer-password").await.is_some());
+    let (_, resp) = send(&env, "GET", path, &token, None).await;
+    assert_eq!(resp["total"], 2);
+}
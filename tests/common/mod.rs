@@ -0,0 +1,12 @@
+/// Names that break a query built by pasting them in, they are all legit
+/// brand names and have to be stored as is.
+pub const HOSTILE_NAMES: &[&str] = &[
+    "O'Reilly",
+    "\"Quoted\"",
+    "back\\slash",
+    "'); DROP TABLE brand; --",
+    "' OR '1'='1",
+    "what?",
+    "100%",
+    "雅诗兰黛'",
+];
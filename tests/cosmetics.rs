@@ -1,4 +1,16 @@
+use chrono::NaiveDate;
 use kerria::api;
+use kerria::environment::{LocalStorage, Storage};
+use kerria::helpers::spreadsheet;
+use kerria::helpers::upload::{self, UploadFile};
+use kerria::models::audit;
+use kerria::models::cosmetics::{
+    build_category_tree, Category, FileFormat, NewHotList, NewProduct, NewSku, PriceGranularity,
+    PriceRecord, ProductItem, ProductStatus, SkuStatus,
+};
+use kerria::models::{Cursor, Validate};
+use serde_json::json;
+use sqlx::types::Decimal;
 use warp::hyper::StatusCode;
 
 #[tokio::test]
@@ -12,3 +24,335 @@ async fn test_status_ok() {
 
     assert_eq!(resp.status(), StatusCode::OK);
 }
+
+#[test]
+fn test_audit_diff() {
+    let before = json!({ "name": "面霜", "sell_price": "100.00" });
+    let after = json!({ "name": "面霜", "sell_price": "120.00" });
+    let diff = audit::diff(Some(&before), Some(&after)).unwrap();
+    assert_eq!(
+        diff,
+        json!({ "sell_price": { "from": "100.00", "to": "120.00" } })
+    );
+    assert_eq!(audit::diff(None, None), None);
+}
+
+fn price_record(day: u32, price: i64) -> PriceRecord {
+    PriceRecord {
+        sell_price: Decimal::new(price, 0),
+        import_price: Decimal::new(price / 2, 0),
+        price_time: NaiveDate::from_ymd(2021, 1, day).and_hms(10, 0, 0),
+    }
+}
+
+fn category(id: u64, parent_id: u64, name: &str) -> Category {
+    Category {
+        id,
+        parent_id,
+        path: String::new(),
+        name: name.to_owned(),
+        sequence: id as i32,
+    }
+}
+
+fn product_item(id: u64, name: &str) -> ProductItem {
+    ProductItem {
+        id,
+        name: name.to_owned(),
+        alias: String::new(),
+        title: String::new(),
+        subtitle: String::new(),
+        brand_id: 3,
+        brand_name: "阿玛尼".to_owned(),
+        spec: String::new(),
+        kind: 1,
+        sell_price: Decimal::new(32000, 2),
+        import_price: Decimal::new(21050, 2),
+        sequence: 0,
+        jd_id: String::new(),
+        jd_url: String::new(),
+        img_url: String::new(),
+        status: ProductStatus::OnShelf,
+        comment: String::new(),
+    }
+}
+
+fn hot_list(slot: &str, start: Option<&str>, end: Option<&str>) -> NewHotList {
+    NewHotList {
+        slot: slot.to_owned(),
+        product_ids: vec![1, 2],
+        start_at: start.map(|s| s.parse().unwrap()),
+        end_at: end.map(|s| s.parse().unwrap()),
+    }
+}
+
+fn sku(barcode: &str, status: SkuStatus) -> NewSku {
+    NewSku {
+        spec: "30ml".to_owned(),
+        kind: 0,
+        barcode: barcode.to_owned(),
+        sell_price: "199.00".parse().unwrap(),
+        import_price: "120.00".parse().unwrap(),
+        sequence: 0,
+        status,
+    }
+}
+
+#[test]
+fn test_price_weekly_aggregation() {
+    // 2021-01-04 is a Monday
+    let records = vec![
+        price_record(4, 100),
+        price_record(6, 80),
+        price_record(8, 90),
+        price_record(12, 120),
+    ];
+    let points = PriceGranularity::Week.aggregate(&records, false);
+    assert_eq!(points.len(), 2);
+    assert_eq!(
+        points[0].price_time,
+        NaiveDate::from_ymd(2021, 1, 4).and_hms(0, 0, 0)
+    );
+    assert_eq!(points[0].sell_price, Decimal::new(90, 0));
+    assert_eq!(points[0].min_sell_price, Decimal::new(80, 0));
+    assert_eq!(points[0].max_sell_price, Decimal::new(100, 0));
+    assert_eq!(points[0].import_price, None);
+    assert_eq!(points[1].sell_price, Decimal::new(120, 0));
+}
+
+#[test]
+fn test_build_category_tree() {
+    let categories = vec![
+        category(1, 0, "护肤"),
+        category(2, 0, "彩妆"),
+        category(3, 1, "精华"),
+        category(4, 2, "口红"),
+        category(5, 1, "面霜"),
+    ];
+    let tree = build_category_tree(&categories);
+    assert_eq!(tree.len(), 2);
+    assert_eq!(tree[0].name, "护肤");
+    let children: Vec<u64> = tree[0].children.iter().map(|c| c.id).collect();
+    assert_eq!(children, vec![3, 5]);
+    assert_eq!(tree[1].children[0].name, "口红");
+}
+
+#[test]
+fn test_cursor_roundtrip() {
+    let cursor = Cursor::new(-3, 42);
+    assert_eq!(Cursor::decode(&cursor.encode()).unwrap(), cursor);
+    assert!(Cursor::decode("not a cursor").is_err());
+}
+
+#[test]
+fn test_import_csv_rows() {
+    let data = "\u{feff}id,name,title,brand_name,sell_price,kind\n\
+                ,精华液,修护精华,雅诗兰黛,99.90,1\n\
+                ,,,,,\n\
+                12,面霜,,兰蔻,abc,1.0\n";
+    let rows = spreadsheet::read_rows(FileFormat::Csv, data.as_bytes()).unwrap();
+    assert_eq!(rows.len(), 2);
+
+    let (line, row) = &rows[0];
+    assert_eq!(*line, 2);
+    let product = NewProduct::from_row(row).unwrap();
+    assert_eq!(product.id, None);
+    assert_eq!(product.brand_name, "雅诗兰黛");
+    assert_eq!(product.sell_price, Decimal::new(9990, 2));
+    assert_eq!(product.status, ProductStatus::Draft);
+
+    let (line, row) = &rows[1];
+    assert_eq!(*line, 4);
+    assert!(NewProduct::from_row(row).is_err());
+}
+
+#[test]
+fn test_import_row_out_of_range() {
+    // numbers that don't fit are rejected instead of wrapping around
+    let data = "name,brand_name,kind,sequence
+                精华液,雅诗兰黛,257,1
+                精华液,雅诗兰黛,-1,1
+                精华液,雅诗兰黛,1,4294967297
+                精华液,雅诗兰黛,255,-2147483648
+";
+    let rows = spreadsheet::read_rows(FileFormat::Csv, data.as_bytes()).unwrap();
+    assert_eq!(rows.len(), 4);
+    for (_, row) in rows[..3].iter() {
+        assert!(NewProduct::from_row(row).is_err());
+    }
+    let product = NewProduct::from_row(&rows[3].1).unwrap();
+    assert_eq!(product.kind, 255);
+    assert_eq!(product.sequence, i32::MIN);
+}
+
+#[test]
+fn test_export_escapes_formulas() {
+    let mut product = product_item(8, "=HYPERLINK(\"http://evil\")");
+    product.alias = "@SUM(A1)".to_owned();
+    product.title = "+cmd".to_owned();
+    product.subtitle = "-2+3".to_owned();
+    product.sequence = -3;
+    let data = spreadsheet::write_all(FileFormat::Csv, "products", &[product]).unwrap();
+    let text = String::from_utf8(data.clone()).unwrap();
+    assert!(text.contains("\"'=HYPERLINK(\"\"http://evil\"\")\""));
+    assert!(text.contains(",'@SUM(A1),'+cmd,'-2+3,"));
+    assert!(text.contains(",-3,"));
+
+    // the quote is dropped again on import
+    let rows = spreadsheet::read_rows(FileFormat::Csv, &data).unwrap();
+    let imported = NewProduct::from_row(&rows[0].1).unwrap();
+    assert_eq!(imported.name, "=HYPERLINK(\"http://evil\")");
+    assert_eq!(imported.alias, "@SUM(A1)");
+    assert_eq!(imported.subtitle, "-2+3");
+    assert_eq!(imported.sequence, -3);
+
+    assert!(spreadsheet::read_rows(FileFormat::Jsonl, b"{\"name\": \"x\"}\n").is_err());
+}
+
+#[test]
+fn test_export_csv_reimport() {
+    let mut product = product_item(7, "口红, 哑光");
+    product.title = "丝绒\"小金条\"".to_owned();
+    product.spec = "3.5g".to_owned();
+    product.sequence = 2;
+    product.status = ProductStatus::OffShelf;
+    let data = spreadsheet::write_all(FileFormat::Csv, "products", &[product]).unwrap();
+    let rows = spreadsheet::read_rows(FileFormat::Csv, &data).unwrap();
+    assert_eq!(rows.len(), 1);
+    let imported = NewProduct::from_row(&rows[0].1).unwrap();
+    assert_eq!(imported.id, Some(7));
+    assert_eq!(imported.name, "口红, 哑光");
+    assert_eq!(imported.title, "丝绒\"小金条\"");
+    assert_eq!(imported.import_price, Decimal::new(21050, 2));
+    assert_eq!(imported.status, ProductStatus::OffShelf);
+}
+
+#[tokio::test]
+async fn test_local_storage() {
+    let root = std::env::temp_dir().join("kerria-storage-test");
+    let storage = LocalStorage::new(&root, "/uploads/");
+    let url = storage
+        .put("products/1/cover.jpg", b"jpg".to_vec())
+        .await
+        .unwrap();
+    assert_eq!(url, "/uploads/products/1/cover.jpg");
+    assert!(root.join("products/1/cover.jpg").exists());
+    assert_eq!(storage.key_of(&url).unwrap(), "products/1/cover.jpg");
+    assert!(storage.key_of("/uploadsx/cover.jpg").is_none());
+    assert!(storage
+        .key_of("https://cdn.example.com/cover.jpg")
+        .is_none());
+    assert!(storage.put("../escape.jpg", vec![]).await.is_err());
+
+    storage.delete("products/1/cover.jpg").await.unwrap();
+    assert!(!root.join("products/1/cover.jpg").exists());
+    // deleting a missing file is fine
+    storage.delete("products/1/cover.jpg").await.unwrap();
+}
+
+fn png(width: u32, height: u32) -> Vec<u8> {
+    let mut data = vec![];
+    image::DynamicImage::new_rgb8(1, 1)
+        .write_to(&mut data, image::ImageOutputFormat::Png)
+        .unwrap();
+    // patch the size in the header, it is all a decoder looks at before
+    // allocating the bitmap
+    data[16..20].copy_from_slice(&width.to_be_bytes());
+    data[20..24].copy_from_slice(&height.to_be_bytes());
+    let crc = data[12..29].iter().fold(!0u32, |crc, b| {
+        (0..8).fold(crc ^ u32::from(*b), |crc, _| {
+            (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg())
+        })
+    });
+    data[29..33].copy_from_slice(&(!crc).to_be_bytes());
+    data
+}
+
+fn png_file(data: Vec<u8>) -> UploadFile {
+    UploadFile {
+        content_type: "image/png".to_owned(),
+        data,
+    }
+}
+
+#[test]
+fn test_process_image() {
+    let image = upload::process_image(png_file(png(1, 1))).unwrap();
+    assert_eq!(image.ext, "png");
+    assert!(!image.thumb.is_empty());
+
+    // a few hundred bytes claiming a 6000x5000 bitmap are turned down
+    // before decoding
+    let err = upload::process_image(png_file(png(6000, 5000))).unwrap_err();
+    assert!(err.to_string().contains("6000x5000"));
+
+    let mut gif = png_file(png(1, 1));
+    gif.content_type = "image/gif".to_owned();
+    assert!(upload::process_image(gif).is_err());
+}
+
+#[test]
+fn test_hot_list_validate() {
+    let cases = [
+        ("new_arrivals", None, None, true),
+        ("Home Banner", None, None, false),
+        (
+            "seasonal_promo",
+            Some("2021-03-01T00:00:00"),
+            Some("2021-03-08T00:00:00"),
+            true,
+        ),
+        (
+            "seasonal_promo",
+            Some("2021-03-08T00:00:00"),
+            Some("2021-03-01T00:00:00"),
+            false,
+        ),
+    ];
+    for (slot, start, end, ok) in cases.iter() {
+        let valid = hot_list(slot, *start, *end).validate().is_ok();
+        assert_eq!(valid, *ok, "{} {:?}", slot, start);
+    }
+}
+
+#[test]
+fn test_sku_validate() {
+    let cases = [
+        ("", SkuStatus::OnSale, true),
+        ("6901234567892", SkuStatus::Disabled, true),
+        ("69012345", SkuStatus::OnSale, true),
+        ("690123456789a", SkuStatus::OnSale, false),
+        ("123", SkuStatus::OnSale, false),
+        ("", SkuStatus::Deleted, false),
+    ];
+    for (barcode, status, ok) in cases.iter() {
+        let valid = sku(barcode, *status).validate().is_ok();
+        assert_eq!(valid, *ok, "{:?} {:?}", barcode, status);
+    }
+
+    let mut blank = sku("", SkuStatus::OnSale);
+    blank.spec = " ".to_owned();
+    assert!(blank.validate().is_err());
+}
+
+#[test]
+fn test_product_status_transitions() {
+    use ProductStatus::*;
+    assert!(Draft.can_transition_to(OnShelf));
+    assert!(OnShelf.can_transition_to(OffShelf));
+    assert!(OffShelf.can_transition_to(OnShelf));
+    assert!(OffShelf.can_transition_to(Deleted));
+    assert!(!OnShelf.can_transition_to(Draft));
+    assert!(!OffShelf.can_transition_to(Draft));
+    assert!(!Deleted.can_transition_to(OnShelf));
+    // keeping the status is not a transition
+    assert!(OnShelf.transition_to(OnShelf).is_ok());
+    // only the trash brings a deleted product back
+    assert!(Deleted.transition_to(OffShelf).is_err());
+    assert!(Deleted.transition_to(OnShelf).is_err());
+    assert!(OnShelf.is_visible() && !Draft.is_visible() && !OffShelf.is_visible());
+
+    assert_eq!("off_shelf".parse::<ProductStatus>().unwrap(), OffShelf);
+    assert_eq!("0".parse::<ProductStatus>().unwrap(), OnShelf);
+    assert!("published".parse::<ProductStatus>().is_err());
+}
@@ -0,0 +1,155 @@
+mod common;
+
+use kerria::models::cosmetics::{Brand, NewBrand, NewHotList, DEFAULT_HOT_SLOT};
+use kerria::models::Validate;
+use kerria::sql;
+use kerria::sql::builder::QueryBuilder;
+use kerria::sql::migrate;
+use sqlx::{MySqlPool, Row};
+
+use common::HOSTILE_NAMES;
+
+#[test]
+fn test_builder_in_list() {
+    let mut qb = QueryBuilder::new("SELECT DISTINCT name FROM brand WHERE name IN ");
+    qb.push_in(HOSTILE_NAMES.iter().copied())
+        .push(" AND status = ")
+        .push_bind(0i8);
+    let placeholders = vec!["?"; HOSTILE_NAMES.len()].join(", ");
+    assert_eq!(
+        qb.sql(),
+        format!(
+            "SELECT DISTINCT name FROM brand WHERE name IN ({}) AND status = ?",
+            placeholders
+        )
+    );
+    assert_eq!(qb.binds(), HOSTILE_NAMES.len() + 1);
+
+    let mut empty = QueryBuilder::new("SELECT id FROM brand WHERE id IN ");
+    empty.push_in(Vec::<u64>::new());
+    assert_eq!(empty.sql(), "SELECT id FROM brand WHERE id IN (NULL)");
+    assert_eq!(empty.binds(), 0);
+}
+
+#[test]
+fn test_builder_batch_insert() {
+    let operator = "admin'--";
+    let mut qb = QueryBuilder::new("INSERT INTO brand (`name`, `sequence`, `creator`) VALUES ");
+    qb.push_values(HOSTILE_NAMES.iter().enumerate(), |row, (i, name)| {
+        row.bind(*name).bind(i as i32 + 1).bind(operator);
+    });
+    let rows = vec!["(?, ?, ?)"; HOSTILE_NAMES.len()].join(", ");
+    assert_eq!(
+        qb.sql(),
+        format!(
+            "INSERT INTO brand (`name`, `sequence`, `creator`) VALUES {}",
+            rows
+        )
+    );
+    assert_eq!(qb.binds(), HOSTILE_NAMES.len() * 3);
+    // nothing from the values makes it into the statement text
+    for name in HOSTILE_NAMES.iter().chain(&[operator]) {
+        assert!(!qb.sql().contains(name));
+    }
+}
+
+#[test]
+fn test_hostile_brand_names_are_valid_input() {
+    // quotes and the like are legit in names, they have to be stored as is
+    for name in HOSTILE_NAMES {
+        let brand = NewBrand {
+            name: name.to_string(),
+        };
+        assert!(brand.validate().is_ok());
+    }
+}
+
+#[test]
+fn test_every_migration_has_a_down_script() {
+    assert_eq!(migrate::irreversible(), Vec::<i64>::new());
+    // and no down script is left over from a renamed migration
+    assert_eq!(
+        migrate::down_migrator().iter().count(),
+        migrate::migrator().iter().count()
+    );
+}
+
+/// The scratch database of `TEST_DATABASE_URL`, brought up to date beforehand
+/// with `kerria migrate up`. The tests using it are ignored unless run with
+/// `cargo test -- --ignored`.
+async fn database() -> MySqlPool {
+    let url = std::env::var("TEST_DATABASE_URL")
+        .expect("TEST_DATABASE_URL is not set, point it at a scratch database");
+    let pool = MySqlPool::connect(&url).await.unwrap();
+    migrate::check(&pool).await.unwrap();
+    pool
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn test_hostile_names_round_trip() {
+    let pool = database().await;
+    // nothing is committed, the rows go away with the transaction
+    let mut tx = pool.begin().await.unwrap();
+    let operator = "admin'--";
+    let new_brands: Vec<NewBrand> = HOSTILE_NAMES
+        .iter()
+        .map(|name| NewBrand {
+            name: name.to_string(),
+        })
+        .collect();
+    assert!(sql::cosmetics::is_brand_names_valid(&mut tx, &new_brands)
+        .await
+        .unwrap());
+    let brands: Vec<Brand> = HOSTILE_NAMES
+        .iter()
+        .enumerate()
+        .map(|(i, name)| Brand {
+            id: 0,
+            name: name.to_string(),
+            en_name: String::new(),
+            logo_url: String::new(),
+            country: String::new(),
+            description: String::new(),
+            website: String::new(),
+            sequence: i as i32 + 1,
+            is_hot: false,
+        })
+        .collect();
+    assert!(sql::cosmetics::create_brands(&mut tx, brands, operator)
+        .await
+        .unwrap());
+    assert!(sql::cosmetics::is_brand_names_valid(&mut tx, &new_brands)
+        .await
+        .is_err());
+
+    let mut qb = QueryBuilder::new("SELECT `name`, creator FROM brand WHERE `name` IN ");
+    qb.push_in(HOSTILE_NAMES.iter().copied())
+        .push(" ORDER BY `sequence`");
+    let rows = qb.build().fetch_all(&mut tx).await.unwrap();
+    let names: Vec<String> = rows.iter().map(|row| row.get(0)).collect();
+    assert_eq!(names, HOSTILE_NAMES);
+    assert!(rows.iter().all(|row| row.get::<String, _>(1) == operator));
+
+    let list = NewHotList {
+        slot: DEFAULT_HOT_SLOT.to_owned(),
+        product_ids: vec![],
+        start_at: None,
+        end_at: None,
+    };
+    let list_id = sql::cosmetics::create_hot_list(&mut tx, &list, operator)
+        .await
+        .unwrap();
+    assert!(
+        sql::cosmetics::create_hot_products(&mut tx, list_id, vec![3, 1, 2], operator)
+            .await
+            .unwrap()
+    );
+    let mut qb = QueryBuilder::new("SELECT product_id, creator FROM hot_product WHERE list_id = ");
+    qb.push_bind(list_id).push(" ORDER BY `sequence`");
+    let rows = qb.build().fetch_all(&mut tx).await.unwrap();
+    let ids: Vec<u64> = rows.iter().map(|row| row.get(0)).collect();
+    assert_eq!(ids, vec![3, 1, 2]);
+    assert!(rows.iter().all(|row| row.get::<String, _>(1) == operator));
+    tx.rollback().await.unwrap();
+}